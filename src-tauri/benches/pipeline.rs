@@ -0,0 +1,137 @@
+// Dissection throughput over a synthetic pcap, single threaded vs the worker pipeline.
+// Run with `cargo bench --bench pipeline`
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use metal_sniffer_app_lib::utils::custom_packet::MyPacket;
+use metal_sniffer_app_lib::utils::decode_as::DecodeAsTable;
+use metal_sniffer_app_lib::utils::packet_store::packet_record;
+use metal_sniffer_app_lib::utils::pipeline::{Pipeline, PipelineConfig, RawFrame};
+use pcap::Capture;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FRAMES: usize = 20_000;
+
+fn checksum_ipv4(header: &[u8]) -> u16 {
+    let mut sum: u32 = header.chunks(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as u32).sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+// Ethernet + IPv4 + UDP or TCP around `payload`
+fn frame(index: usize, udp: bool, dst_port: u16, payload: &[u8]) -> Vec<u8> {
+    let transport_len = if udp { 8 } else { 20 };
+    let ip_len = 20 + transport_len + payload.len();
+    let mut frame = Vec::with_capacity(14 + ip_len);
+    frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x01, 0x02, 0, 0, 0, 0, 0x02, 0x08, 0x00]);
+
+    let mut ip = vec![0x45, 0, 0, 0, 0, 0, 0x40, 0, 64, if udp { 17 } else { 6 }, 0, 0];
+    ip[2..4].copy_from_slice(&(ip_len as u16).to_be_bytes());
+    ip.extend_from_slice(&[10, 0, (index >> 8) as u8, index as u8]);
+    ip.extend_from_slice(&[192, 168, 1, 1]);
+    let checksum = checksum_ipv4(&ip);
+    ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+    frame.extend_from_slice(&ip);
+
+    let src_port = 40000 + (index % 20000) as u16;
+    frame.extend_from_slice(&src_port.to_be_bytes());
+    frame.extend_from_slice(&dst_port.to_be_bytes());
+    if udp {
+        frame.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+    } else {
+        frame.extend_from_slice(&(index as u32).to_be_bytes());
+        frame.extend_from_slice(&[0, 0, 0, 0, 0x50, 0x18, 0xff, 0xff, 0, 0, 0, 0]);
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+// A mix of DNS, DHCP and HTTP(S) sized frames written as a classic pcap file
+fn synthetic_pcap(path: &Path) {
+    let dns_query = b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01";
+    let mut dhcp = vec![0u8; 240];
+    dhcp[0] = 1;
+    dhcp[1] = 1;
+    dhcp[2] = 6;
+    dhcp[236..240].copy_from_slice(&[99, 130, 83, 99]);
+    dhcp.extend_from_slice(&[53, 1, 1, 12, 4, b'l', b'a', b'b', b'1', 255]);
+    let http = b"GET / HTTP/1.1\r\nHost: example.com\r\nUser-Agent: bench\r\n\r\n".to_vec();
+    let tls = vec![0x17u8; 1200];
+
+    let mut pcap = Vec::new();
+    pcap.extend_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+    pcap.extend_from_slice(&2u16.to_le_bytes());
+    pcap.extend_from_slice(&4u16.to_le_bytes());
+    pcap.extend_from_slice(&[0; 8]);
+    pcap.extend_from_slice(&65535u32.to_le_bytes());
+    pcap.extend_from_slice(&1u32.to_le_bytes());
+
+    for index in 0..FRAMES {
+        let data = match index % 4 {
+            0 => frame(index, true, 53, dns_query),
+            1 => frame(index, true, 67, &dhcp),
+            2 => frame(index, false, 80, &http),
+            _ => frame(index, false, 443, &tls),
+        };
+        pcap.extend_from_slice(&(1_700_000_000u32 + (index / 1000) as u32).to_le_bytes());
+        pcap.extend_from_slice(&((index % 1000) as u32 * 1000).to_le_bytes());
+        pcap.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        pcap.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        pcap.extend_from_slice(&data);
+    }
+    fs::write(path, pcap).expect("failed to write synthetic pcap");
+}
+
+fn single_thread(path: &Path) -> usize {
+    let decode_as = DecodeAsTable::default();
+    let mut capture = Capture::from_file(path).unwrap();
+    let mut dissected = 0;
+    while let Ok(packet) = capture.next_packet() {
+        let ts = packet.header.ts;
+        if let Ok(custom_packet) = MyPacket::from_frame(packet.data, &decode_as) {
+            packet_record(&custom_packet, ts.tv_sec as i64, ts.tv_usec as u32);
+            dissected += 1;
+        }
+    }
+    dissected
+}
+
+fn pipeline(path: &Path, workers: usize) -> usize {
+    let config = PipelineConfig { workers, ..PipelineConfig::default() };
+    let (mut pipeline, ordered) = Pipeline::start(config, |frame: &RawFrame| {
+        let custom_packet = MyPacket::from_frame(&frame.data, &DecodeAsTable::default())?;
+        Ok(packet_record(&custom_packet, frame.ts_sec, frame.ts_usec))
+    });
+    let consumer = std::thread::spawn(move || ordered.filter(|frame| frame.packet.is_ok()).count());
+
+    let mut capture = Capture::from_file(path).unwrap();
+    while let Ok(packet) = capture.next_packet() {
+        let ts = packet.header.ts;
+        pipeline.submit_blocking(ts.tv_sec as i64, ts.tv_usec as u32, packet.header.len, packet.data.to_vec());
+    }
+    pipeline.finish();
+    consumer.join().unwrap()
+}
+
+fn bench_dissection(c: &mut Criterion) {
+    let path: PathBuf = std::env::temp_dir().join("metal-sniffer-bench.pcap");
+    synthetic_pcap(&path);
+
+    let mut group = c.benchmark_group("dissect");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    group.sample_size(10);
+    group.bench_function("single_thread", |b| b.iter(|| single_thread(&path)));
+    for workers in [1, 2, 4, 8] {
+        group.bench_with_input(BenchmarkId::new("pipeline", workers), &workers, |b, &workers| {
+            b.iter(|| pipeline(&path, workers))
+        });
+    }
+    group.finish();
+
+    let _ = fs::remove_file(&path);
+}
+
+criterion_group!(benches, bench_dissection);
+criterion_main!(benches);
@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+use std::io::Error;
+use std::net::IpAddr;
+use serde::Serialize;
+use pnet::packet::Packet;
+use pnet::packet::{ipv4::Ipv4Packet, ipv6::Ipv6Packet, udp::UdpPacket, tcp::TcpPacket, icmpv6::Icmpv6Packet};
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::tcp::TcpFlags;
+use pnet::packet::ethernet::{EtherType, EthernetPacket, EtherTypes};
+use crate::field_tree::Field;
+use crate::layers::packet_info::PacketInfo;
+
+use super::decode_as::{DecodeAsTable, Transport};
+use super::layers::{layer1::*, layer2::*, layer3::*, quic};
+use super::link::{self, LinkType};
+
+// Where a layer's header (or the application payload) sits in the original frame
+#[derive(Debug, Clone, Serialize)]
+pub struct LayerRange {
+    pub layer: String,
+    pub protocol: String,
+    pub offset: usize,
+    pub length: usize,
+}
+
+// A decoded field's position in the original frame, for linking the field tree to the hex view
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldOffset {
+    pub layer: String,
+    pub field: String,
+    pub offset: usize,
+    pub length: usize,
+}
+
+/// A decoded TCP segment, borrowed from the packet it came from
+#[derive(Debug, Clone, Copy)]
+pub struct TcpSegment<'a> {
+    pub seq: u32,
+    // All flag bits as they appear in the header
+    pub flags: u8,
+    pub syn: bool,
+    pub fin: bool,
+    pub rst: bool,
+    pub payload: &'a [u8],
+}
+
+/// A dissected frame: network layer (IPv4/IPv6), transport layer and, when
+/// there is one, the application payload classified by port or "decode as" rule
+pub struct MyPacket {
+    link : LinkType,
+    layer_1 : LayerOne,
+    layer_2 : LayerTwo,
+    layer_3 : Option<LayerThree>
+}
+
+fn malformed(message: &str) -> Error {
+    Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+impl MyPacket {
+    pub fn new(layer_1_packet : &EthernetPacket) -> Result<MyPacket, Error> {
+        MyPacket::new_with_decode_as(layer_1_packet, &DecodeAsTable::default())
+    }
+
+    /// Dissects a raw Ethernet frame as it came off the wire or out of a pcap file
+    pub fn from_frame(data: &[u8], decode_as: &DecodeAsTable) -> Result<MyPacket, Error> {
+        link::decode(LinkType::Ethernet, data, decode_as)
+    }
+
+    // Same as new, but user "decode as" rules win over the well known port tables
+    pub fn new_with_decode_as(layer_1_packet : &EthernetPacket, decode_as: &DecodeAsTable) -> Result<MyPacket, Error> {
+        MyPacket::from_network(LinkType::Ethernet, layer_1_packet.get_ethertype(), layer_1_packet.payload(), decode_as)
+    }
+
+    /// Dissects the network layer payload of a frame whose link header has already been
+    /// removed, `ethertype` says what the payload is
+    pub fn from_network(link: LinkType, ethertype: EtherType, payload: &[u8], decode_as: &DecodeAsTable) -> Result<MyPacket, Error> {
+        // Creating the first layer of the network packet: IE. IP4 || IP6
+        let layer_1: LayerOne = match ethertype {
+            EtherTypes::Ipv4 => {
+                let v4_packet = Ipv4Packet::new(payload).ok_or_else(|| malformed("Truncated IPv4 header"))?;
+                LayerOne::V4(Vec::from(v4_packet.packet()))
+            },
+            EtherTypes::Ipv6 => {
+                let v6_packet = Ipv6Packet::new(payload).ok_or_else(|| malformed("Truncated IPv6 header"))?;
+                LayerOne::V6(Vec::from(v6_packet.packet()))
+            }
+            _ => return Err(Error::new(std::io::ErrorKind::Unsupported, "Unsupported LayerOne protocol")),
+        };
+
+        // Creating the second layer of the network packet IE. UDP, TCP...
+        let layer_2: LayerTwo = match &layer_1 {
+            // Checking if the layer 1 is a v4 or v6, and updating the layer 2 values
+            LayerOne::V4(v4_packet) => {
+                // We can safely unwrap, because it has already been handled at level 1
+                let v4_packet: Ipv4Packet<'_> = Ipv4Packet::new(v4_packet).unwrap();
+                match v4_packet.get_next_level_protocol() {
+                    IpNextHeaderProtocols::Udp => {
+                        let udp_packet: UdpPacket<'_> = UdpPacket::new(v4_packet.payload()).ok_or_else(|| malformed("Truncated UDP header"))?;
+                        LayerTwo::UDP(Vec::from(udp_packet.packet()))
+                    },
+                    IpNextHeaderProtocols::Tcp => {
+                        let tcp_packet: TcpPacket<'_> = TcpPacket::new(v4_packet.payload()).ok_or_else(|| malformed("Truncated TCP header"))?;
+                        LayerTwo::TCP(Vec::from(tcp_packet.packet()))
+                    },
+                    // Add more protocols
+                    _ => return Err(Error::new(std::io::ErrorKind::Unsupported, "Unsupported LayerTwo protocol")),
+                }
+            }
+            LayerOne::V6(v6_packet) => {
+                // // We can safely unwrap, because it has already been handled at level 1
+                let v6_packet: Ipv6Packet<'_> = Ipv6Packet::new(v6_packet).unwrap();
+                match v6_packet.get_next_header() {
+                    IpNextHeaderProtocols::Udp => {
+                        let udp_packet: UdpPacket<'_> = UdpPacket::new(v6_packet.payload()).ok_or_else(|| malformed("Truncated UDP header"))?;
+                        LayerTwo::UDP(Vec::from(udp_packet.packet()))
+                    },
+                    IpNextHeaderProtocols::Tcp => {
+                        let tcp_packet: TcpPacket<'_> = TcpPacket::new(v6_packet.payload()).ok_or_else(|| malformed("Truncated TCP header"))?;
+                        LayerTwo::TCP(Vec::from(tcp_packet.packet()))
+                    },
+                    IpNextHeaderProtocols::Icmpv6 => {
+                        let tcp_packet: Icmpv6Packet<'_> = Icmpv6Packet::new(v6_packet.payload()).ok_or_else(|| malformed("Truncated ICMPv6 header"))?;
+                        LayerTwo::ICMPV6(Vec::from(tcp_packet.packet()))
+
+                    }
+                    _ => return Err(Error::new(std::io::ErrorKind::Unsupported, "Unsupported LayerTwo protocol")),
+                }
+            }
+        };
+        let (src_ip, dst_ip) = MyPacket::endpoints(&layer_1);
+        let layer_3: Option<LayerThree> = match &layer_2 {
+            LayerTwo::UDP(udp_packet) => {
+                // We can safely unwrap, because it has already been handled at level 1
+                let udp_packet = UdpPacket::new(udp_packet).unwrap();
+                // Check for common ports this packet may be and convert
+                let src_port = udp_packet.get_source();
+                let dst_port = udp_packet.get_destination();
+                if let Some(dissector) = decode_as.lookup(Transport::UDP, (src_ip, src_port), (dst_ip, dst_port)) {
+                    Some(dissector.build(udp_packet.payload()))
+                } else {
+                    match (src_port, dst_port) {
+                        (53, _) | (_, 53) => Some(LayerThree::DNS(DNS {
+                            payload: Vec::from(udp_packet.payload())
+                        })),
+                        (5353, _) | (_, 5353) => Some(LayerThree::MDNS(MDNS {
+                            payload: Vec::from(udp_packet.payload())
+
+                        })),
+                        // DHCP servers listen on 67 and clients on 68
+                        (67, _) | (_, 67) | (68, _) | (_, 68) => Some(LayerThree::DHCP(DHCP {
+                            payload: Vec::from(udp_packet.payload())
+                        })),
+                        // DHCPv6 clients listen on 546 and servers/relays on 547
+                        (546, _) | (_, 546) | (547, _) | (_, 547) => Some(LayerThree::DHCPV6(DHCPV6 {
+                            payload: Vec::from(udp_packet.payload())
+                        })),
+                        // QUIC (and HTTP/3 on top of it) runs on UDP 443
+                        (443, _) | (_, 443) if quic::looks_like_quic(udp_packet.payload()) => Some(LayerThree::QUIC(QUIC {
+                            payload: Vec::from(udp_packet.payload())
+                        })),
+                        // Default to Raw if no match
+                        _ => Some(LayerThree::Raw(Raw {
+                            payload: Vec::from(udp_packet.payload())
+                        })),
+                    }
+                }
+            },
+            LayerTwo::TCP(tcp_packet) => {
+                // We can safely unwrap, because it has already been handled at level 1
+                let tcp_packet: TcpPacket<'_> = TcpPacket::new(tcp_packet).unwrap();
+                let src_port = tcp_packet.get_source();
+                let dst_port = tcp_packet.get_destination();
+                if let Some(dissector) = decode_as.lookup(Transport::TCP, (src_ip, src_port), (dst_ip, dst_port)) {
+                    Some(dissector.build(tcp_packet.payload()))
+                } else {
+                    match (src_port, dst_port) {
+                        // HTTP typically uses port 80
+                        (80, _) | (_, 80) => Some(LayerThree::HTTP(HTTP {
+                            payload: Vec::from(tcp_packet.payload())
+                        })),
+                    
+                        // HTTPS typically uses port 443
+                        (443, _) | (_, 443) => Some(LayerThree::HTTPS(HTTPS {
+                            payload: Vec::from(tcp_packet.payload())
+                        })),
+                    
+                        // Add other TCP-based protocols as needed
+
+                        // Default to Raw if no match
+                        _ => Some(LayerThree::Raw(Raw {
+                            payload: Vec::from(tcp_packet.payload())
+                        })),
+                    }
+                }
+            },
+            LayerTwo::ICMPV6(icmpv6_packet) => {
+                // We can safely unwrap, because it has already been handled at level 1
+                let icmpv6_packet: Icmpv6Packet<'_> = Icmpv6Packet::new(icmpv6_packet).unwrap();
+                Some(LayerThree::Raw(Raw {
+                    payload: Vec::from(icmpv6_packet.payload())
+                }))
+
+            },
+        };
+
+        Ok(MyPacket {
+            link,
+            layer_1,
+            layer_2,
+            layer_3
+        })
+            }
+
+    /// Link type of the frame this packet was decoded from
+    pub fn link_type(&self) -> LinkType {
+        self.link
+    }
+
+    /// Source and destination IP addresses
+    pub fn addresses(&self) -> (IpAddr, IpAddr) {
+        MyPacket::endpoints(&self.layer_1)
+    }
+
+    /// Source and destination ports, ICMPv6 has none
+    pub fn ports(&self) -> Option<(u16, u16)> {
+        match &self.layer_2 {
+            LayerTwo::TCP(tcp_packet) => TcpPacket::new(tcp_packet).map(|tcp| (tcp.get_source(), tcp.get_destination())),
+            LayerTwo::UDP(udp_packet) => UdpPacket::new(udp_packet).map(|udp| (udp.get_source(), udp.get_destination())),
+            LayerTwo::ICMPV6(_) => None,
+        }
+    }
+
+    /// TCP or UDP, `None` for ICMPv6
+    pub fn transport(&self) -> Option<Transport> {
+        match &self.layer_2 {
+            LayerTwo::TCP(_) => Some(Transport::TCP),
+            LayerTwo::UDP(_) => Some(Transport::UDP),
+            LayerTwo::ICMPV6(_) => None,
+        }
+    }
+
+    /// Sequence number, flags and payload when the transport is TCP
+    pub fn tcp_segment(&self) -> Option<TcpSegment<'_>> {
+        let LayerTwo::TCP(tcp_packet) = &self.layer_2 else {
+            return None;
+        };
+        let tcp = TcpPacket::new(tcp_packet)?;
+        let flags = tcp.get_flags();
+        let header_len = (tcp.get_data_offset() as usize * 4).min(tcp_packet.len());
+        Some(TcpSegment {
+            seq: tcp.get_sequence(),
+            flags,
+            syn: flags & TcpFlags::SYN != 0,
+            fin: flags & TcpFlags::FIN != 0,
+            rst: flags & TcpFlags::RST != 0,
+            payload: &tcp_packet[header_len..],
+        })
+    }
+
+    /// Protocol names from the link layer up, e.g. `["Ethernet", "IPv4", "TCP", "HTTPS"]`.
+    /// Payload nothing claimed is `Data`; an empty payload (a bare TCP ACK) adds nothing
+    pub fn protocols(&self) -> Vec<&'static str> {
+        let network = match &self.layer_1 {
+            LayerOne::V4(_) => "IPv4",
+            LayerOne::V6(_) => "IPv6",
+        };
+        let transport = match &self.layer_2 {
+            LayerTwo::TCP(_) => "TCP",
+            LayerTwo::UDP(_) => "UDP",
+            LayerTwo::ICMPV6(_) => "ICMPv6",
+        };
+        let mut protocols = vec![self.link.name(), network, transport];
+        match &self.layer_3 {
+            Some(layer_3) if layer_3.payload().is_empty() => {}
+            Some(LayerThree::Raw(_)) => protocols.push("Data"),
+            Some(layer_3) => protocols.push(layer_3.display_name()),
+            None => {}
+        }
+        protocols
+    }
+
+    // Source and destination addresses of the network layer
+    fn endpoints(layer_1: &LayerOne) -> (IpAddr, IpAddr) {
+        match layer_1 {
+            LayerOne::V4(v4_packet) => {
+                let v4_packet = Ipv4Packet::new(v4_packet).unwrap();
+                (IpAddr::V4(v4_packet.get_source()), IpAddr::V4(v4_packet.get_destination()))
+            }
+            LayerOne::V6(v6_packet) => {
+                let v6_packet = Ipv6Packet::new(v6_packet).unwrap();
+                (IpAddr::V6(v6_packet.get_source()), IpAddr::V6(v6_packet.get_destination()))
+            }
+        }
+    }
+}
+
+pub trait CustomPacket {
+    fn get_network(&self) -> LayerOne;
+    fn get_transport(&self) -> LayerTwo;
+    fn get_application(&self) -> Option<LayerThree>;
+    fn get_packet_info(&self) ->  HashMap<String, HashMap<String, String>>;
+    fn get_layer_ranges(&self) -> Vec<LayerRange>;
+    fn get_field_offsets(&self) -> Vec<FieldOffset>;
+    fn get_field_tree(&self) -> Vec<Field>;
+}
+
+impl CustomPacket for MyPacket {
+    fn get_network(&self) -> LayerOne {
+        self.layer_1.clone()
+    }
+    
+    fn get_transport(&self) -> LayerTwo {
+        self.layer_2.clone()
+    }
+    
+    fn get_application(&self) -> Option<LayerThree> {
+        self.layer_3.clone()
+    }
+    
+    fn get_packet_info(&self) -> HashMap<String, HashMap<String, String>> {
+        let mut packet_info: HashMap<String, HashMap<String, String>> = HashMap::new();
+    
+        // Add Layer 1 information
+        packet_info.insert("Layer 1".to_string(), self.layer_1.get_info());
+    
+        // Add Layer 2 information
+        packet_info.insert("Layer 2".to_string(), self.layer_2.get_info());
+    
+        // Add Layer 3 information if available
+        if let Some(layer_3) = &self.layer_3 {
+            packet_info.insert("Layer 3".to_string(), layer_3.get_info());
+        }
+    
+        
+        packet_info
+    }
+
+    fn get_layer_ranges(&self) -> Vec<LayerRange> {
+        let mut ranges = vec![LayerRange {
+            layer: self.link.name().to_string(),
+            protocol: self.link.name().to_string(),
+            offset: 0,
+            length: self.link.header_len(),
+        }];
+
+        // We can safely unwrap, these were all validated in MyPacket::new
+        let (network, network_len) = match &self.layer_1 {
+            LayerOne::V4(v4_packet) => ("IPv4", Ipv4Packet::new(v4_packet).unwrap().get_header_length() as usize * 4),
+            LayerOne::V6(_) => ("IPv6", 40),
+        };
+        let (transport, transport_len) = match &self.layer_2 {
+            LayerTwo::TCP(tcp_packet) => ("TCP", TcpPacket::new(tcp_packet).unwrap().get_data_offset() as usize * 4),
+            LayerTwo::UDP(_) => ("UDP", 8),
+            LayerTwo::ICMPV6(_) => ("ICMPv6", 4),
+        };
+
+        let mut offset = self.link.header_len();
+        ranges.push(LayerRange { layer: "Layer 1".to_string(), protocol: network.to_string(), offset, length: network_len });
+        offset += network_len;
+        ranges.push(LayerRange { layer: "Layer 2".to_string(), protocol: transport.to_string(), offset, length: transport_len });
+        offset += transport_len;
+
+        if let Some(layer_3) = &self.layer_3 {
+            let info = layer_3.get_info();
+            ranges.push(LayerRange {
+                layer: "Layer 3".to_string(),
+                protocol: info.get("Protocol").cloned().unwrap_or_default(),
+                offset,
+                length: layer_3.payload_len(),
+            });
+        }
+        ranges
+    }
+
+    // One root per layer, with offsets relative to the start of the frame
+    fn get_field_tree(&self) -> Vec<Field> {
+        let mut trees = vec![self.layer_1.get_field_tree(), self.layer_2.get_field_tree()];
+        if let Some(layer_3) = &self.layer_3 {
+            trees.push(layer_3.get_field_tree());
+        }
+
+        // Layer ranges come in link, Layer 1, Layer 2, Layer 3 order
+        let ranges = self.get_layer_ranges();
+        for (tree, range) in trees.iter_mut().zip(ranges.iter().skip(1)) {
+            tree.shift(range.offset);
+            tree.offset = Some(range.offset);
+            tree.length = Some(range.length);
+        }
+        trees
+    }
+
+    fn get_field_offsets(&self) -> Vec<FieldOffset> {
+        let mut spans = vec![
+            ("Layer 1", self.layer_1.get_field_spans()),
+            ("Layer 2", self.layer_2.get_field_spans()),
+        ];
+        if let Some(layer_3) = &self.layer_3 {
+            spans.push(("Layer 3", layer_3.get_field_spans()));
+        }
+
+        // Layer spans are relative to the layer, shift them by where the layer starts in the frame
+        let ranges = self.get_layer_ranges();
+        let mut offsets = Vec::new();
+        for (layer, layer_spans) in spans {
+            let Some(range) = ranges.iter().find(|range| range.layer == layer) else {
+                continue;
+            };
+            offsets.extend(layer_spans.into_iter().map(|span| FieldOffset {
+                layer: layer.to_string(),
+                field: span.field,
+                offset: range.offset + span.offset,
+                length: span.length,
+            }));
+        }
+        offsets
+    }
+}
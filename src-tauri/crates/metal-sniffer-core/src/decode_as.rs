@@ -0,0 +1,240 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+
+use super::layers::layer3::*;
+
+/// The application layer dissectors a port or flow can be forced to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Dissector {
+    DNS,
+    MDNS,
+    HTTP,
+    HTTPS,
+    QUIC,
+    DHCP,
+    DHCPV6,
+    Raw,
+}
+
+impl Dissector {
+    /// Wraps a UDP/TCP payload in this dissector's application layer
+    pub fn build(&self, payload: &[u8]) -> LayerThree {
+        let payload = Vec::from(payload);
+        match self {
+            Dissector::DNS => LayerThree::DNS(DNS { payload }),
+            Dissector::MDNS => LayerThree::MDNS(MDNS { payload }),
+            Dissector::HTTP => LayerThree::HTTP(HTTP { payload }),
+            Dissector::HTTPS => LayerThree::HTTPS(HTTPS { payload }),
+            Dissector::QUIC => LayerThree::QUIC(QUIC { payload }),
+            Dissector::DHCP => LayerThree::DHCP(DHCP { payload }),
+            Dissector::DHCPV6 => LayerThree::DHCPV6(DHCPV6 { payload }),
+            Dissector::Raw => LayerThree::Raw(Raw { payload }),
+        }
+    }
+}
+
+/// Transport a decode as rule applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Transport {
+    TCP,
+    UDP,
+}
+
+/// A conversation between two endpoints, matched in either direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Flow {
+    pub a_ip: IpAddr,
+    pub a_port: u16,
+    pub b_ip: IpAddr,
+    pub b_port: u16,
+}
+
+impl Flow {
+    fn matches(&self, src: (IpAddr, u16), dst: (IpAddr, u16)) -> bool {
+        let a = (self.a_ip, self.a_port);
+        let b = (self.b_ip, self.b_port);
+        (src == a && dst == b) || (src == b && dst == a)
+    }
+}
+
+/// Either a single port (source or destination) or a specific flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DecodeAsTarget {
+    Port(u16),
+    Flow(Flow),
+}
+
+/// Decodes traffic on `target` over `transport` with `dissector`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecodeAsRule {
+    pub transport: Transport,
+    pub target: DecodeAsTarget,
+    pub dissector: Dissector,
+}
+
+/// The user's decode as rules, persisted as JSON
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DecodeAsTable {
+    rules: Vec<DecodeAsRule>,
+}
+
+impl DecodeAsTable {
+    /// Reads the rules saved at `path`, an empty table when the file does not exist
+    pub fn load(path: &Path) -> Result<DecodeAsTable, String> {
+        if !path.exists() {
+            return Ok(DecodeAsTable::default());
+        }
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read decode as config: {}", e))?;
+        serde_json::from_str(&contents)
+            .map_err(|e| format!("Invalid decode as config: {}", e))
+    }
+
+    /// Writes the rules to `path`, creating its directory if needed
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize decode as config: {}", e))?;
+        fs::write(path, contents).map_err(|e| format!("Failed to write decode as config: {}", e))
+    }
+
+    /// Rules in the order they were added
+    pub fn rules(&self) -> &[DecodeAsRule] {
+        &self.rules
+    }
+
+    /// Adds a rule, replacing any existing rule for the same transport and target
+    pub fn set(&mut self, rule: DecodeAsRule) {
+        self.remove(rule.transport, rule.target);
+        self.rules.push(rule);
+    }
+
+    /// Removes the rule for `transport` and `target`, true if there was one
+    pub fn remove(&mut self, transport: Transport, target: DecodeAsTarget) -> bool {
+        let before = self.rules.len();
+        self.rules
+            .retain(|rule| !(rule.transport == transport && rule.target == target));
+        before != self.rules.len()
+    }
+
+    /// Flow rules are more specific than port rules, so they are checked first
+    pub fn lookup(
+        &self,
+        transport: Transport,
+        src: (IpAddr, u16),
+        dst: (IpAddr, u16),
+    ) -> Option<Dissector> {
+        let candidates = || self.rules.iter().filter(|rule| rule.transport == transport);
+
+        candidates()
+            .find(|rule| matches!(rule.target, DecodeAsTarget::Flow(flow) if flow.matches(src, dst)))
+            .or_else(|| {
+                candidates().find(|rule| {
+                    matches!(rule.target, DecodeAsTarget::Port(port) if port == src.1 || port == dst.1)
+                })
+            })
+            .map(|rule| rule.dissector)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_packet::CustomPacket;
+    use crate::link::{self, LinkType};
+
+    const CLIENT: ([u8; 4], u16) = ([10, 0, 0, 2], 40000);
+    const SERVER: ([u8; 4], u16) = ([10, 0, 0, 1], 5353);
+
+    fn ip(address: [u8; 4]) -> IpAddr {
+        IpAddr::from(address)
+    }
+
+    // A raw IPv4/UDP datagram with an 8 byte payload
+    fn datagram(src: ([u8; 4], u16), dst: ([u8; 4], u16)) -> Vec<u8> {
+        let mut frame = vec![0x45, 0, 0, 36, 0, 0, 0, 0, 64, 17, 0, 0];
+        frame.extend_from_slice(&src.0);
+        frame.extend_from_slice(&dst.0);
+        frame.extend_from_slice(&src.1.to_be_bytes());
+        frame.extend_from_slice(&dst.1.to_be_bytes());
+        frame.extend_from_slice(&[0, 16, 0, 0]);
+        frame.extend_from_slice(&[0; 8]);
+        frame
+    }
+
+    fn dissected_as(table: &DecodeAsTable) -> LayerThree {
+        let packet = link::decode(LinkType::Raw, &datagram(CLIENT, SERVER), table).unwrap();
+        packet.get_application().unwrap()
+    }
+
+    fn port_rule(port: u16, dissector: Dissector) -> DecodeAsRule {
+        DecodeAsRule { transport: Transport::UDP, target: DecodeAsTarget::Port(port), dissector }
+    }
+
+    fn flow_rule(dissector: Dissector) -> DecodeAsRule {
+        let flow = Flow { a_ip: ip(SERVER.0), a_port: SERVER.1, b_ip: ip(CLIENT.0), b_port: CLIENT.1 };
+        DecodeAsRule { transport: Transport::UDP, target: DecodeAsTarget::Flow(flow), dissector }
+    }
+
+    #[test]
+    fn built_in_ports_without_rules() {
+        assert!(matches!(dissected_as(&DecodeAsTable::default()), LayerThree::MDNS(_)));
+    }
+
+    #[test]
+    fn port_rule_wins_over_built_in_ports() {
+        let mut table = DecodeAsTable::default();
+        table.set(port_rule(5353, Dissector::DNS));
+        assert!(matches!(dissected_as(&table), LayerThree::DNS(_)));
+
+        // Either end of the conversation can match
+        let mut table = DecodeAsTable::default();
+        table.set(port_rule(40000, Dissector::Raw));
+        assert!(matches!(dissected_as(&table), LayerThree::Raw(_)));
+    }
+
+    #[test]
+    fn flow_rule_wins_over_port_rule() {
+        // Whichever was added first
+        let mut table = DecodeAsTable::default();
+        table.set(port_rule(5353, Dissector::DNS));
+        table.set(flow_rule(Dissector::QUIC));
+        assert!(matches!(dissected_as(&table), LayerThree::QUIC(_)));
+
+        let mut table = DecodeAsTable::default();
+        table.set(flow_rule(Dissector::QUIC));
+        table.set(port_rule(5353, Dissector::DNS));
+        assert!(matches!(dissected_as(&table), LayerThree::QUIC(_)));
+
+        // A flow rule for another conversation leaves the port rule in charge
+        let other = ([10, 0, 0, 3], 40000);
+        let packet = link::decode(LinkType::Raw, &datagram(other, SERVER), &table).unwrap();
+        assert!(matches!(packet.get_application(), Some(LayerThree::DNS(_))));
+    }
+
+    #[test]
+    fn rules_apply_to_their_transport_only() {
+        let mut table = DecodeAsTable::default();
+        table.set(DecodeAsRule { transport: Transport::TCP, ..port_rule(5353, Dissector::HTTP) });
+        assert!(matches!(dissected_as(&table), LayerThree::MDNS(_)));
+        assert_eq!(table.lookup(Transport::TCP, (ip(CLIENT.0), CLIENT.1), (ip(SERVER.0), SERVER.1)), Some(Dissector::HTTP));
+    }
+
+    #[test]
+    fn set_replaces_and_remove_forgets() {
+        let mut table = DecodeAsTable::default();
+        table.set(port_rule(5353, Dissector::DNS));
+        table.set(port_rule(5353, Dissector::Raw));
+        assert_eq!(table.rules(), [port_rule(5353, Dissector::Raw)]);
+
+        assert!(table.remove(Transport::UDP, DecodeAsTarget::Port(5353)));
+        assert!(!table.remove(Transport::UDP, DecodeAsTarget::Port(5353)));
+        assert!(matches!(dissected_as(&table), LayerThree::MDNS(_)));
+    }
+}
@@ -0,0 +1,231 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use super::layers::dhcp;
+use super::layers::layer3::LayerThree;
+
+/// The last message seen for a lease
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum LeaseState {
+    Offered,
+    Bound,
+    Declined,
+}
+
+/// A MAC -> IP binding as seen on the wire, keyed by client MAC (or DUID when
+/// a DHCPv6 client does not embed its link layer address)
+#[derive(Debug, Clone, Serialize)]
+pub struct DhcpLease {
+    pub client: String,
+    pub ip: IpAddr,
+    pub state: LeaseState,
+    pub hostname: Option<String>,
+    pub server: Option<String>,
+    pub lease_time: Option<u32>,
+    pub first_seen: i64,
+    pub last_seen: i64,
+}
+
+// DHCPv6 clients are keyed by their MAC when the DUID carries one
+fn v6_client(duid: &[u8]) -> String {
+    dhcp::duid_mac(duid).unwrap_or_else(|| dhcp::duid_string(duid))
+}
+
+/// Leases seen in DHCP and DHCPv6 traffic, one per client and address. A release ends the
+/// binding, so released leases are dropped
+#[derive(Debug, Default)]
+pub struct LeaseTable {
+    leases: HashMap<(String, IpAddr), DhcpLease>,
+}
+
+impl LeaseTable {
+    /// Feeds a dissected application layer into the table; timestamps are unix seconds
+    pub fn observe(&mut self, layer_3: &LayerThree, timestamp: i64) {
+        match layer_3 {
+            LayerThree::DHCP(payload) => {
+                if let Some(message) = dhcp::parse_v4(&payload.payload) {
+                    self.observe_v4(&message, timestamp);
+                }
+            }
+            LayerThree::DHCPV6(payload) => {
+                if let Some(message) = dhcp::parse_v6(&payload.payload) {
+                    self.observe_v6(&message, timestamp);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn observe_v4(&mut self, message: &dhcp::DhcpV4Message, timestamp: i64) {
+        let (ip, state) = match message.message_type {
+            Some(2) => (message.your_ip, LeaseState::Offered),
+            Some(5) => (message.your_ip, LeaseState::Bound),
+            // RELEASE carries the address in ciaddr, DECLINE in the requested IP option
+            Some(7) => {
+                self.leases.remove(&(message.client_mac.clone(), IpAddr::V4(message.client_ip)));
+                return;
+            }
+            Some(4) => match message.requested_ip {
+                Some(ip) => (ip, LeaseState::Declined),
+                None => return,
+            },
+            _ => return,
+        };
+        if ip.is_unspecified() {
+            return;
+        }
+
+        self.update(
+            message.client_mac.clone(),
+            IpAddr::V4(ip),
+            state,
+            message.hostname.clone(),
+            message.server_identifier.map(|server| server.to_string()),
+            message.lease_time,
+            timestamp,
+        );
+    }
+
+    fn observe_v6(&mut self, message: &dhcp::DhcpV6Message, timestamp: i64) {
+        let state = match message.message_type {
+            2 => LeaseState::Offered,
+            7 => LeaseState::Bound,
+            9 => LeaseState::Declined,
+            8 => {
+                if let Some(duid) = &message.client_duid {
+                    let client = v6_client(duid);
+                    for address in &message.addresses {
+                        self.leases.remove(&(client.clone(), IpAddr::V6(address.address)));
+                    }
+                }
+                return;
+            }
+            _ => return,
+        };
+        let Some(duid) = &message.client_duid else {
+            return;
+        };
+        let client = v6_client(duid);
+        let server = message.server_duid.as_ref().map(|duid| dhcp::duid_string(duid));
+
+        for address in &message.addresses {
+            self.update(
+                client.clone(),
+                IpAddr::V6(address.address),
+                state,
+                message.hostname.clone(),
+                server.clone(),
+                Some(address.valid_lifetime),
+                timestamp,
+            );
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn update(
+        &mut self,
+        client: String,
+        ip: IpAddr,
+        state: LeaseState,
+        hostname: Option<String>,
+        server: Option<String>,
+        lease_time: Option<u32>,
+        timestamp: i64,
+    ) {
+        let lease = self.leases.entry((client.clone(), ip)).or_insert_with(|| DhcpLease {
+            client,
+            ip,
+            state,
+            hostname: None,
+            server: None,
+            lease_time: None,
+            first_seen: timestamp,
+            last_seen: timestamp,
+        });
+
+        // An offer never downgrades a lease that has already been acknowledged
+        if !(state == LeaseState::Offered && lease.state == LeaseState::Bound) {
+            lease.state = state;
+        }
+        lease.hostname = hostname.or(lease.hostname.take());
+        lease.server = server.or(lease.server.take());
+        lease.lease_time = lease_time.or(lease.lease_time);
+        lease.last_seen = timestamp;
+    }
+
+    /// All leases, sorted by client then address
+    pub fn leases(&self) -> Vec<DhcpLease> {
+        let mut leases: Vec<DhcpLease> = self.leases.values().cloned().collect();
+        leases.sort_by(|a, b| a.client.cmp(&b.client).then(a.ip.cmp(&b.ip)));
+        leases
+    }
+
+    /// Forgets every lease, e.g. when a new capture starts
+    pub fn clear(&mut self) {
+        self.leases.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::layer3::DHCP;
+
+    const CLIENT_MAC: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+
+    // A DHCPv4 message from CLIENT_MAC, `ciaddr`/`yiaddr` set and `options` after the message type
+    fn dhcp(message_type: u8, client_ip: [u8; 4], your_ip: [u8; 4], options: &[u8]) -> LayerThree {
+        let mut payload = vec![0; 236];
+        payload[..3].copy_from_slice(&[if matches!(message_type, 2 | 5) { 2 } else { 1 }, 1, 6]);
+        payload[12..16].copy_from_slice(&client_ip);
+        payload[16..20].copy_from_slice(&your_ip);
+        payload[28..34].copy_from_slice(&CLIENT_MAC);
+        payload.extend_from_slice(&[0x63, 0x82, 0x53, 0x63, 53, 1, message_type]);
+        payload.extend_from_slice(options);
+        payload.push(255);
+        LayerThree::DHCP(DHCP { payload })
+    }
+
+    const SERVER_OPTIONS: &[u8] = &[54, 4, 10, 0, 0, 1, 51, 4, 0, 0, 0x0e, 0x10];
+
+    #[test]
+    fn an_exchange_binds_one_lease() {
+        let mut table = LeaseTable::default();
+        table.observe(&dhcp(1, [0; 4], [0; 4], &[12, 3, b'l', b'a', b'b']), 100);
+        assert!(table.leases().is_empty());
+
+        table.observe(&dhcp(2, [0; 4], [10, 0, 0, 50], SERVER_OPTIONS), 101);
+        assert_eq!(table.leases()[0].state, LeaseState::Offered);
+        table.observe(&dhcp(3, [0; 4], [0; 4], &[50, 4, 10, 0, 0, 50, 12, 3, b'l', b'a', b'b']), 102);
+        let ack = [SERVER_OPTIONS, &[12, 3, b'l', b'a', b'b']].concat();
+        table.observe(&dhcp(5, [0; 4], [10, 0, 0, 50], &ack), 103);
+
+        let leases = table.leases();
+        assert_eq!(leases.len(), 1);
+        let lease = &leases[0];
+        assert_eq!(lease.client, "00:11:22:33:44:55");
+        assert_eq!(lease.ip, IpAddr::from([10, 0, 0, 50]));
+        assert_eq!(lease.state, LeaseState::Bound);
+        assert_eq!(lease.hostname.as_deref(), Some("lab"));
+        assert_eq!(lease.server.as_deref(), Some("10.0.0.1"));
+        assert_eq!(lease.lease_time, Some(3600));
+        assert_eq!((lease.first_seen, lease.last_seen), (101, 103));
+
+        // A late retransmitted offer does not undo the ACK
+        table.observe(&dhcp(2, [0; 4], [10, 0, 0, 50], SERVER_OPTIONS), 104);
+        assert_eq!(table.leases()[0].state, LeaseState::Bound);
+    }
+
+    #[test]
+    fn a_release_removes_the_lease() {
+        let mut table = LeaseTable::default();
+        table.observe(&dhcp(5, [0; 4], [10, 0, 0, 50], SERVER_OPTIONS), 100);
+        table.observe(&dhcp(5, [0; 4], [10, 0, 0, 60], SERVER_OPTIONS), 100);
+
+        table.observe(&dhcp(7, [10, 0, 0, 50], [0; 4], &[54, 4, 10, 0, 0, 1]), 200);
+        let leases = table.leases();
+        assert_eq!(leases.len(), 1);
+        assert_eq!(leases[0].ip, IpAddr::from([10, 0, 0, 60]));
+    }
+}
@@ -0,0 +1,134 @@
+use std::cmp::Ordering;
+use std::net::IpAddr;
+
+use super::parser::{CompareOp, Expr, Literal, SetItem};
+use crate::field_tree::{Field, FieldValue};
+
+// Names that stand for "either direction", as in Wireshark
+fn aliases(name: &str) -> Option<&'static [&'static str]> {
+    match name {
+        "ip.addr" => Some(&["ip.src", "ip.dst"]),
+        "ipv6.addr" => Some(&["ipv6.src", "ipv6.dst"]),
+        "tcp.port" => Some(&["tcp.srcport", "tcp.dstport"]),
+        "udp.port" => Some(&["udp.srcport", "udp.dstport"]),
+        _ => None,
+    }
+}
+
+// Every occurrence of a field anywhere in the packet
+fn lookup<'a>(fields: &'a [Field], name: &str) -> Vec<&'a Field> {
+    let names: Vec<&str> = match aliases(name) {
+        Some(names) => names.to_vec(),
+        None => vec![name],
+    };
+    let mut found = Vec::new();
+    for root in fields {
+        root.walk(&mut |field| {
+            if names.contains(&field.name.as_str()) {
+                found.push(field);
+            }
+        });
+    }
+    found
+}
+
+pub fn evaluate(expr: &Expr, fields: &[Field]) -> bool {
+    match expr {
+        Expr::And(operands) => operands.iter().all(|operand| evaluate(operand, fields)),
+        Expr::Or(operands) => operands.iter().any(|operand| evaluate(operand, fields)),
+        Expr::Not(inner) => !evaluate(inner, fields),
+        Expr::Exists(name) => !lookup(fields, name).is_empty(),
+        // "a != b" holds when the field is present and no occurrence equals b
+        Expr::Compare(name, CompareOp::Ne, literal) => {
+            let found = lookup(fields, name);
+            !found.is_empty() && !found.iter().any(|field| equals(field, literal))
+        }
+        Expr::Compare(name, op, literal) => lookup(fields, name)
+            .iter()
+            .any(|field| compare(field, op, literal)),
+        Expr::In(name, items) => lookup(fields, name)
+            .iter()
+            .any(|field| items.iter().any(|item| in_set_item(field, item))),
+    }
+}
+
+fn compare(field: &Field, op: &CompareOp, literal: &Literal) -> bool {
+    match op {
+        CompareOp::Eq => equals(field, literal),
+        CompareOp::Ne => !equals(field, literal),
+        CompareOp::Gt => ordering(field, literal) == Some(Ordering::Greater),
+        CompareOp::Ge => matches!(ordering(field, literal), Some(Ordering::Greater | Ordering::Equal)),
+        CompareOp::Lt => ordering(field, literal) == Some(Ordering::Less),
+        CompareOp::Le => matches!(ordering(field, literal), Some(Ordering::Less | Ordering::Equal)),
+        CompareOp::Contains => contains(field, literal),
+        CompareOp::Matches(regex) => regex.is_match(&field.display),
+    }
+}
+
+fn in_set_item(field: &Field, item: &SetItem) -> bool {
+    match item {
+        SetItem::Value(literal) => equals(field, literal),
+        SetItem::Range(low, high) => {
+            matches!(ordering(field, low), Some(Ordering::Greater | Ordering::Equal))
+                && matches!(ordering(field, high), Some(Ordering::Less | Ordering::Equal))
+        }
+    }
+}
+
+// Enumerated values (message types, versions) can be compared by their display text too
+fn display_equals(field: &Field, literal: &Literal) -> bool {
+    field.display.eq_ignore_ascii_case(&literal.text)
+}
+
+fn equals(field: &Field, literal: &Literal) -> bool {
+    match &field.value {
+        FieldValue::Ip(address) => match literal.network {
+            Some((network, prefix)) => in_network(*address, network, prefix),
+            None => display_equals(field, literal),
+        },
+        FieldValue::UInt(value) => literal.number == Some(*value) || display_equals(field, literal),
+        FieldValue::Str(value) => *value == literal.text,
+        FieldValue::Bool(value) => literal.boolean == Some(*value),
+        FieldValue::Bytes(value) => literal.bytes.as_ref() == Some(value) || display_equals(field, literal),
+        FieldValue::None => display_equals(field, literal),
+    }
+}
+
+fn ordering(field: &Field, literal: &Literal) -> Option<Ordering> {
+    match &field.value {
+        FieldValue::UInt(value) => literal.number.map(|number| value.cmp(&number)),
+        FieldValue::Ip(address) => match literal.network {
+            Some((other, _)) if other.is_ipv4() == address.is_ipv4() => Some(address.cmp(&other)),
+            _ => None,
+        },
+        FieldValue::Str(value) => Some(value.as_str().cmp(literal.text.as_str())),
+        FieldValue::Bool(value) => literal.boolean.map(|boolean| value.cmp(&boolean)),
+        FieldValue::Bytes(value) => literal.bytes.as_ref().map(|bytes| value.cmp(bytes)),
+        FieldValue::None => None,
+    }
+}
+
+fn contains(field: &Field, literal: &Literal) -> bool {
+    match &field.value {
+        FieldValue::Str(value) => value.contains(&literal.text),
+        FieldValue::Bytes(value) => {
+            let needle = literal.bytes.clone().unwrap_or_else(|| literal.text.as_bytes().to_vec());
+            !needle.is_empty() && value.windows(needle.len()).any(|window| window == needle.as_slice())
+        }
+        _ => field.display.contains(&literal.text),
+    }
+}
+
+fn in_network(address: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (address, network) {
+        (IpAddr::V4(address), IpAddr::V4(network)) => {
+            let mask = if prefix == 0 { 0 } else { u32::MAX << (32 - prefix) };
+            u32::from(address) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(address), IpAddr::V6(network)) => {
+            let mask = if prefix == 0 { 0 } else { u128::MAX << (128 - prefix) };
+            u128::from(address) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
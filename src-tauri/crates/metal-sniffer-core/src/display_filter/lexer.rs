@@ -0,0 +1,124 @@
+use super::FilterError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    // Field names, numbers, addresses and keywords before they are classified
+    Word(String),
+    Str(String),
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Tilde,
+    Not,
+    And,
+    Or,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Comma,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    // Byte offset of the token in the filter text, used for error positions
+    pub position: usize,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '.' | ':' | '/' | '_' | '-')
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, FilterError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(position, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let two: String = input[position..].chars().take(2).collect();
+        let double = match two.as_str() {
+            "==" => Some(TokenKind::Eq),
+            "!=" => Some(TokenKind::Ne),
+            ">=" => Some(TokenKind::Ge),
+            "<=" => Some(TokenKind::Le),
+            "&&" => Some(TokenKind::And),
+            "||" => Some(TokenKind::Or),
+            _ => None,
+        };
+        if let Some(kind) = double {
+            chars.next();
+            chars.next();
+            tokens.push(Token { kind, position });
+            continue;
+        }
+
+        let single = match c {
+            '>' => Some(TokenKind::Gt),
+            '<' => Some(TokenKind::Lt),
+            '~' => Some(TokenKind::Tilde),
+            '!' => Some(TokenKind::Not),
+            '(' => Some(TokenKind::LParen),
+            ')' => Some(TokenKind::RParen),
+            '{' => Some(TokenKind::LBrace),
+            '}' => Some(TokenKind::RBrace),
+            ',' => Some(TokenKind::Comma),
+            _ => None,
+        };
+        if let Some(kind) = single {
+            chars.next();
+            tokens.push(Token { kind, position });
+            continue;
+        }
+
+        if c == '"' {
+            chars.next();
+            let mut text = String::new();
+            let mut closed = false;
+            while let Some((_, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        closed = true;
+                        break;
+                    }
+                    '\\' => match chars.next() {
+                        Some((_, 'n')) => text.push('\n'),
+                        Some((_, 't')) => text.push('\t'),
+                        Some((_, escaped)) => text.push(escaped),
+                        None => break,
+                    },
+                    _ => text.push(c),
+                }
+            }
+            if !closed {
+                return Err(FilterError::new("Unterminated string", position));
+            }
+            tokens.push(Token { kind: TokenKind::Str(text), position });
+            continue;
+        }
+
+        if is_word_char(c) {
+            let mut word = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if !is_word_char(c) {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token { kind: TokenKind::Word(word), position });
+            continue;
+        }
+
+        return Err(FilterError::new(&format!("Unexpected character '{}'", c), position));
+    }
+
+    Ok(tokens)
+}
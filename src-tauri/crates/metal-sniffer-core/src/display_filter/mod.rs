@@ -0,0 +1,165 @@
+// Wireshark style display filters evaluated over the decoded field tree, e.g.
+// `ip.src == 10.0.0.0/8 and not (udp.port in {53 5353} or quic.sni contains "corp")`
+mod eval;
+mod lexer;
+mod parser;
+
+use serde::Serialize;
+
+use super::field_tree::Field;
+use parser::{Expr, Parser};
+
+// `position` is the byte offset in the filter text the error refers to
+#[derive(Debug, Clone, PartialEq, Serialize, thiserror::Error)]
+#[error("{message} at position {position}")]
+pub struct FilterError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl FilterError {
+    pub fn new(message: &str, position: usize) -> FilterError {
+        FilterError { message: message.to_string(), position }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DisplayFilter {
+    expr: Expr,
+}
+
+impl DisplayFilter {
+    pub fn compile(filter: &str) -> Result<DisplayFilter, FilterError> {
+        let tokens = lexer::tokenize(filter)?;
+        let expr = Parser::new(tokens, filter.len()).parse()?;
+        Ok(DisplayFilter { expr })
+    }
+
+    pub fn matches(&self, fields: &[Field]) -> bool {
+        eval::evaluate(&self.expr, fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field_tree::FieldValue;
+
+    // An IPv4/TCP packet from 10.1.2.3:51000 to 192.168.0.10:443 carrying an SNI
+    fn packet() -> Vec<Field> {
+        vec![
+            Field::protocol("ip", "IPv4").with_children(vec![
+                Field::new("ip.src", "Source", FieldValue::Ip("10.1.2.3".parse().unwrap())),
+                Field::new("ip.dst", "Destination", FieldValue::Ip("192.168.0.10".parse().unwrap())),
+                Field::new("ip.ttl", "TTL", FieldValue::UInt(64)),
+            ]),
+            Field::protocol("tcp", "TCP").with_children(vec![
+                Field::new("tcp.srcport", "Source Port", FieldValue::UInt(51000)),
+                Field::new("tcp.dstport", "Destination Port", FieldValue::UInt(443)),
+                Field::new("tcp.flags.syn", "SYN", FieldValue::Bool(true)),
+            ]),
+            Field::protocol("tls", "TLS").with_children(vec![
+                Field::new("tls.handshake.extensions_server_name", "SNI", FieldValue::Str("mail.corp.example".to_string())),
+                Field::new("tls.random", "Random", FieldValue::Bytes(vec![0xde, 0xad, 0xbe, 0xef])),
+            ]),
+        ]
+    }
+
+    fn matches(filter: &str) -> bool {
+        DisplayFilter::compile(filter).unwrap().matches(&packet())
+    }
+
+    fn error(filter: &str) -> FilterError {
+        DisplayFilter::compile(filter).unwrap_err()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        // Read as "tcp or (udp and dns)", not "(tcp or udp) and dns"
+        assert!(matches("tcp or udp and dns"));
+        assert!(matches("udp and dns or tcp"));
+        assert!(!matches("(tcp or udp) and dns"));
+        assert!(matches("tcp || udp && dns"));
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and() {
+        assert!(!matches("not tcp and udp"));
+        assert!(matches("not udp and tcp"));
+        assert!(matches("not (tcp and udp)"));
+        assert!(matches("!!tcp"));
+    }
+
+    #[test]
+    fn long_chains_evaluate() {
+        let chain = vec!["udp"; 10_000].join(" or ") + " or tcp";
+        assert!(matches(&chain));
+        let chain = vec!["tcp"; 10_000].join(" and ");
+        assert!(matches(&chain));
+    }
+
+    #[test]
+    fn compares_addresses_and_networks() {
+        assert!(matches("ip.src == 10.1.2.3"));
+        assert!(matches("ip.src == 10.0.0.0/8"));
+        assert!(!matches("ip.src == 10.2.0.0/16"));
+        assert!(matches("ip.addr == 192.168.0.0/24"));
+        assert!(matches("ip.src in {172.16.0.0/12 10.0.0.0/8}"));
+        assert!(matches("ip.dst != 10.0.0.0/8"));
+        assert!(matches("ip.src == 0.0.0.0/0"));
+        assert!(!matches("ip.src == 10.1.2.3/33"));
+    }
+
+    #[test]
+    fn compares_numbers_and_sets() {
+        assert!(matches("tcp.port == 443"));
+        assert!(matches("tcp.dstport eq 0x1bb"));
+        assert!(matches("ip.ttl > 32 and ip.ttl <= 64"));
+        assert!(matches("tcp.port in {80, 443 8443}"));
+        assert!(matches("tcp.srcport in {49152..65535}"));
+        assert!(!matches("tcp.dstport in {1..442}"));
+        assert!(matches("tcp.flags.syn == 1"));
+        assert!(!matches("tcp.flags.syn == false"));
+    }
+
+    #[test]
+    fn contains_and_matches() {
+        assert!(matches("tls.handshake.extensions_server_name contains \"corp\""));
+        assert!(!matches("tls.handshake.extensions_server_name contains \"CORP\""));
+        assert!(matches("tls.random contains be:ef"));
+        assert!(matches("tls.random contains adbe"));
+        assert!(!matches("tls.random contains efde"));
+        assert!(matches("tls.handshake.extensions_server_name matches \"^mail\\\\.[a-z]+\\\\.example$\""));
+        assert!(matches("tls.handshake.extensions_server_name ~ \"(?i)CORP\""));
+        assert!(!matches("tls.handshake.extensions_server_name matches \"^corp\""));
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        assert_eq!(error(""), FilterError::new("Empty filter", 0));
+        assert_eq!(error("tcp and").position, 7);
+        assert_eq!(error("tcp udp").position, 4);
+        assert_eq!(error("(tcp or udp").position, 0);
+        assert_eq!(error("ip.src == \"10.0.0.1").position, 10);
+        assert_eq!(error("tcp.port in 80").position, 12);
+        assert_eq!(error("tcp.port in {80").position, 12);
+        assert_eq!(error("tcp.port in {80..}").position, 13);
+        assert_eq!(error("tcp.port == $").position, 12);
+        assert_eq!(error("x matches \"(\"").position, 10);
+        assert_eq!(error("== 80").position, 0);
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let nested = "(".repeat(100_000) + "tcp" + &")".repeat(100_000);
+        assert_eq!(error(&nested), FilterError::new("Filter is nested too deeply", 64));
+        let nots = "not ".repeat(100_000) + "tcp";
+        assert_eq!(error(&nots).message, "Filter is nested too deeply");
+        assert_eq!(error(&"!".repeat(100_000)).message, "Filter is nested too deeply");
+        // Typing "((((" one character at a time stays an error, not a crash
+        assert_eq!(error(&"(".repeat(100_000)).message, "Filter is nested too deeply");
+
+        let shallow = "(".repeat(64) + "tcp" + &")".repeat(64);
+        assert!(matches(&shallow));
+    }
+}
@@ -0,0 +1,320 @@
+use regex::Regex;
+use std::net::IpAddr;
+
+use super::lexer::{Token, TokenKind};
+use super::FilterError;
+use crate::field_tree::hex_bytes;
+
+// Parentheses and `not`s nested deeper than this are refused rather than recursed into,
+// filters are checked as they are typed and must not be able to overflow the stack
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Contains,
+    Matches(Regex),
+}
+
+// A right hand side value. The type it is read as depends on the field it is
+// compared with, so every interpretation that parses is kept
+#[derive(Debug, Clone)]
+pub struct Literal {
+    pub text: String,
+    pub number: Option<u64>,
+    // An address is a network with a full length prefix
+    pub network: Option<(IpAddr, u8)>,
+    pub bytes: Option<Vec<u8>>,
+    pub boolean: Option<bool>,
+}
+
+impl Literal {
+    fn new(text: String, quoted: bool) -> Literal {
+        let mut literal = Literal { text, number: None, network: None, bytes: None, boolean: None };
+        if quoted {
+            return literal;
+        }
+        let text = literal.text.as_str();
+        literal.number = match text.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => text.parse().ok(),
+        };
+        literal.network = parse_network(text);
+        literal.bytes = parse_bytes(text);
+        literal.boolean = match text {
+            "true" | "1" => Some(true),
+            "false" | "0" => Some(false),
+            _ => None,
+        };
+        literal
+    }
+}
+
+fn parse_network(text: &str) -> Option<(IpAddr, u8)> {
+    let (address, prefix) = match text.split_once('/') {
+        Some((address, prefix)) => (address, Some(prefix)),
+        None => (text, None),
+    };
+    let address: IpAddr = address.parse().ok()?;
+    let max = if address.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(prefix) => prefix.parse::<u8>().ok().filter(|prefix| *prefix <= max)?,
+        None => max,
+    };
+    Some((address, prefix))
+}
+
+// MAC style (aa:bb:cc), dash separated or plain even length hex
+fn parse_bytes(text: &str) -> Option<Vec<u8>> {
+    let plain: String = text.chars().filter(|c| *c != ':' && *c != '-').collect();
+    if plain.is_empty() || (plain.len() == text.len() && !text.chars().all(|c| c.is_ascii_hexdigit())) {
+        return None;
+    }
+    hex_bytes::decode(&plain)
+}
+
+#[derive(Debug, Clone)]
+pub enum SetItem {
+    Value(Literal),
+    Range(Literal, Literal),
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    // Chains are kept flat so a long "a or b or c ..." does not nest
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    // A bare field or protocol name, true when it is present in the packet
+    Exists(String),
+    Compare(String, CompareOp, Literal),
+    In(String, Vec<SetItem>),
+}
+
+enum Operator {
+    Compare(CompareOp),
+    Matches,
+    In,
+}
+
+fn operator(kind: &TokenKind) -> Option<Operator> {
+    let op = match kind {
+        TokenKind::Eq => CompareOp::Eq,
+        TokenKind::Ne => CompareOp::Ne,
+        TokenKind::Gt => CompareOp::Gt,
+        TokenKind::Ge => CompareOp::Ge,
+        TokenKind::Lt => CompareOp::Lt,
+        TokenKind::Le => CompareOp::Le,
+        TokenKind::Tilde => return Some(Operator::Matches),
+        TokenKind::Word(word) => match word.as_str() {
+            "eq" => CompareOp::Eq,
+            "ne" => CompareOp::Ne,
+            "gt" => CompareOp::Gt,
+            "ge" => CompareOp::Ge,
+            "lt" => CompareOp::Lt,
+            "le" => CompareOp::Le,
+            "contains" => CompareOp::Contains,
+            "matches" => return Some(Operator::Matches),
+            "in" => return Some(Operator::In),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(Operator::Compare(op))
+}
+
+pub struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    end: usize,
+    depth: usize,
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<Token>, input_len: usize) -> Parser {
+        Parser { tokens, pos: 0, end: input_len, depth: 0 }
+    }
+
+    pub fn parse(mut self) -> Result<Expr, FilterError> {
+        if self.tokens.is_empty() {
+            return Err(FilterError::new("Empty filter", 0));
+        }
+        let expr = self.parse_or()?;
+        if let Some(token) = self.peek() {
+            return Err(FilterError::new("Unexpected token, expected 'and' or 'or'", token.position));
+        }
+        Ok(expr)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn position(&self) -> usize {
+        self.peek().map(|token| token.position).unwrap_or(self.end)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token { kind: TokenKind::Word(word), .. }) if word == keyword)
+    }
+
+    fn eat(&mut self, kind: &TokenKind, keyword: &str) -> bool {
+        let matched = match self.peek() {
+            Some(token) => &token.kind == kind || self.is_keyword(keyword),
+            None => false,
+        };
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    // Called before recursing into parentheses or `not`, paired with `leave`
+    fn enter(&mut self, position: usize) -> Result<(), FilterError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(FilterError::new("Filter is nested too deeply", position));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, FilterError> {
+        let mut operands = vec![self.parse_and()?];
+        while self.eat(&TokenKind::Or, "or") {
+            operands.push(self.parse_and()?);
+        }
+        Ok(if operands.len() == 1 { operands.remove(0) } else { Expr::Or(operands) })
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, FilterError> {
+        let mut operands = vec![self.parse_not()?];
+        while self.eat(&TokenKind::And, "and") {
+            operands.push(self.parse_not()?);
+        }
+        Ok(if operands.len() == 1 { operands.remove(0) } else { Expr::And(operands) })
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, FilterError> {
+        let position = self.position();
+        if self.eat(&TokenKind::Not, "not") {
+            self.enter(position)?;
+            let inner = self.parse_not()?;
+            self.leave();
+            return Ok(Expr::Not(Box::new(inner)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, FilterError> {
+        let position = self.position();
+        match self.next() {
+            Some(Token { kind: TokenKind::LParen, .. }) => {
+                self.enter(position)?;
+                let expr = self.parse_or()?;
+                self.leave();
+                match self.next() {
+                    Some(Token { kind: TokenKind::RParen, .. }) => Ok(expr),
+                    _ => Err(FilterError::new("Missing closing parenthesis", position)),
+                }
+            }
+            Some(Token { kind: TokenKind::Word(field), position }) => {
+                if !field.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') {
+                    return Err(FilterError::new(&format!("Expected a field name, found '{}'", field), position));
+                }
+                self.parse_test(field)
+            }
+            Some(token) => Err(FilterError::new("Expected a field name or '('", token.position)),
+            None => Err(FilterError::new("Unexpected end of filter", self.end)),
+        }
+    }
+
+    fn parse_test(&mut self, field: String) -> Result<Expr, FilterError> {
+        // Anything other than an operator ("and", ")", end of input) makes this an existence test
+        let Some(operator) = self.peek().and_then(|token| operator(&token.kind)) else {
+            return Ok(Expr::Exists(field));
+        };
+        self.pos += 1;
+
+        match operator {
+            Operator::In => Ok(Expr::In(field, self.parse_set()?)),
+            Operator::Matches => {
+                let (literal, position) = self.parse_literal()?;
+                let regex = Regex::new(&literal.text)
+                    .map_err(|e| FilterError::new(&format!("Invalid regular expression: {}", e), position))?;
+                Ok(Expr::Compare(field, CompareOp::Matches(regex), literal))
+            }
+            Operator::Compare(op) => {
+                let (literal, _) = self.parse_literal()?;
+                Ok(Expr::Compare(field, op, literal))
+            }
+        }
+    }
+
+    fn parse_literal(&mut self) -> Result<(Literal, usize), FilterError> {
+        let position = self.position();
+        match self.next() {
+            Some(Token { kind: TokenKind::Str(text), .. }) => Ok((Literal::new(text, true), position)),
+            Some(Token { kind: TokenKind::Word(text), .. }) => Ok((Literal::new(text, false), position)),
+            Some(_) => Err(FilterError::new("Expected a value", position)),
+            None => Err(FilterError::new("Expected a value, found end of filter", self.end)),
+        }
+    }
+
+    // { 80 443 8000..8080 } with optional commas between items
+    fn parse_set(&mut self) -> Result<Vec<SetItem>, FilterError> {
+        let position = self.position();
+        if !matches!(self.next(), Some(Token { kind: TokenKind::LBrace, .. })) {
+            return Err(FilterError::new("Expected '{' after 'in'", position));
+        }
+
+        let mut items = Vec::new();
+        loop {
+            match self.peek().map(|token| token.kind.clone()) {
+                Some(TokenKind::RBrace) => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(TokenKind::Comma) => {
+                    self.pos += 1;
+                }
+                Some(TokenKind::Word(word)) if word.contains("..") => {
+                    let item_position = self.position();
+                    self.pos += 1;
+                    let (low, high) = word.split_once("..").unwrap();
+                    if low.is_empty() || high.is_empty() {
+                        return Err(FilterError::new("Incomplete range", item_position));
+                    }
+                    items.push(SetItem::Range(
+                        Literal::new(low.to_string(), false),
+                        Literal::new(high.to_string(), false),
+                    ));
+                }
+                Some(_) => {
+                    let (literal, _) = self.parse_literal()?;
+                    items.push(SetItem::Value(literal));
+                }
+                None => return Err(FilterError::new("Missing closing '}'", position)),
+            }
+        }
+
+        if items.is_empty() {
+            return Err(FilterError::new("Empty set", position));
+        }
+        Ok(items)
+    }
+}
@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+/// Typed value of a field, what display filters compare against
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum FieldValue {
+    // Protocol roots and lists, the value lives in the children
+    None,
+    Bool(bool),
+    UInt(u64),
+    Str(String),
+    #[serde(with = "hex_bytes")]
+    Bytes(Vec<u8>),
+    Ip(IpAddr),
+}
+
+impl FieldValue {
+    /// How the value is shown when the dissector does not format it itself
+    pub fn default_display(&self) -> String {
+        match self {
+            FieldValue::None => String::new(),
+            FieldValue::Bool(value) => value.to_string(),
+            FieldValue::UInt(value) => value.to_string(),
+            FieldValue::Str(value) => value.clone(),
+            FieldValue::Bytes(value) => hex_bytes::encode(value),
+            FieldValue::Ip(value) => value.to_string(),
+        }
+    }
+}
+
+/// One node of a decoded packet. `name` is the filter friendly name (e.g. "ip.src"),
+/// `display_name` is what the UI has always shown (e.g. "Source IP")
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Field {
+    pub name: String,
+    pub display_name: String,
+    pub value: FieldValue,
+    pub display: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Field>,
+}
+
+impl Field {
+    /// A field showing its value the default way, without a span or children
+    pub fn new(name: &str, display_name: &str, value: FieldValue) -> Field {
+        Field {
+            name: name.to_string(),
+            display_name: display_name.to_string(),
+            display: value.default_display(),
+            value,
+            offset: None,
+            length: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// A protocol root, displayed with its short name ("TCP", "IPv4", ...)
+    pub fn protocol(name: &str, display_name: &str) -> Field {
+        let mut field = Field::new(name, display_name, FieldValue::None);
+        field.display = display_name.to_string();
+        field
+    }
+
+    /// Marks a layer that could not be decoded, shown as "Error" like before
+    pub fn malformed(message: &str) -> Field {
+        Field::new("_malformed", "Error", FieldValue::Str(message.to_string()))
+    }
+
+    /// Replaces the text shown for the value
+    pub fn with_display(mut self, display: String) -> Field {
+        self.display = display;
+        self
+    }
+
+    /// Sets where the field lives, in bytes from the start of its layer
+    pub fn with_span(mut self, offset: usize, length: usize) -> Field {
+        self.offset = Some(offset);
+        self.length = Some(length);
+        self
+    }
+
+    /// Replaces the children of this field
+    pub fn with_children(mut self, children: Vec<Field>) -> Field {
+        self.children = children;
+        self
+    }
+
+    /// Appends a child field
+    pub fn push(&mut self, child: Field) {
+        self.children.push(child);
+    }
+
+    /// A repeated value (routers, DNS servers, ...) as a parent holding one child per item
+    pub fn list(name: &str, display_name: &str, values: Vec<FieldValue>) -> Field {
+        let children: Vec<Field> = values
+            .into_iter()
+            .map(|value| Field::new(name, display_name, value))
+            .collect();
+        let display = children.iter().map(|child| child.display.clone()).collect::<Vec<_>>().join(", ");
+        Field::new(name, display_name, FieldValue::None)
+            .with_display(display)
+            .with_children(children)
+    }
+
+    /// Moves this field and all of its children by `base` bytes
+    pub fn shift(&mut self, base: usize) {
+        if let Some(offset) = self.offset.as_mut() {
+            *offset += base;
+        }
+        for child in &mut self.children {
+            child.shift(base);
+        }
+    }
+
+    /// Depth first walk over this field and everything below it
+    pub fn walk<'a>(&'a self, visit: &mut dyn FnMut(&'a Field)) {
+        visit(self);
+        for child in &self.children {
+            child.walk(visit);
+        }
+    }
+
+    /// Depth first iterator over this field and everything below it
+    pub fn iter(&self) -> FieldIter<'_> {
+        FieldIter { stack: vec![self] }
+    }
+
+    /// Compatibility adapter for the original `HashMap<String, String>` layer shape
+    pub fn to_info_map(&self) -> HashMap<String, String> {
+        self.children
+            .iter()
+            .map(|child| (child.display_name.clone(), child.display.clone()))
+            .collect()
+    }
+}
+
+/// Yields a field and its children depth first, in the order `walk` visits them
+pub struct FieldIter<'a> {
+    stack: Vec<&'a Field>,
+}
+
+impl<'a> Iterator for FieldIter<'a> {
+    type Item = &'a Field;
+
+    fn next(&mut self) -> Option<&'a Field> {
+        let field = self.stack.pop()?;
+        self.stack.extend(field.children.iter().rev());
+        Some(field)
+    }
+}
+
+/// A packet as emitted to the UI: the legacy "Layer N" maps for existing consumers plus the typed tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PacketRecord {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame_number: Option<u64>,
+    #[serde(flatten)]
+    pub info: HashMap<String, HashMap<String, String>>,
+    #[serde(default)]
+    pub fields: Vec<Field>,
+}
+
+/// Bytes as a lowercase hex string, also used to (de)serialize `FieldValue::Bytes`
+pub mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// Two lowercase hex digits per byte, no separators
+    pub fn encode(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// The inverse of `encode`, `None` for odd lengths or non hex digits
+    pub fn decode(text: &str) -> Option<Vec<u8>> {
+        if !text.len().is_multiple_of(2) {
+            return None;
+        }
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+            .collect()
+    }
+
+    /// Serializes bytes as a hex string
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode(bytes))
+    }
+
+    /// Deserializes bytes from a hex string
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        decode(&text).ok_or_else(|| serde::de::Error::custom("invalid hex string"))
+    }
+}
@@ -0,0 +1,186 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use crate::custom_packet::MyPacket;
+use crate::decode_as::Transport;
+
+/// A TCP or UDP conversation, the same key whichever side sent the packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct FlowKey {
+    pub transport: Transport,
+    pub a_ip: IpAddr,
+    pub a_port: u16,
+    pub b_ip: IpAddr,
+    pub b_port: u16,
+}
+
+/// Which side of a `FlowKey` sent a packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    AToB,
+    BToA,
+}
+
+impl FlowKey {
+    /// Builds the key for a packet's 5-tuple, `a` is always the lower address/port pair
+    pub fn new(transport: Transport, src: (IpAddr, u16), dst: (IpAddr, u16)) -> (FlowKey, Direction) {
+        let (a, b, direction) = if src <= dst {
+            (src, dst, Direction::AToB)
+        } else {
+            (dst, src, Direction::BToA)
+        };
+        let key = FlowKey {
+            transport,
+            a_ip: a.0,
+            a_port: a.1,
+            b_ip: b.0,
+            b_port: b.1,
+        };
+        (key, direction)
+    }
+
+    /// The flow a dissected packet belongs to, `None` for packets without ports
+    pub fn of(packet: &MyPacket) -> Option<(FlowKey, Direction)> {
+        let transport = packet.transport()?;
+        let (src_ip, dst_ip) = packet.addresses();
+        let (src_port, dst_port) = packet.ports()?;
+        Some(FlowKey::new(transport, (src_ip, src_port), (dst_ip, dst_port)))
+    }
+
+    /// IANA protocol number of the transport
+    pub fn protocol_number(&self) -> u8 {
+        match self.transport {
+            Transport::TCP => 6,
+            Transport::UDP => 17,
+        }
+    }
+}
+
+/// What a flow table needs from one packet, small enough to pass between threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlowSample {
+    pub key: FlowKey,
+    pub direction: Direction,
+    /// TCP flag bits, 0 for UDP
+    pub tcp_flags: u8,
+}
+
+impl FlowSample {
+    pub fn of(packet: &MyPacket) -> Option<FlowSample> {
+        let (key, direction) = FlowKey::of(packet)?;
+        let tcp_flags = packet.tcp_segment().map(|segment| segment.flags).unwrap_or(0);
+        Some(FlowSample { key, direction, tcp_flags })
+    }
+}
+
+/// Packet and byte counts for one flow, split by direction
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct FlowStats {
+    pub first_seen: (i64, u32),
+    pub last_seen: (i64, u32),
+    pub packets_a_to_b: u64,
+    pub bytes_a_to_b: u64,
+    pub packets_b_to_a: u64,
+    pub bytes_b_to_a: u64,
+    /// TCP flags seen in each direction, OR-ed together
+    pub tcp_flags_a_to_b: u8,
+    pub tcp_flags_b_to_a: u8,
+}
+
+impl FlowStats {
+    pub fn packets(&self) -> u64 {
+        self.packets_a_to_b + self.packets_b_to_a
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes_a_to_b + self.bytes_b_to_a
+    }
+}
+
+/// Groups packets into flows as they are dissected
+#[derive(Debug, Default)]
+pub struct FlowTable {
+    flows: HashMap<FlowKey, FlowStats>,
+}
+
+impl FlowTable {
+    pub fn new() -> FlowTable {
+        FlowTable::default()
+    }
+
+    /// Counts a packet of `len` bytes seen at `ts_sec`.`ts_usec` against its flow
+    pub fn record(&mut self, packet: &MyPacket, ts_sec: i64, ts_usec: u32, len: u32) -> Option<(FlowKey, Direction)> {
+        let sample = FlowSample::of(packet)?;
+        self.add(&sample, ts_sec, ts_usec, len);
+        Some((sample.key, sample.direction))
+    }
+
+    /// Same as `record` for a packet dissected elsewhere
+    pub fn add(&mut self, sample: &FlowSample, ts_sec: i64, ts_usec: u32, len: u32) {
+        let stats = self.flows.entry(sample.key).or_insert_with(|| FlowStats {
+            first_seen: (ts_sec, ts_usec),
+            last_seen: (ts_sec, ts_usec),
+            ..FlowStats::default()
+        });
+        stats.last_seen = stats.last_seen.max((ts_sec, ts_usec));
+        match sample.direction {
+            Direction::AToB => {
+                stats.packets_a_to_b += 1;
+                stats.bytes_a_to_b += len as u64;
+                stats.tcp_flags_a_to_b |= sample.tcp_flags;
+            }
+            Direction::BToA => {
+                stats.packets_b_to_a += 1;
+                stats.bytes_b_to_a += len as u64;
+                stats.tcp_flags_b_to_a |= sample.tcp_flags;
+            }
+        }
+    }
+
+    /// Removes and returns the flows idle for `idle_secs` or open for `active_secs` as of
+    /// `now_sec`, the way flow exporters age out their cache
+    pub fn expire(&mut self, now_sec: i64, idle_secs: i64, active_secs: i64) -> Vec<(FlowKey, FlowStats)> {
+        let expired: Vec<FlowKey> = self
+            .flows
+            .iter()
+            .filter(|(_, stats)| now_sec - stats.last_seen.0 >= idle_secs || now_sec - stats.first_seen.0 >= active_secs)
+            .map(|(key, _)| *key)
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|key| self.flows.remove(&key).map(|stats| (key, stats)))
+            .collect()
+    }
+
+    /// Removes and returns every flow
+    pub fn drain(&mut self) -> Vec<(FlowKey, FlowStats)> {
+        self.flows.drain().collect()
+    }
+
+    pub fn get(&self, key: &FlowKey) -> Option<&FlowStats> {
+        self.flows.get(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&FlowKey, &FlowStats)> {
+        self.flows.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.flows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flows.is_empty()
+    }
+
+    /// Drops a finished flow, e.g. once its TCP stream has been closed
+    pub fn remove(&mut self, key: &FlowKey) -> Option<FlowStats> {
+        self.flows.remove(key)
+    }
+
+    pub fn clear(&mut self) {
+        self.flows.clear();
+    }
+}
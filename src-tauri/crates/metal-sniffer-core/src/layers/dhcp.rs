@@ -0,0 +1,431 @@
+// DHCPv4 (RFC 2131 / RFC 2132) and DHCPv6 (RFC 8415) message parsing
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::ops::Range;
+
+use crate::field_tree::hex_bytes;
+use crate::link::format_mac;
+
+const MAGIC_COOKIE: [u8; 4] = [0x63, 0x82, 0x53, 0x63];
+// op, htype, hlen, hops, xid, secs, flags, 4 addresses, chaddr, sname and file
+const BOOTP_HEADER_LEN: usize = 236;
+// Where option overload (option 52) continues the options
+const SNAME_FIELD: Range<usize> = 44..108;
+const FILE_FIELD: Range<usize> = 108..236;
+
+/// The BOOTP header and the DHCP options the dissector understands
+#[derive(Debug, Clone, PartialEq)]
+pub struct DhcpV4Message {
+    pub op: u8,
+    pub message_type: Option<u8>,
+    pub transaction_id: u32,
+    pub client_mac: String,
+    pub client_ip: Ipv4Addr,
+    pub your_ip: Ipv4Addr,
+    pub server_ip: Ipv4Addr,
+    pub relay_ip: Ipv4Addr,
+    pub requested_ip: Option<Ipv4Addr>,
+    pub server_identifier: Option<Ipv4Addr>,
+    pub subnet_mask: Option<Ipv4Addr>,
+    pub routers: Vec<Ipv4Addr>,
+    pub dns_servers: Vec<Ipv4Addr>,
+    pub domain_name: Option<String>,
+    pub hostname: Option<String>,
+    pub vendor_class: Option<String>,
+    pub client_identifier: Option<String>,
+    pub lease_time: Option<u32>,
+    pub renewal_time: Option<u32>,
+    pub rebinding_time: Option<u32>,
+    pub parameter_request_list: Vec<u8>,
+    // Option code, value offset and value length within the payload
+    pub option_offsets: Vec<(u8, usize, usize)>,
+}
+
+/// Name of a DHCP message type (option 53)
+pub fn v4_message_type_name(message_type: u8) -> &'static str {
+    match message_type {
+        1 => "DISCOVER",
+        2 => "OFFER",
+        3 => "REQUEST",
+        4 => "DECLINE",
+        5 => "ACK",
+        6 => "NAK",
+        7 => "RELEASE",
+        8 => "INFORM",
+        _ => "Unknown",
+    }
+}
+
+fn ipv4_at(data: &[u8], offset: usize) -> Option<Ipv4Addr> {
+    let bytes: [u8; 4] = data.get(offset..offset + 4)?.try_into().ok()?;
+    Some(Ipv4Addr::from(bytes))
+}
+
+fn ipv4_list(value: &[u8]) -> Vec<Ipv4Addr> {
+    value.chunks_exact(4).map(|chunk| Ipv4Addr::new(chunk[0], chunk[1], chunk[2], chunk[3])).collect()
+}
+
+fn u32_value(value: &[u8]) -> Option<u32> {
+    Some(u32::from_be_bytes(value.get(..4)?.try_into().ok()?))
+}
+
+fn text_value(value: &[u8]) -> String {
+    String::from_utf8_lossy(value).trim_end_matches('\0').to_string()
+}
+
+// Option code, value offset and value within the payload. An area ends at the End option,
+// an option running past the area ends it too and the options before it are kept
+fn v4_options(payload: &[u8], area: Range<usize>) -> Vec<(u8, usize, &[u8])> {
+    let data = &payload[..area.end];
+    let mut options = Vec::new();
+    let mut pos = area.start;
+    while pos < data.len() {
+        let code = data[pos];
+        match code {
+            // Pad
+            0 => {
+                pos += 1;
+                continue;
+            }
+            // End
+            255 => break,
+            _ => {}
+        }
+        let Some(&len) = data.get(pos + 1) else {
+            break;
+        };
+        let Some(value) = data.get(pos + 2..pos + 2 + len as usize) else {
+            break;
+        };
+        options.push((code, pos + 2, value));
+        pos += 2 + len as usize;
+    }
+    options
+}
+
+/// None unless the payload holds a full BOOTP header followed by the DHCP magic cookie
+pub fn parse_v4(payload: &[u8]) -> Option<DhcpV4Message> {
+    if payload.len() < BOOTP_HEADER_LEN + MAGIC_COOKIE.len() {
+        return None;
+    }
+    if payload[BOOTP_HEADER_LEN..BOOTP_HEADER_LEN + 4] != MAGIC_COOKIE {
+        return None;
+    }
+
+    let hlen = (payload[2] as usize).min(16);
+    let mut message = DhcpV4Message {
+        op: payload[0],
+        transaction_id: u32::from_be_bytes(payload[4..8].try_into().ok()?),
+        client_ip: ipv4_at(payload, 12)?,
+        your_ip: ipv4_at(payload, 16)?,
+        server_ip: ipv4_at(payload, 20)?,
+        relay_ip: ipv4_at(payload, 24)?,
+        client_mac: format_mac(&payload[28..28 + hlen]),
+        message_type: None,
+        requested_ip: None,
+        server_identifier: None,
+        subnet_mask: None,
+        routers: Vec::new(),
+        dns_servers: Vec::new(),
+        domain_name: None,
+        hostname: None,
+        vendor_class: None,
+        client_identifier: None,
+        lease_time: None,
+        renewal_time: None,
+        rebinding_time: None,
+        parameter_request_list: Vec::new(),
+        option_offsets: Vec::new(),
+    };
+
+    let mut options = v4_options(payload, BOOTP_HEADER_LEN + 4..payload.len());
+    // Overloaded options continue in the file field, then in the sname field
+    let overload = options
+        .iter()
+        .find(|(code, _, _)| *code == 52)
+        .and_then(|(_, _, value)| value.first().copied())
+        .unwrap_or(0);
+    if overload & 1 != 0 {
+        options.extend(v4_options(payload, FILE_FIELD));
+    }
+    if overload & 2 != 0 {
+        options.extend(v4_options(payload, SNAME_FIELD));
+    }
+
+    for (code, offset, value) in options {
+        message.option_offsets.push((code, offset, value.len()));
+        match code {
+            1 => message.subnet_mask = ipv4_list(value).first().copied(),
+            3 => message.routers = ipv4_list(value),
+            6 => message.dns_servers = ipv4_list(value),
+            12 => message.hostname = Some(text_value(value)),
+            15 => message.domain_name = Some(text_value(value)),
+            50 => message.requested_ip = ipv4_list(value).first().copied(),
+            51 => message.lease_time = u32_value(value),
+            53 => message.message_type = value.first().copied(),
+            54 => message.server_identifier = ipv4_list(value).first().copied(),
+            55 => message.parameter_request_list = value.to_vec(),
+            58 => message.renewal_time = u32_value(value),
+            59 => message.rebinding_time = u32_value(value),
+            60 => message.vendor_class = Some(text_value(value)),
+            61 => message.client_identifier = Some(hex_bytes::encode(value)),
+            _ => {}
+        }
+    }
+
+    Some(message)
+}
+
+/// An address from an IA_NA option
+#[derive(Debug, Clone, PartialEq)]
+pub struct DhcpV6Address {
+    pub address: Ipv6Addr,
+    pub preferred_lifetime: u32,
+    pub valid_lifetime: u32,
+}
+
+/// A DHCPv6 client/server message and the options the dissector understands
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DhcpV6Message {
+    pub message_type: u8,
+    pub transaction_id: u32,
+    pub client_duid: Option<Vec<u8>>,
+    pub server_duid: Option<Vec<u8>>,
+    pub iaid: Option<u32>,
+    pub t1: Option<u32>,
+    pub t2: Option<u32>,
+    pub addresses: Vec<DhcpV6Address>,
+    pub dns_servers: Vec<Ipv6Addr>,
+    pub domain_search: Vec<String>,
+    pub hostname: Option<String>,
+    pub vendor_class: Option<String>,
+    // Top level option code, value offset and value length within the payload
+    pub option_offsets: Vec<(u16, usize, usize)>,
+}
+
+/// Name of a DHCPv6 message type
+pub fn v6_message_type_name(message_type: u8) -> &'static str {
+    match message_type {
+        1 => "SOLICIT",
+        2 => "ADVERTISE",
+        3 => "REQUEST",
+        4 => "CONFIRM",
+        5 => "RENEW",
+        6 => "REBIND",
+        7 => "REPLY",
+        8 => "RELEASE",
+        9 => "DECLINE",
+        10 => "RECONFIGURE",
+        11 => "INFORMATION-REQUEST",
+        12 => "RELAY-FORW",
+        13 => "RELAY-REPL",
+        _ => "Unknown",
+    }
+}
+
+/// DUID-LLT and DUID-LL embed the client's link layer address
+pub fn duid_mac(duid: &[u8]) -> Option<String> {
+    let duid_type = u16::from_be_bytes(duid.get(..2)?.try_into().ok()?);
+    let address = match duid_type {
+        1 => duid.get(8..)?,
+        3 => duid.get(4..)?,
+        _ => return None,
+    };
+    if address.is_empty() {
+        None
+    } else {
+        Some(format_mac(address))
+    }
+}
+
+/// A DUID as plain hex, for clients whose DUID carries no MAC
+pub fn duid_string(duid: &[u8]) -> String {
+    hex_bytes::encode(duid)
+}
+
+fn ipv6_list(value: &[u8]) -> Vec<Ipv6Addr> {
+    value
+        .chunks_exact(16)
+        .filter_map(|chunk| <[u8; 16]>::try_from(chunk).ok())
+        .map(Ipv6Addr::from)
+        .collect()
+}
+
+// Domain names in DHCPv6 use the uncompressed DNS wire format
+fn dns_names(value: &[u8]) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut labels: Vec<String> = Vec::new();
+    let mut pos = 0;
+    while pos < value.len() {
+        let len = value[pos] as usize;
+        pos += 1;
+        if len == 0 {
+            names.push(labels.join("."));
+            labels.clear();
+            continue;
+        }
+        match value.get(pos..pos + len) {
+            Some(label) => labels.push(String::from_utf8_lossy(label).into_owned()),
+            None => break,
+        }
+        pos += len;
+    }
+    // A partially qualified name has no terminating zero length label
+    if !labels.is_empty() {
+        names.push(labels.join("."));
+    }
+    names
+}
+
+fn v6_options(data: &[u8]) -> Vec<(u16, usize, &[u8])> {
+    let mut options = Vec::new();
+    let mut pos = 0;
+    while pos + 4 <= data.len() {
+        let code = u16::from_be_bytes([data[pos], data[pos + 1]]);
+        let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let Some(value) = data.get(pos + 4..pos + 4 + len) else {
+            break;
+        };
+        options.push((code, pos + 4, value));
+        pos += 4 + len;
+    }
+    options
+}
+
+/// None when the payload is empty or a client/server message header is cut short
+pub fn parse_v6(payload: &[u8]) -> Option<DhcpV6Message> {
+    let message_type = *payload.first()?;
+    // Relay messages wrap the client message with a different header, only the type is reported
+    if message_type == 12 || message_type == 13 {
+        return Some(DhcpV6Message { message_type, ..Default::default() });
+    }
+    let transaction_id = u32::from_be_bytes([0, *payload.get(1)?, *payload.get(2)?, *payload.get(3)?]);
+    let mut message = DhcpV6Message { message_type, transaction_id, ..Default::default() };
+
+    for (code, offset, value) in v6_options(&payload[4..]) {
+        message.option_offsets.push((code, 4 + offset, value.len()));
+        match code {
+            1 => message.client_duid = Some(value.to_vec()),
+            2 => message.server_duid = Some(value.to_vec()),
+            // IA_NA: IAID, T1, T2 and nested options carrying the addresses
+            3 if value.len() >= 12 => {
+                message.iaid = u32_value(&value[0..4]);
+                message.t1 = u32_value(&value[4..8]);
+                message.t2 = u32_value(&value[8..12]);
+                for (nested_code, _, nested) in v6_options(&value[12..]) {
+                    if nested_code == 5 && nested.len() >= 24 {
+                        let address: [u8; 16] = nested[0..16].try_into().ok()?;
+                        message.addresses.push(DhcpV6Address {
+                            address: Ipv6Addr::from(address),
+                            preferred_lifetime: u32_value(&nested[16..20])?,
+                            valid_lifetime: u32_value(&nested[20..24])?,
+                        });
+                    }
+                }
+            }
+            // Vendor class: enterprise number followed by length prefixed opaque data
+            16 if value.len() >= 6 => {
+                let len = u16::from_be_bytes([value[4], value[5]]) as usize;
+                let data = value.get(6..6 + len).unwrap_or(&value[6..]);
+                message.vendor_class = Some(format!("{}: {}", u32_value(value)?, text_value(data)));
+            }
+            23 => message.dns_servers = ipv6_list(value),
+            24 => message.domain_search = dns_names(value),
+            // Client FQDN: one byte of flags followed by the name
+            39 if !value.is_empty() => {
+                message.hostname = dns_names(&value[1..]).into_iter().next();
+            }
+            _ => {}
+        }
+    }
+
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A BOOTP header from 00:11:22:33:44:55 with transaction ID 0x12345678, the magic cookie,
+    // then `options` as given
+    fn dhcp_message(options: &[u8]) -> Vec<u8> {
+        let mut payload = vec![0; BOOTP_HEADER_LEN];
+        payload[..4].copy_from_slice(&[1, 1, 6, 0]);
+        payload[4..8].copy_from_slice(&0x12345678u32.to_be_bytes());
+        payload[28..34].copy_from_slice(&[0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        payload.extend_from_slice(&MAGIC_COOKIE);
+        payload.extend_from_slice(options);
+        payload
+    }
+
+    #[test]
+    fn parses_common_options() {
+        let payload = dhcp_message(&[
+            53, 1, 5,
+            0, 0,
+            1, 4, 255, 255, 255, 0,
+            3, 8, 10, 0, 0, 1, 10, 0, 0, 2,
+            12, 4, b'l', b'a', b'b', 0,
+            51, 4, 0, 0, 0x0e, 0x10,
+            255,
+        ]);
+        let message = parse_v4(&payload).unwrap();
+        assert_eq!(message.message_type, Some(5));
+        assert_eq!(message.transaction_id, 0x12345678);
+        assert_eq!(message.client_mac, "00:11:22:33:44:55");
+        assert_eq!(message.subnet_mask, Some(Ipv4Addr::new(255, 255, 255, 0)));
+        assert_eq!(message.routers, [Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2)]);
+        assert_eq!(message.hostname.as_deref(), Some("lab"));
+        assert_eq!(message.lease_time, Some(3600));
+        // Offsets skip the pad bytes and point at the values
+        let options = BOOTP_HEADER_LEN + 4;
+        assert_eq!(message.option_offsets[0], (53, options + 2, 1));
+        assert_eq!(message.option_offsets[1], (1, options + 7, 4));
+    }
+
+    #[test]
+    fn options_stop_at_the_end_option() {
+        let message = parse_v4(&dhcp_message(&[53, 1, 1, 255, 12, 3, b'l', b'a', b'b'])).unwrap();
+        assert_eq!(message.message_type, Some(1));
+        assert_eq!(message.hostname, None);
+        assert_eq!(message.option_offsets.len(), 1);
+    }
+
+    #[test]
+    fn truncated_options_keep_the_ones_before() {
+        // The hostname claims 10 bytes but the payload ends after 3
+        let message = parse_v4(&dhcp_message(&[53, 1, 3, 50, 4, 10, 0, 0, 7, 12, 10, b'l', b'a', b'b'])).unwrap();
+        assert_eq!(message.message_type, Some(3));
+        assert_eq!(message.requested_ip, Some(Ipv4Addr::new(10, 0, 0, 7)));
+        assert_eq!(message.hostname, None);
+
+        // A length byte cut off is no different
+        let message = parse_v4(&dhcp_message(&[53, 1, 3, 12])).unwrap();
+        assert_eq!(message.option_offsets.len(), 1);
+    }
+
+    #[test]
+    fn overloaded_options_continue_in_file_then_sname() {
+        let mut payload = dhcp_message(&[53, 1, 2, 52, 1, 3, 255]);
+        payload[FILE_FIELD.start..FILE_FIELD.start + 6].copy_from_slice(&[12, 3, b'l', b'a', b'b', 255]);
+        payload[SNAME_FIELD.start..SNAME_FIELD.start + 7].copy_from_slice(&[15, 4, b'c', b'o', b'r', b'p', 255]);
+        let message = parse_v4(&payload).unwrap();
+        assert_eq!(message.hostname.as_deref(), Some("lab"));
+        assert_eq!(message.domain_name.as_deref(), Some("corp"));
+        let codes: Vec<u8> = message.option_offsets.iter().map(|(code, _, _)| *code).collect();
+        assert_eq!(codes, [53, 52, 12, 15]);
+        assert_eq!(message.option_offsets[2], (12, FILE_FIELD.start + 2, 3));
+
+        // Without the overload option those fields are a boot file name and server name
+        let mut payload = dhcp_message(&[53, 1, 2, 255]);
+        payload[FILE_FIELD.start..FILE_FIELD.start + 6].copy_from_slice(&[12, 3, b'l', b'a', b'b', 255]);
+        assert_eq!(parse_v4(&payload).unwrap().hostname, None);
+    }
+
+    #[test]
+    fn rejects_payloads_that_are_not_dhcp() {
+        assert!(parse_v4(&dhcp_message(&[])[..BOOTP_HEADER_LEN + 3]).is_none());
+        let mut payload = dhcp_message(&[53, 1, 1, 255]);
+        payload[BOOTP_HEADER_LEN] = 0;
+        assert!(parse_v4(&payload).is_none());
+    }
+}
@@ -0,0 +1,321 @@
+// DNS message parsing (RFC 1035), also used for mDNS which shares the wire format
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+const HEADER_LEN: usize = 12;
+// Compression pointers may chain, but never more than this in a sane message
+const MAX_POINTERS: usize = 32;
+const MAX_NAME_LEN: usize = 255;
+
+/// An entry of the question section
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsQuestion {
+    pub name: String,
+    pub record_type: u16,
+    pub class: u16,
+    // Offset and length of the question within the payload
+    pub offset: usize,
+    pub length: usize,
+}
+
+/// A resource record from the answer, authority or additional section
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsRecord {
+    pub name: String,
+    pub record_type: u16,
+    pub class: u16,
+    pub ttl: u32,
+    // Addresses and names decoded, anything else as hex
+    pub data: String,
+    pub offset: usize,
+    pub length: usize,
+}
+
+/// A decoded DNS message, header flags and all four sections
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsMessage {
+    pub id: u16,
+    pub response: bool,
+    pub opcode: u8,
+    pub authoritative: bool,
+    pub truncated: bool,
+    pub recursion_desired: bool,
+    pub recursion_available: bool,
+    pub rcode: u8,
+    pub questions: Vec<DnsQuestion>,
+    pub answers: Vec<DnsRecord>,
+    pub authorities: Vec<DnsRecord>,
+    pub additionals: Vec<DnsRecord>,
+}
+
+/// Name of a response code, "Unknown" for anything past NOTZONE
+pub fn rcode_name(rcode: u8) -> &'static str {
+    match rcode {
+        0 => "NOERROR",
+        1 => "FORMERR",
+        2 => "SERVFAIL",
+        3 => "NXDOMAIN",
+        4 => "NOTIMP",
+        5 => "REFUSED",
+        6 => "YXDOMAIN",
+        7 => "YXRRSET",
+        8 => "NXRRSET",
+        9 => "NOTAUTH",
+        10 => "NOTZONE",
+        _ => "Unknown",
+    }
+}
+
+/// Name of a record type, "Unknown" for types the dissector does not decode
+pub fn type_name(record_type: u16) -> &'static str {
+    match record_type {
+        1 => "A",
+        2 => "NS",
+        5 => "CNAME",
+        6 => "SOA",
+        12 => "PTR",
+        15 => "MX",
+        16 => "TXT",
+        28 => "AAAA",
+        33 => "SRV",
+        41 => "OPT",
+        64 => "SVCB",
+        65 => "HTTPS",
+        255 => "ANY",
+        _ => "Unknown",
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+// Reads a possibly compressed name, returns it and the offset just past it in the message
+fn read_name(data: &[u8], mut offset: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut name_len = 0;
+    let mut end = None;
+    let mut pointers = 0;
+    loop {
+        let len = *data.get(offset)? as usize;
+        match len & 0xc0 {
+            0x00 if len == 0 => {
+                let end = end.unwrap_or(offset + 1);
+                let name = if labels.is_empty() { "<Root>".to_string() } else { labels.join(".") };
+                return Some((name, end));
+            }
+            0x00 => {
+                let label = data.get(offset + 1..offset + 1 + len)?;
+                name_len += len + 1;
+                if name_len > MAX_NAME_LEN {
+                    return None;
+                }
+                labels.push(String::from_utf8_lossy(label).to_string());
+                offset += 1 + len;
+            }
+            0xc0 => {
+                pointers += 1;
+                if pointers > MAX_POINTERS {
+                    return None;
+                }
+                let target = (u16_at(data, offset)? & 0x3fff) as usize;
+                end.get_or_insert(offset + 2);
+                offset = target;
+            }
+            // Extended label types were never deployed
+            _ => return None,
+        }
+    }
+}
+
+fn record_data(data: &[u8], record_type: u16, offset: usize, rdata: &[u8]) -> String {
+    let name_at = |at: usize| read_name(data, at).map(|(name, _)| name);
+    let decoded = match record_type {
+        1 => <[u8; 4]>::try_from(rdata).ok().map(|bytes| Ipv4Addr::from(bytes).to_string()),
+        28 => <[u8; 16]>::try_from(rdata).ok().map(|bytes| Ipv6Addr::from(bytes).to_string()),
+        2 | 5 | 12 => name_at(offset),
+        15 => u16_at(rdata, 0).and_then(|preference| Some(format!("{} {}", preference, name_at(offset + 2)?))),
+        16 => {
+            let mut strings = Vec::new();
+            let mut rest = rdata;
+            while let Some((&len, tail)) = rest.split_first() {
+                let text = tail.get(..len as usize).unwrap_or(tail);
+                strings.push(format!("\"{}\"", String::from_utf8_lossy(text)));
+                rest = &tail[text.len()..];
+            }
+            Some(strings.join(" "))
+        }
+        _ => None,
+    };
+    decoded.unwrap_or_else(|| rdata.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+fn read_records(data: &[u8], offset: &mut usize, count: u16) -> Option<Vec<DnsRecord>> {
+    let mut records = Vec::new();
+    for _ in 0..count {
+        let start = *offset;
+        let (name, after_name) = read_name(data, start)?;
+        let record_type = u16_at(data, after_name)?;
+        let class = u16_at(data, after_name + 2)?;
+        let ttl = u32_at(data, after_name + 4)?;
+        let rdata_len = u16_at(data, after_name + 8)? as usize;
+        let rdata_offset = after_name + 10;
+        let rdata = data.get(rdata_offset..rdata_offset + rdata_len)?;
+        *offset = rdata_offset + rdata_len;
+        records.push(DnsRecord {
+            name,
+            record_type,
+            class,
+            ttl,
+            data: record_data(data, record_type, rdata_offset, rdata),
+            offset: start,
+            length: *offset - start,
+        });
+    }
+    Some(records)
+}
+
+/// None if the header or any question/record runs past the end of the payload
+pub fn parse(payload: &[u8]) -> Option<DnsMessage> {
+    if payload.len() < HEADER_LEN {
+        return None;
+    }
+    let flags = u16_at(payload, 2)?;
+    let question_count = u16_at(payload, 4)?;
+    let answer_count = u16_at(payload, 6)?;
+    let authority_count = u16_at(payload, 8)?;
+    let additional_count = u16_at(payload, 10)?;
+
+    let mut offset = HEADER_LEN;
+    let mut questions = Vec::new();
+    for _ in 0..question_count {
+        let start = offset;
+        let (name, after_name) = read_name(payload, start)?;
+        let record_type = u16_at(payload, after_name)?;
+        let class = u16_at(payload, after_name + 2)?;
+        offset = after_name + 4;
+        questions.push(DnsQuestion { name, record_type, class, offset: start, length: offset - start });
+    }
+    let answers = read_records(payload, &mut offset, answer_count)?;
+    let authorities = read_records(payload, &mut offset, authority_count)?;
+    let additionals = read_records(payload, &mut offset, additional_count)?;
+
+    Some(DnsMessage {
+        id: u16_at(payload, 0)?,
+        response: flags & 0x8000 != 0,
+        opcode: ((flags >> 11) & 0x0f) as u8,
+        authoritative: flags & 0x0400 != 0,
+        truncated: flags & 0x0200 != 0,
+        recursion_desired: flags & 0x0100 != 0,
+        recursion_available: flags & 0x0080 != 0,
+        rcode: (flags & 0x000f) as u8,
+        questions,
+        answers,
+        authorities,
+        additionals,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(id: u16, flags: u16, questions: u16, answers: u16) -> Vec<u8> {
+        let mut message = Vec::new();
+        for field in [id, flags, questions, answers, 0, 0] {
+            message.extend(field.to_be_bytes());
+        }
+        message
+    }
+
+    fn name(labels: &[&str]) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        for label in labels {
+            encoded.push(label.len() as u8);
+            encoded.extend(label.as_bytes());
+        }
+        encoded.push(0);
+        encoded
+    }
+
+    // A response for example.com/A whose answer names the question through a pointer
+    fn response() -> Vec<u8> {
+        let mut message = header(0x1234, 0x8183, 1, 1);
+        message.extend(name(&["www", "example", "com"]));
+        message.extend([0, 1, 0, 1]);
+        // Pointer to "example.com" inside the question
+        message.extend([0xc0, 16]);
+        message.extend([0, 1, 0, 1, 0, 0, 0x0e, 0x10, 0, 4, 93, 184, 216, 34]);
+        message
+    }
+
+    #[test]
+    fn follows_compression_pointers() {
+        let message = parse(&response()).unwrap();
+        assert_eq!((message.id, message.response, message.rcode), (0x1234, true, 3));
+        assert!(message.recursion_desired && message.recursion_available);
+        assert_eq!(message.questions[0].name, "www.example.com");
+        assert_eq!((message.questions[0].offset, message.questions[0].length), (12, 21));
+
+        let answer = &message.answers[0];
+        assert_eq!((answer.name.as_str(), answer.record_type, answer.ttl), ("example.com", 1, 3600));
+        assert_eq!(answer.data, "93.184.216.34");
+        assert_eq!((answer.offset, answer.length), (33, 16));
+    }
+
+    #[test]
+    fn pointer_loops_are_rejected() {
+        // A name that points at itself
+        let mut message = header(1, 0, 1, 0);
+        message.extend([0xc0, 12, 0, 1, 0, 1]);
+        assert!(parse(&message).is_none());
+
+        // Two labels pointing at each other, each hop adds to the chain
+        let mut message = header(1, 0, 1, 0);
+        message.extend([1, b'a', 0xc0, 16, 1, b'b', 0xc0, 12, 0, 1, 0, 1]);
+        assert!(parse(&message).is_none());
+
+        // A chain of MAX_POINTERS pointers still resolves
+        let mut message = header(1, 0, 1, 0);
+        let first = message.len();
+        for hop in 1..=MAX_POINTERS {
+            message.extend([0xc0, (first + hop * 2) as u8]);
+        }
+        message.extend(name(&["end"]));
+        message.extend([0, 1, 0, 1]);
+        assert_eq!(parse(&message).unwrap().questions[0].name, "end");
+    }
+
+    #[test]
+    fn over_long_names_are_rejected() {
+        // 4 labels of 63 bytes make 256 bytes with their length bytes, one over the limit
+        let label = "a".repeat(63);
+        let mut message = header(1, 0, 1, 0);
+        message.extend(name(&[&label, &label, &label, &label]));
+        message.extend([0, 1, 0, 1]);
+        assert!(parse(&message).is_none());
+
+        let mut message = header(1, 0, 1, 0);
+        message.extend(name(&[&label, &label, &label, &"a".repeat(62)]));
+        message.extend([0, 1, 0, 1]);
+        assert_eq!(parse(&message).unwrap().questions[0].name.len(), MAX_NAME_LEN - 1);
+    }
+
+    #[test]
+    fn truncated_messages_are_rejected() {
+        let message = response();
+        assert!(parse(&message[..HEADER_LEN - 1]).is_none());
+        // Cut inside a label, the question type, the answer header and the answer data
+        for cut in [15, 30, 40, message.len() - 1] {
+            assert!(parse(&message[..cut]).is_none(), "cut at {}", cut);
+        }
+        // Fewer records than the header promises
+        let mut message = header(1, 0, 2, 0);
+        message.extend(name(&["a"]));
+        message.extend([0, 1, 0, 1]);
+        assert!(parse(&message).is_none());
+    }
+}
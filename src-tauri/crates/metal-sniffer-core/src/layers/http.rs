@@ -0,0 +1,171 @@
+// HTTP/1.x start line and header parsing (RFC 9112). Only the segment at hand is looked at,
+// so a message whose headers continue in the next segment reports the headers seen so far.
+
+const MAX_HEADERS: usize = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HttpStartLine {
+    Request { method: String, uri: String, version: String },
+    Response { version: String, status: u16, reason: String },
+}
+
+/// A header, offset and length cover the whole `name: value` line without its line ending
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpHeader {
+    pub name: String,
+    pub value: String,
+    pub offset: usize,
+    pub length: usize,
+}
+
+/// The start line and headers of an HTTP/1.x request or response
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpMessage {
+    pub start_line: HttpStartLine,
+    // Length of the start line without its line ending
+    pub start_line_length: usize,
+    pub headers: Vec<HttpHeader>,
+}
+
+impl HttpMessage {
+    /// Value of the first header called `name`, compared case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case(name))
+            .map(|header| header.value.as_str())
+    }
+}
+
+// Lines up to the blank line ending the headers, or up to the last complete line
+fn lines(payload: &[u8]) -> impl Iterator<Item = (usize, &[u8])> {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        let rest = payload.get(offset..)?;
+        let end = rest.iter().position(|&byte| byte == b'\n')?;
+        let line = rest[..end].strip_suffix(b"\r").unwrap_or(&rest[..end]);
+        let start = offset;
+        offset += end + 1;
+        Some((start, line))
+    })
+    .take_while(|(_, line)| !line.is_empty())
+}
+
+fn is_token(text: &str) -> bool {
+    !text.is_empty() && text.bytes().all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
+
+fn parse_start_line(line: &str) -> Option<HttpStartLine> {
+    if let Some(rest) = line.strip_prefix("HTTP/") {
+        let (version, rest) = rest.split_once(' ')?;
+        let (status, reason) = rest.split_once(' ').unwrap_or((rest, ""));
+        if status.len() != 3 {
+            return None;
+        }
+        return Some(HttpStartLine::Response {
+            version: format!("HTTP/{}", version),
+            status: status.parse().ok()?,
+            reason: reason.to_string(),
+        });
+    }
+
+    let mut parts = line.split(' ');
+    let (method, uri, version) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || !is_token(method) || uri.is_empty() || !version.starts_with("HTTP/") {
+        return None;
+    }
+    Some(HttpStartLine::Request {
+        method: method.to_string(),
+        uri: uri.to_string(),
+        version: version.to_string(),
+    })
+}
+
+/// None unless the payload starts with a complete request or status line, so body segments
+/// and binary traffic on HTTP ports are left alone
+pub fn parse(payload: &[u8]) -> Option<HttpMessage> {
+    let mut lines = lines(payload);
+    let (_, start_line) = lines.next()?;
+    let start_line_length = start_line.len();
+    let start_line = parse_start_line(std::str::from_utf8(start_line).ok()?)?;
+
+    let headers = lines
+        .take(MAX_HEADERS)
+        .filter_map(|(offset, line)| {
+            let line = std::str::from_utf8(line).ok()?;
+            let (name, value) = line.split_once(':')?;
+            if !is_token(name) {
+                return None;
+            }
+            Some(HttpHeader {
+                name: name.to_string(),
+                value: value.trim().to_string(),
+                offset,
+                length: line.len(),
+            })
+        })
+        .collect();
+
+    Some(HttpMessage { start_line, start_line_length, headers })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display_filter::DisplayFilter;
+    use crate::layers::layer3::{LayerThree, HTTP};
+    use crate::layers::packet_info::PacketInfo;
+
+    #[test]
+    fn parses_requests() {
+        let message = parse(b"GET /index.html HTTP/1.1\r\nHost: example.com\r\nUser-Agent: curl/8.0\r\n\r\nbody").unwrap();
+        assert_eq!(
+            message.start_line,
+            HttpStartLine::Request { method: "GET".into(), uri: "/index.html".into(), version: "HTTP/1.1".into() }
+        );
+        assert_eq!(message.start_line_length, 24);
+        assert_eq!(message.header("host"), Some("example.com"));
+        assert_eq!((message.headers[0].offset, message.headers[0].length), (26, 17));
+        assert_eq!(message.headers.len(), 2);
+    }
+
+    #[test]
+    fn parses_responses() {
+        let message = parse(b"HTTP/1.1 503 Service Unavailable\nRetry-After: 10\n").unwrap();
+        assert_eq!(
+            message.start_line,
+            HttpStartLine::Response { version: "HTTP/1.1".into(), status: 503, reason: "Service Unavailable".into() }
+        );
+        assert_eq!(message.header("Retry-After"), Some("10"));
+
+        // The reason phrase may be empty
+        let message = parse(b"HTTP/1.0 204\r\n\r\n").unwrap();
+        assert!(matches!(message.start_line, HttpStartLine::Response { status: 204, .. }));
+    }
+
+    #[test]
+    fn ignores_anything_else() {
+        assert!(parse(b"").is_none());
+        assert!(parse(b"GET / HTTP/1.1").is_none());
+        assert!(parse(b"{\"json\": \"body\"}\r\n").is_none());
+        assert!(parse(b"HTTP/1.1 5000 Nope\r\n").is_none());
+        assert!(parse(b"GET /a b HTTP/1.1\r\n").is_none());
+        assert!(parse(&[0x16, 0x03, 0x01, 0x00, b'\n']).is_none());
+    }
+
+    #[test]
+    fn fields_can_be_filtered() {
+        let matches = |payload: &[u8], filter: &str| {
+            let fields = vec![LayerThree::HTTP(HTTP { payload: payload.to_vec() }).get_field_tree()];
+            DisplayFilter::compile(filter).unwrap().matches(&fields)
+        };
+        let response = b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\n\r\n";
+        assert!(matches(response, "http.status >= 500"));
+        assert!(matches(response, "http.response and http.content_length == 0"));
+        assert!(!matches(b"HTTP/1.1 200 OK\r\n\r\n", "http.status >= 500"));
+
+        let request = b"POST /login HTTP/1.1\r\nHost: intranet.corp\r\n\r\n";
+        assert!(matches(request, "http.request.method == \"POST\" and http.host contains \"corp\""));
+        assert!(!matches(request, "http.status"));
+    }
+}
@@ -0,0 +1,48 @@
+use super::packet_info;
+use crate::field_tree::{Field, FieldValue};
+use pnet::packet::{ipv4::Ipv4Packet, ipv6::Ipv6Packet};
+use std::net::IpAddr;
+
+/// The network layer of a packet, holding the IP packet from its header on
+#[derive(Debug, Clone, PartialEq,)]
+pub enum LayerOne {
+    V4(Vec<u8>),
+    V6(Vec<u8>)
+}
+
+impl packet_info::PacketInfo for LayerOne {
+    fn get_field_tree(&self) -> Field {
+        match self {
+            LayerOne::V4(ip_data) => {
+                let mut tree = Field::protocol("ip", "IPv4");
+                if let Some(ip_packet) = Ipv4Packet::new(ip_data) {
+                    // Version shares its byte with the header length
+                    tree.push(Field::new("ip.version", "Version", FieldValue::UInt(4)).with_display("IPv4".into()).with_span(0, 1));
+                    tree.push(Field::new("ip.src", "Source IP", FieldValue::Ip(IpAddr::V4(ip_packet.get_source()))).with_span(12, 4));
+                    tree.push(Field::new("ip.dst", "Destination IP", FieldValue::Ip(IpAddr::V4(ip_packet.get_destination()))).with_span(16, 4));
+                    tree.push(Field::new("ip.ttl", "TTL", FieldValue::UInt(ip_packet.get_ttl() as u64)).with_span(8, 1));
+                    tree.push(Field::new("ip.len", "Total Length", FieldValue::UInt(ip_packet.get_total_length() as u64)).with_span(2, 2));
+                } else {
+                    tree.push(Field::malformed("Invalid IPv4 packet"));
+                }
+                tree
+            }
+            LayerOne::V6(ip_data) => {
+                let mut tree = Field::protocol("ipv6", "IPv6");
+                if let Some(ip_packet) = Ipv6Packet::new(ip_data) {
+                    // Version, traffic class and flow label are packed into the first four bytes
+                    tree.push(Field::new("ipv6.version", "Version", FieldValue::UInt(6)).with_display("IPv6".into()).with_span(0, 1));
+                    tree.push(Field::new("ipv6.src", "Source IP", FieldValue::Ip(IpAddr::V6(ip_packet.get_source()))).with_span(8, 16));
+                    tree.push(Field::new("ipv6.dst", "Destination IP", FieldValue::Ip(IpAddr::V6(ip_packet.get_destination()))).with_span(24, 16));
+                    tree.push(Field::new("ipv6.tclass", "Traffic Class", FieldValue::UInt(ip_packet.get_traffic_class() as u64)).with_span(0, 2));
+                    tree.push(Field::new("ipv6.flow", "Flow Label", FieldValue::UInt(ip_packet.get_flow_label() as u64)).with_span(1, 3));
+                } else {
+                    tree.push(Field::malformed("Invalid IPv6 packet"));
+                }
+                tree
+            }
+        }
+    }
+}
+
+// First vec is raw packet and second is the layer packet
\ No newline at end of file
@@ -0,0 +1,84 @@
+use pnet::packet::{icmpv6::Icmpv6Packet, tcp::TcpPacket, udp::UdpPacket};
+use super::packet_info::PacketInfo;
+use crate::field_tree::{Field, FieldValue};
+use std::collections::HashMap;
+
+/// The transport layer of a packet, holding the segment from its header on
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayerTwo {
+    TCP(Vec<u8>),
+    UDP(Vec<u8>),
+    ICMPV6(Vec<u8>)
+}
+
+
+// Implement for LayerTwo
+
+impl PacketInfo for LayerTwo {
+    fn get_field_tree(&self) -> Field {
+        match self {
+            LayerTwo::TCP(tcp_data) => {
+                let mut tree = Field::protocol("tcp", "TCP");
+                if let Some(tcp_packet) = TcpPacket::new(tcp_data) {
+                    let flags = tcp_packet.get_flags();
+                    let flag_fields = vec![
+                        Field::new("tcp.flags.ack", "ACK", FieldValue::Bool(flags & 0b00010000 != 0)).with_span(13, 1),
+                        Field::new("tcp.flags.reset", "RST", FieldValue::Bool(flags & 0b00000100 != 0)).with_span(13, 1),
+                        Field::new("tcp.flags.syn", "SYN", FieldValue::Bool(flags & 0b00000010 != 0)).with_span(13, 1),
+                        Field::new("tcp.flags.fin", "FIN", FieldValue::Bool(flags & 0b00000001 != 0)).with_span(13, 1),
+                    ];
+                    let flags_display = flag_fields
+                        .iter()
+                        .map(|flag| format!("{}:{}", flag.display_name, flag.display))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+
+                    tree.push(Field::new("tcp.srcport", "Source Port", FieldValue::UInt(tcp_packet.get_source() as u64)).with_span(0, 2));
+                    tree.push(Field::new("tcp.dstport", "Destination Port", FieldValue::UInt(tcp_packet.get_destination() as u64)).with_span(2, 2));
+                    tree.push(Field::new("tcp.seq", "Sequence", FieldValue::UInt(tcp_packet.get_sequence() as u64)).with_span(4, 4));
+                    tree.push(Field::new("tcp.window_size", "Window Size", FieldValue::UInt(tcp_packet.get_window() as u64)).with_span(14, 2));
+                    tree.push(
+                        Field::new("tcp.flags", "Flags", FieldValue::UInt(flags as u64))
+                            .with_display(flags_display)
+                            .with_span(12, 2)
+                            .with_children(flag_fields),
+                    );
+                } else {
+                    tree.push(Field::malformed("Invalid TCP packet"));
+                }
+                tree
+            }
+            LayerTwo::UDP(udp_data) => {
+                let mut tree = Field::protocol("udp", "UDP");
+                if let Some(udp_packet) = UdpPacket::new(udp_data) {
+                    let checksum = udp_packet.get_checksum();
+                    tree.push(Field::new("udp.srcport", "Source Port", FieldValue::UInt(udp_packet.get_source() as u64)).with_span(0, 2));
+                    tree.push(Field::new("udp.dstport", "Destination Port", FieldValue::UInt(udp_packet.get_destination() as u64)).with_span(2, 2));
+                    tree.push(Field::new("udp.length", "Length", FieldValue::UInt(udp_packet.get_length() as u64)).with_span(4, 2));
+                    tree.push(Field::new("udp.checksum", "Checksum", FieldValue::UInt(checksum as u64)).with_display(format!("0x{:04x}", checksum)).with_span(6, 2));
+                } else {
+                    tree.push(Field::malformed("Invalid UDP packet"));
+                }
+                tree
+            }
+            LayerTwo::ICMPV6(icmp_data) => {
+                let mut tree = Field::protocol("icmpv6", "ICMPv6");
+                if let Some(icmp_packet) = Icmpv6Packet::new(icmp_data) {
+                    let checksum = icmp_packet.get_checksum();
+                    tree.push(Field::new("icmpv6.checksum", "Checksum", FieldValue::UInt(checksum as u64)).with_display(format!("0x{:04x}", checksum)).with_span(2, 2));
+                } else {
+                    tree.push(Field::malformed("Invalid ICMPv6 packet"));
+                }
+                tree
+            }
+        }
+    }
+
+    // The flat map has always carried the protocol name alongside the fields
+    fn get_info(&self) -> HashMap<String, String> {
+        let tree = self.get_field_tree();
+        let mut info = tree.to_info_map();
+        info.insert("Protocol".into(), tree.display);
+        info
+    }
+}
\ No newline at end of file
@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use super::packet_info::PacketInfo;
+use super::{dhcp, dns, http, quic};
+use crate::field_tree::{Field, FieldValue};
+
+/// The application layer payload and the dissector chosen for it
+#[derive(Debug, Clone)]
+pub enum LayerThree {
+    DNS(DNS),
+    MDNS(MDNS),
+    HTTP(HTTP),
+    HTTPS(HTTPS),
+    QUIC(QUIC),
+    DHCP(DHCP),
+    DHCPV6(DHCPV6),
+    Raw(Raw)
+}
+
+/// DNS over UDP or TCP
+#[derive(Debug, Clone)]
+pub struct DNS {
+    pub payload: Vec<u8>,
+}
+
+/// Multicast DNS on port 5353
+#[derive(Debug, Clone)]
+pub struct MDNS {
+    pub payload: Vec<u8>,
+}
+
+/// Plain text HTTP
+#[derive(Debug, Clone)]
+pub struct HTTP {
+    pub payload: Vec<u8>,
+}
+
+/// TLS, only the ClientHello is dissected
+#[derive(Debug, Clone)]
+pub struct HTTPS {
+    pub payload: Vec<u8>,
+}
+
+/// QUIC, Initial packets are decrypted to reach the ClientHello
+#[derive(Debug, Clone)]
+pub struct QUIC {
+    pub payload: Vec<u8>,
+}
+
+/// DHCPv4 on ports 67/68
+#[derive(Debug, Clone)]
+pub struct DHCP {
+    pub payload: Vec<u8>,
+}
+
+/// DHCPv6 on ports 546/547
+#[derive(Debug, Clone)]
+pub struct DHCPV6 {
+    pub payload: Vec<u8>,
+}
+
+/// A payload no dissector claimed
+#[derive(Debug, Clone)]
+pub struct Raw {
+    pub payload: Vec<u8>,
+}
+
+impl LayerThree {
+    /// The application payload, whatever the dissector
+    pub fn payload(&self) -> &[u8] {
+        match self {
+            LayerThree::DNS(dns) => &dns.payload,
+            LayerThree::MDNS(mdns) => &mdns.payload,
+            LayerThree::HTTP(http) => &http.payload,
+            LayerThree::HTTPS(https) => &https.payload,
+            LayerThree::QUIC(quic) => &quic.payload,
+            LayerThree::DHCP(dhcp) => &dhcp.payload,
+            LayerThree::DHCPV6(dhcp) => &dhcp.payload,
+            LayerThree::Raw(raw) => &raw.payload,
+        }
+    }
+
+    /// Length of the application payload in bytes
+    pub fn payload_len(&self) -> usize {
+        self.payload().len()
+    }
+
+    /// Display filter name of the protocol, e.g. `dns`
+    pub fn filter_name(&self) -> &'static str {
+        match self {
+            LayerThree::DNS(_) => "dns",
+            LayerThree::MDNS(_) => "mdns",
+            LayerThree::HTTP(_) => "http",
+            LayerThree::HTTPS(_) => "tls",
+            LayerThree::QUIC(_) => "quic",
+            LayerThree::DHCP(_) => "dhcp",
+            LayerThree::DHCPV6(_) => "dhcpv6",
+            LayerThree::Raw(_) => "data",
+        }
+    }
+
+    /// Name shown to the user, e.g. `DNS`
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            LayerThree::DNS(_) => "DNS",
+            LayerThree::MDNS(_) => "MDNS",
+            LayerThree::HTTP(_) => "HTTP",
+            LayerThree::HTTPS(_) => "HTTPS",
+            LayerThree::QUIC(_) => "QUIC",
+            LayerThree::DHCP(_) => "DHCP",
+            LayerThree::DHCPV6(_) => "DHCPv6",
+            LayerThree::Raw(_) => "Raw/Unknown",
+        }
+    }
+}
+
+// Implement for LayerThree protocols
+impl PacketInfo for LayerThree {
+    fn get_field_tree(&self) -> Field {
+        let mut tree = Field::protocol(self.filter_name(), self.display_name());
+        tree.push(
+            Field::new(&format!("{}.payload_length", self.filter_name()), "Payload Length", FieldValue::UInt(self.payload_len() as u64))
+                .with_span(0, self.payload_len()),
+        );
+
+        match self {
+            LayerThree::DNS(dns) => dns_fields(&mut tree, &dns.payload),
+            LayerThree::MDNS(mdns) => dns_fields(&mut tree, &mdns.payload),
+            LayerThree::HTTP(http) => http_fields(&mut tree, &http.payload),
+            LayerThree::QUIC(quic) => quic_fields(&mut tree, &quic.payload),
+            LayerThree::DHCP(dhcp) => dhcp_fields(&mut tree, &dhcp.payload),
+            LayerThree::DHCPV6(dhcp) => dhcpv6_fields(&mut tree, &dhcp.payload),
+            _ => {}
+        }
+        tree
+    }
+
+    // The flat map has always carried the protocol name alongside the fields
+    fn get_info(&self) -> HashMap<String, String> {
+        let tree = self.get_field_tree();
+        let mut info = tree.to_info_map();
+        info.insert("Protocol".into(), tree.display);
+        info
+    }
+}
+
+// mDNS shares the format, and like Wireshark its fields are dns.* too
+fn dns_fields(tree: &mut Field, payload: &[u8]) {
+    let Some(message) = dns::parse(payload) else {
+        tree.push(Field::malformed("Invalid DNS message"));
+        return;
+    };
+
+    tree.push(
+        Field::new("dns.id", "Transaction ID", FieldValue::UInt(message.id as u64))
+            .with_display(format!("0x{:04x}", message.id))
+            .with_span(0, 2),
+    );
+    let kind = if message.response { "Response" } else { "Query" };
+    tree.push(Field::new("dns.flags.response", "Message Type", FieldValue::Bool(message.response)).with_display(kind.into()).with_span(2, 2));
+    tree.push(Field::new("dns.flags.opcode", "Opcode", FieldValue::UInt(message.opcode as u64)).with_span(2, 2));
+    if message.response {
+        tree.push(
+            Field::new("dns.flags.rcode", "Reply Code", FieldValue::UInt(message.rcode as u64))
+                .with_display(dns::rcode_name(message.rcode).into())
+                .with_span(2, 2),
+        );
+    }
+    if message.truncated {
+        tree.push(Field::new("dns.flags.truncated", "Truncated", FieldValue::Bool(true)).with_span(2, 2));
+    }
+    tree.push(Field::new("dns.count.queries", "Questions", FieldValue::UInt(message.questions.len() as u64)).with_span(4, 2));
+    tree.push(Field::new("dns.count.answers", "Answer RRs", FieldValue::UInt(message.answers.len() as u64)).with_span(6, 2));
+
+    if !message.questions.is_empty() {
+        let queries = message
+            .questions
+            .iter()
+            .map(|question| {
+                let record_type = dns::type_name(question.record_type);
+                Field::new("dns.qry", &question.name, FieldValue::None)
+                    .with_display(format!("type {}", record_type))
+                    .with_span(question.offset, question.length)
+                    .with_children(vec![
+                        Field::new("dns.qry.name", "Name", FieldValue::Str(question.name.clone())),
+                        Field::new("dns.qry.type", "Type", FieldValue::UInt(question.record_type as u64)).with_display(record_type.into()),
+                    ])
+            })
+            .collect();
+        tree.push(Field::new("dns.queries", "Queries", FieldValue::None).with_children(queries));
+    }
+    if !message.answers.is_empty() {
+        let answers = message.answers.iter().map(dns_answer).collect();
+        tree.push(Field::new("dns.answers", "Answers", FieldValue::None).with_children(answers));
+    }
+}
+
+fn dns_answer(record: &dns::DnsRecord) -> Field {
+    let record_type = dns::type_name(record.record_type);
+    let mut children = vec![
+        Field::new("dns.resp.name", "Name", FieldValue::Str(record.name.clone())),
+        Field::new("dns.resp.type", "Type", FieldValue::UInt(record.record_type as u64)).with_display(record_type.into()),
+        seconds("dns.resp.ttl", "Time to Live", record.ttl),
+    ];
+    let value = match record.record_type {
+        1 | 28 => record.data.parse::<IpAddr>().ok().map(FieldValue::Ip),
+        _ => Some(FieldValue::Str(record.data.clone())),
+    };
+    let data_field = match record.record_type {
+        1 => Some(("dns.a", "Address")),
+        28 => Some(("dns.aaaa", "AAAA Address")),
+        2 => Some(("dns.ns", "Name Server")),
+        5 => Some(("dns.cname", "CNAME")),
+        12 => Some(("dns.ptr.domain_name", "Domain Name")),
+        15 => Some(("dns.mx.mail_exchange", "Mail Exchange")),
+        16 => Some(("dns.txt", "TXT")),
+        _ => None,
+    };
+    if let (Some((name, display_name)), Some(value)) = (data_field, value) {
+        children.push(Field::new(name, display_name, value));
+    }
+    Field::new("dns.resp", &record.name, FieldValue::None)
+        .with_display(format!("type {}, {}", record_type, record.data))
+        .with_span(record.offset, record.length)
+        .with_children(children)
+}
+
+// Segments that do not start a message (bodies, continuations) only get the payload length
+fn http_fields(tree: &mut Field, payload: &[u8]) {
+    let Some(message) = http::parse(payload) else {
+        return;
+    };
+
+    let start_line = message.start_line_length;
+    match &message.start_line {
+        http::HttpStartLine::Request { method, uri, version } => {
+            tree.push(Field::new("http.request", "Request", FieldValue::Bool(true)).with_display(format!("{} {}", method, uri)).with_span(0, start_line));
+            tree.push(Field::new("http.request.method", "Method", FieldValue::Str(method.clone())).with_span(0, method.len()));
+            tree.push(Field::new("http.request.uri", "URI", FieldValue::Str(uri.clone())).with_span(method.len() + 1, uri.len()));
+            tree.push(Field::new("http.request.version", "Version", FieldValue::Str(version.clone())).with_span(start_line - version.len(), version.len()));
+        }
+        http::HttpStartLine::Response { version, status, reason } => {
+            tree.push(Field::new("http.response", "Response", FieldValue::Bool(true)).with_display(format!("{} {}", status, reason)).with_span(0, start_line));
+            tree.push(Field::new("http.response.version", "Version", FieldValue::Str(version.clone())).with_span(0, version.len()));
+            tree.push(
+                Field::new("http.status", "Status Code", FieldValue::UInt(*status as u64))
+                    .with_display(format!("{} {}", status, reason))
+                    .with_span(version.len() + 1, 3),
+            );
+        }
+    }
+
+    let named = [
+        ("host", "http.host", "Host"),
+        ("user-agent", "http.user_agent", "User-Agent"),
+        ("content-type", "http.content_type", "Content-Type"),
+        ("content-length", "http.content_length", "Content-Length"),
+        ("location", "http.location", "Location"),
+        ("server", "http.server", "Server"),
+    ];
+    for header in &message.headers {
+        let Some((_, name, display_name)) = named.iter().find(|(header_name, _, _)| header.name.eq_ignore_ascii_case(header_name)) else {
+            continue;
+        };
+        let value = match (*name, header.value.parse::<u64>()) {
+            ("http.content_length", Ok(length)) => FieldValue::UInt(length),
+            _ => FieldValue::Str(header.value.clone()),
+        };
+        tree.push(Field::new(name, display_name, value).with_span(header.offset, header.length));
+    }
+}
+
+fn quic_fields(tree: &mut Field, payload: &[u8]) {
+    let Some(header) = quic::parse(payload) else {
+        tree.push(Field::malformed("Invalid QUIC header"));
+        return;
+    };
+
+    let form = if header.long_header { "Long" } else { "Short" };
+    tree.push(Field::new("quic.header_form", "Header Form", FieldValue::Str(form.into())).with_span(0, 1));
+    tree.push(Field::new("quic.packet_type", "Packet Type", FieldValue::Str(header.packet_type.name().into())).with_span(0, 1));
+    if let Some(version) = header.version {
+        tree.push(Field::new("quic.version", "Version", FieldValue::UInt(version as u64)).with_display(quic::version_name(version)).with_span(1, 4));
+    }
+    if header.long_header {
+        tree.push(Field::new("quic.dcid", "DCID", FieldValue::Bytes(header.dcid.clone())).with_span(6, header.dcid.len()));
+    }
+    if let Some(scid) = &header.scid {
+        tree.push(Field::new("quic.scid", "SCID", FieldValue::Bytes(scid.clone())).with_span(header.scid_offset + 1, scid.len()));
+    }
+    if header.packet_type == quic::QuicPacketType::Initial {
+        tree.push(Field::new("quic.decrypted", "Decrypted", FieldValue::Bool(header.decrypted)));
+    }
+    // SNI and ALPN come out of the decrypted payload, so they have no position in the frame
+    if let Some(client_hello) = &header.client_hello {
+        if let Some(sni) = &client_hello.sni {
+            tree.push(Field::new("quic.sni", "SNI", FieldValue::Str(sni.clone())));
+        }
+        if !client_hello.alpn.is_empty() {
+            let alpn = client_hello.alpn.iter().map(|alpn| FieldValue::Str(alpn.clone())).collect();
+            tree.push(Field::list("quic.alpn", "ALPN", alpn));
+            if client_hello.alpn.iter().any(|alpn| alpn.starts_with("h3")) {
+                tree.push(Field::new("quic.application", "Application", FieldValue::Str("HTTP/3".into())));
+            }
+        }
+    }
+}
+
+fn option_span<T: PartialEq>(field: Field, options: &[(T, usize, usize)], code: T) -> Field {
+    match options.iter().find(|(option, _, _)| *option == code) {
+        Some((_, offset, length)) => field.with_span(*offset, *length),
+        None => field,
+    }
+}
+
+fn ip_values<T: Into<IpAddr> + Copy>(addresses: &[T]) -> Vec<FieldValue> {
+    addresses.iter().map(|address| FieldValue::Ip((*address).into())).collect()
+}
+
+fn seconds(name: &str, display_name: &str, value: u32) -> Field {
+    Field::new(name, display_name, FieldValue::UInt(value as u64)).with_display(format!("{}s", value))
+}
+
+fn dhcp_fields(tree: &mut Field, payload: &[u8]) {
+    let Some(message) = dhcp::parse_v4(payload) else {
+        tree.push(Field::malformed("Invalid DHCP message"));
+        return;
+    };
+    let options = &message.option_offsets;
+
+    if let Some(message_type) = message.message_type {
+        let field = Field::new("dhcp.type", "Message Type", FieldValue::UInt(message_type as u64))
+            .with_display(dhcp::v4_message_type_name(message_type).into());
+        tree.push(option_span(field, options, 53));
+    }
+    tree.push(
+        Field::new("dhcp.id", "Transaction ID", FieldValue::UInt(message.transaction_id as u64))
+            .with_display(format!("0x{:08x}", message.transaction_id))
+            .with_span(4, 4),
+    );
+    tree.push(Field::new("dhcp.hw.mac_addr", "Client MAC", FieldValue::Str(message.client_mac.clone())).with_span(28, (payload[2] as usize).min(16)));
+    tree.push(Field::new("dhcp.ip.client", "Client IP", FieldValue::Ip(message.client_ip.into())).with_span(12, 4));
+    tree.push(Field::new("dhcp.ip.your", "Your IP", FieldValue::Ip(message.your_ip.into())).with_span(16, 4));
+    tree.push(Field::new("dhcp.ip.server", "Server IP", FieldValue::Ip(message.server_ip.into())).with_span(20, 4));
+    if !message.relay_ip.is_unspecified() {
+        tree.push(Field::new("dhcp.ip.relay", "Relay IP", FieldValue::Ip(message.relay_ip.into())).with_span(24, 4));
+    }
+    if let Some(requested_ip) = message.requested_ip {
+        tree.push(option_span(Field::new("dhcp.requested_ip", "Requested IP", FieldValue::Ip(requested_ip.into())), options, 50));
+    }
+    if let Some(server_identifier) = message.server_identifier {
+        tree.push(option_span(Field::new("dhcp.server_id", "Server Identifier", FieldValue::Ip(server_identifier.into())), options, 54));
+    }
+    if let Some(subnet_mask) = message.subnet_mask {
+        tree.push(option_span(Field::new("dhcp.subnet_mask", "Subnet Mask", FieldValue::Ip(subnet_mask.into())), options, 1));
+    }
+    if !message.routers.is_empty() {
+        tree.push(option_span(Field::list("dhcp.router", "Router", ip_values(&message.routers)), options, 3));
+    }
+    if !message.dns_servers.is_empty() {
+        tree.push(option_span(Field::list("dhcp.dns_server", "DNS Servers", ip_values(&message.dns_servers)), options, 6));
+    }
+    if let Some(domain_name) = &message.domain_name {
+        tree.push(option_span(Field::new("dhcp.domain_name", "Domain Name", FieldValue::Str(domain_name.clone())), options, 15));
+    }
+    if let Some(hostname) = &message.hostname {
+        tree.push(option_span(Field::new("dhcp.hostname", "Hostname", FieldValue::Str(hostname.clone())), options, 12));
+    }
+    if let Some(vendor_class) = &message.vendor_class {
+        tree.push(option_span(Field::new("dhcp.vendor_class", "Vendor Class", FieldValue::Str(vendor_class.clone())), options, 60));
+    }
+    if let Some(client_identifier) = &message.client_identifier {
+        tree.push(option_span(Field::new("dhcp.client_id", "Client Identifier", FieldValue::Str(client_identifier.clone())), options, 61));
+    }
+    if let Some(lease_time) = message.lease_time {
+        tree.push(option_span(seconds("dhcp.lease_time", "Lease Time", lease_time), options, 51));
+    }
+    if let Some(renewal_time) = message.renewal_time {
+        tree.push(option_span(seconds("dhcp.renewal_time", "Renewal Time", renewal_time), options, 58));
+    }
+    if let Some(rebinding_time) = message.rebinding_time {
+        tree.push(option_span(seconds("dhcp.rebinding_time", "Rebinding Time", rebinding_time), options, 59));
+    }
+}
+
+fn dhcpv6_fields(tree: &mut Field, payload: &[u8]) {
+    let Some(message) = dhcp::parse_v6(payload) else {
+        tree.push(Field::malformed("Invalid DHCPv6 message"));
+        return;
+    };
+    let options = &message.option_offsets;
+
+    tree.push(
+        Field::new("dhcpv6.msgtype", "Message Type", FieldValue::UInt(message.message_type as u64))
+            .with_display(dhcp::v6_message_type_name(message.message_type).into())
+            .with_span(0, 1),
+    );
+    tree.push(
+        Field::new("dhcpv6.xid", "Transaction ID", FieldValue::UInt(message.transaction_id as u64))
+            .with_display(format!("0x{:06x}", message.transaction_id))
+            .with_span(1, 3),
+    );
+    if let Some(duid) = &message.client_duid {
+        tree.push(option_span(Field::new("dhcpv6.client_duid", "Client DUID", FieldValue::Bytes(duid.clone())), options, 1));
+        if let Some(mac) = dhcp::duid_mac(duid) {
+            tree.push(option_span(Field::new("dhcpv6.client_mac", "Client MAC", FieldValue::Str(mac)), options, 1));
+        }
+    }
+    if let Some(duid) = &message.server_duid {
+        tree.push(option_span(Field::new("dhcpv6.server_duid", "Server DUID", FieldValue::Bytes(duid.clone())), options, 2));
+    }
+    if let Some(iaid) = message.iaid {
+        let field = Field::new("dhcpv6.iaid", "IAID", FieldValue::UInt(iaid as u64)).with_display(format!("0x{:08x}", iaid));
+        tree.push(match options.iter().find(|(code, _, _)| *code == 3) {
+            Some((_, offset, _)) => field.with_span(*offset, 4),
+            None => field,
+        });
+    }
+    if !message.addresses.is_empty() {
+        let addresses: Vec<_> = message.addresses.iter().map(|address| address.address).collect();
+        tree.push(option_span(Field::list("dhcpv6.addr", "Addresses", ip_values(&addresses)), options, 3));
+        tree.push(seconds("dhcpv6.valid_lifetime", "Valid Lifetime", message.addresses[0].valid_lifetime));
+        tree.push(seconds("dhcpv6.preferred_lifetime", "Preferred Lifetime", message.addresses[0].preferred_lifetime));
+    }
+    if !message.dns_servers.is_empty() {
+        tree.push(option_span(Field::list("dhcpv6.dns_server", "DNS Servers", ip_values(&message.dns_servers)), options, 23));
+    }
+    if !message.domain_search.is_empty() {
+        let domains = message.domain_search.iter().map(|domain| FieldValue::Str(domain.clone())).collect();
+        tree.push(option_span(Field::list("dhcpv6.domain_search", "Domain Search", domains), options, 24));
+    }
+    if let Some(hostname) = &message.hostname {
+        tree.push(option_span(Field::new("dhcpv6.hostname", "Hostname", FieldValue::Str(hostname.clone())), options, 39));
+    }
+    if let Some(vendor_class) = &message.vendor_class {
+        tree.push(option_span(Field::new("dhcpv6.vendor_class", "Vendor Class", FieldValue::Str(vendor_class.clone())), options, 16));
+    }
+}
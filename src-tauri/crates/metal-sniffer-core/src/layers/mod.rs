@@ -0,0 +1,18 @@
+/// DHCPv4 and DHCPv6 message parsing
+pub mod dhcp;
+/// DNS and mDNS message parsing
+pub mod dns;
+/// HTTP/1.x request and status lines and headers
+pub mod http;
+/// Network layer: IPv4 and IPv6
+pub mod layer1;
+/// Transport layer: TCP, UDP and ICMPv6
+pub mod layer2;
+/// Application layer payloads and their dissectors
+pub mod layer3;
+/// The trait every layer implements to produce its fields
+pub mod packet_info;
+/// QUIC headers and Initial packet decryption
+pub mod quic;
+/// TLS ClientHello parsing for SNI and ALPN
+pub mod tls;
\ No newline at end of file
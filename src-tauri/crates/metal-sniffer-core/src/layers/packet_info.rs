@@ -0,0 +1,37 @@
+use std::collections::HashMap;
+use serde::Serialize;
+
+use crate::field_tree::Field;
+
+/// Where a field from get_info lives, relative to the start of its layer
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldSpan {
+    pub field: String,
+    pub offset: usize,
+    pub length: usize,
+}
+
+/// Implemented by each layer to describe itself as fields
+pub trait PacketInfo {
+    /// Typed, ordered fields of the layer with offsets relative to the start of the layer
+    fn get_field_tree(&self) -> Field;
+
+    /// The original flat "display name -> value" shape, derived from the tree
+    fn get_info(&self) -> HashMap<String, String> {
+        self.get_field_tree().to_info_map()
+    }
+
+    fn get_field_spans(&self) -> Vec<FieldSpan> {
+        self.get_field_tree()
+            .children
+            .into_iter()
+            .filter_map(|field| {
+                Some(FieldSpan {
+                    offset: field.offset?,
+                    length: field.length?,
+                    field: field.display_name,
+                })
+            })
+            .collect()
+    }
+}
\ No newline at end of file
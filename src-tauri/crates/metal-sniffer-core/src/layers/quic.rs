@@ -0,0 +1,419 @@
+// QUIC header parsing and Initial packet decryption (RFC 9000 / RFC 9001 / RFC 9369).
+// Initial packets are only protected with keys derived from the client's Destination
+// Connection ID, so anyone on the path can read the ClientHello inside them.
+
+use aes::cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit};
+use aes::Aes128;
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::Aes128Gcm;
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+use super::tls::{parse_client_hello, ClientHello};
+
+const VERSION_NEGOTIATION: u32 = 0x0000_0000;
+const VERSION_1: u32 = 0x0000_0001;
+const VERSION_2: u32 = 0x6b33_43cf;
+const VERSION_DRAFT_29: u32 = 0xff00_001d;
+
+const SALT_V1: [u8; 20] = [
+    0x38, 0x76, 0x2c, 0xf7, 0xf5, 0x59, 0x34, 0xb3, 0x4d, 0x17, 0x9a, 0xe6, 0xa4, 0xc8, 0x0c, 0xad,
+    0xcc, 0xbb, 0x7f, 0x0a,
+];
+const SALT_V2: [u8; 20] = [
+    0x0d, 0xed, 0xe3, 0xde, 0xf7, 0x00, 0xa6, 0xdb, 0x81, 0x93, 0x81, 0xbe, 0x6e, 0x26, 0x9d, 0xcb,
+    0xf9, 0xbd, 0x2e, 0xd9,
+];
+const SALT_DRAFT_29: [u8; 20] = [
+    0xaf, 0xbf, 0xec, 0x28, 0x99, 0x93, 0xd2, 0x4c, 0x9e, 0x97, 0x86, 0xf1, 0x9c, 0x61, 0x11, 0xe0,
+    0x43, 0x90, 0xa8, 0x99,
+];
+
+/// Packet type from the first byte and, for long headers, the version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuicPacketType {
+    Initial,
+    ZeroRtt,
+    Handshake,
+    Retry,
+    VersionNegotiation,
+    Short,
+}
+
+impl QuicPacketType {
+    /// Name shown in the packet details
+    pub fn name(&self) -> &'static str {
+        match self {
+            QuicPacketType::Initial => "Initial",
+            QuicPacketType::ZeroRtt => "0-RTT",
+            QuicPacketType::Handshake => "Handshake",
+            QuicPacketType::Retry => "Retry",
+            QuicPacketType::VersionNegotiation => "Version Negotiation",
+            QuicPacketType::Short => "1-RTT (Short Header)",
+        }
+    }
+}
+
+/// The unprotected header fields of a QUIC packet
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuicHeader {
+    pub long_header: bool,
+    pub packet_type: QuicPacketType,
+    pub version: Option<u32>,
+    pub dcid: Vec<u8>,
+    pub scid: Option<Vec<u8>>,
+    // Offset of the SCID length byte, the DCID always starts at 6 in a long header
+    pub scid_offset: usize,
+    // Only filled in for Initial packets we managed to decrypt
+    pub client_hello: Option<ClientHello>,
+    pub decrypted: bool,
+}
+
+/// "1", "2", "draft-NN" or the version in hex
+pub fn version_name(version: u32) -> String {
+    match version {
+        VERSION_NEGOTIATION => "Version Negotiation".to_string(),
+        VERSION_1 => "1".to_string(),
+        VERSION_2 => "2".to_string(),
+        v if v >> 8 == 0xff0000 => format!("draft-{}", v & 0xff),
+        v => format!("0x{:08x}", v),
+    }
+}
+
+/// Quick check used before handing a UDP payload to the QUIC dissector
+pub fn looks_like_quic(payload: &[u8]) -> bool {
+    match payload.first() {
+        // Long headers always carry a version, short headers need the fixed bit
+        Some(first) if first & 0x80 != 0 => payload.len() >= 7,
+        Some(first) => first & 0x40 != 0,
+        None => false,
+    }
+}
+
+/// Parses the header, decrypting Initial packets to read the ClientHello
+pub fn parse(payload: &[u8]) -> Option<QuicHeader> {
+    let first = *payload.first()?;
+
+    if first & 0x80 == 0 {
+        // Short header: the DCID length was negotiated out of band, so it can't be known here
+        return Some(QuicHeader {
+            long_header: false,
+            packet_type: QuicPacketType::Short,
+            version: None,
+            dcid: Vec::new(),
+            scid: None,
+            scid_offset: 0,
+            client_hello: None,
+            decrypted: false,
+        });
+    }
+
+    let version = u32::from_be_bytes(payload.get(1..5)?.try_into().ok()?);
+    let mut pos = 5;
+    let dcid_len = *payload.get(pos)? as usize;
+    let dcid = payload.get(pos + 1..pos + 1 + dcid_len)?.to_vec();
+    pos += 1 + dcid_len;
+    let scid_offset = pos;
+    let scid_len = *payload.get(pos)? as usize;
+    let scid = payload.get(pos + 1..pos + 1 + scid_len)?.to_vec();
+    pos += 1 + scid_len;
+
+    let packet_type = long_packet_type(version, first);
+    let mut header = QuicHeader {
+        long_header: true,
+        packet_type,
+        version: Some(version),
+        dcid,
+        scid: Some(scid),
+        scid_offset,
+        client_hello: None,
+        decrypted: false,
+    };
+
+    if packet_type == QuicPacketType::Initial {
+        if let Some(crypto) = decrypt_initial(payload, pos, version, &header.dcid) {
+            header.decrypted = true;
+            header.client_hello = parse_client_hello(&crypto);
+        }
+    }
+    Some(header)
+}
+
+fn long_packet_type(version: u32, first: u8) -> QuicPacketType {
+    if version == VERSION_NEGOTIATION {
+        return QuicPacketType::VersionNegotiation;
+    }
+    let bits = (first >> 4) & 0x03;
+    // QUIC v2 shuffled the long header type codes
+    let bits = if version == VERSION_2 { (bits + 3) % 4 } else { bits };
+    match bits {
+        0 => QuicPacketType::Initial,
+        1 => QuicPacketType::ZeroRtt,
+        2 => QuicPacketType::Handshake,
+        _ => QuicPacketType::Retry,
+    }
+}
+
+// Variable length integer encoding from RFC 9000 section 16
+fn read_varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let first = *data.get(*pos)?;
+    let len = 1usize << (first >> 6);
+    let bytes = data.get(*pos..*pos + len)?;
+    let mut value = (first & 0x3f) as u64;
+    for byte in &bytes[1..] {
+        value = (value << 8) | *byte as u64;
+    }
+    *pos += len;
+    Some(value)
+}
+
+struct InitialKeys {
+    key: [u8; 16],
+    iv: [u8; 12],
+    hp: [u8; 16],
+}
+
+fn hkdf_expand_label(secret: &[u8], label: &str, out: &mut [u8]) -> Option<()> {
+    let hkdf = Hkdf::<Sha256>::from_prk(secret).ok()?;
+    let full_label = format!("tls13 {}", label);
+    let mut info = Vec::with_capacity(4 + full_label.len());
+    info.extend_from_slice(&(out.len() as u16).to_be_bytes());
+    info.push(full_label.len() as u8);
+    info.extend_from_slice(full_label.as_bytes());
+    // Empty context
+    info.push(0);
+    hkdf.expand(&info, out).ok()
+}
+
+fn client_initial_keys(version: u32, dcid: &[u8]) -> Option<InitialKeys> {
+    let (salt, prefix) = match version {
+        VERSION_1 => (&SALT_V1, "quic"),
+        VERSION_2 => (&SALT_V2, "quicv2"),
+        VERSION_DRAFT_29 => (&SALT_DRAFT_29, "quic"),
+        _ => return None,
+    };
+
+    let (initial_secret, _) = Hkdf::<Sha256>::extract(Some(salt), dcid);
+    let mut client_secret = [0u8; 32];
+    hkdf_expand_label(&initial_secret, "client in", &mut client_secret)?;
+
+    let mut keys = InitialKeys { key: [0; 16], iv: [0; 12], hp: [0; 16] };
+    hkdf_expand_label(&client_secret, &format!("{} key", prefix), &mut keys.key)?;
+    hkdf_expand_label(&client_secret, &format!("{} iv", prefix), &mut keys.iv)?;
+    hkdf_expand_label(&client_secret, &format!("{} hp", prefix), &mut keys.hp)?;
+    Some(keys)
+}
+
+// Removes header protection and decrypts a client Initial packet, returning the
+// reassembled CRYPTO stream starting at offset 0
+fn decrypt_initial(packet: &[u8], mut pos: usize, version: u32, dcid: &[u8]) -> Option<Vec<u8>> {
+    let keys = client_initial_keys(version, dcid)?;
+
+    let token_len = read_varint(packet, &mut pos)? as usize;
+    pos = pos.checked_add(token_len)?;
+    let length = read_varint(packet, &mut pos)? as usize;
+    let pn_offset = pos;
+    let packet_end = pn_offset.checked_add(length)?;
+    if packet_end > packet.len() {
+        return None;
+    }
+
+    // Header protection: sample 16 bytes assuming a 4 byte packet number
+    let sample = packet.get(pn_offset + 4..pn_offset + 20)?;
+    let hp = Aes128::new(GenericArray::from_slice(&keys.hp));
+    let mut mask = GenericArray::clone_from_slice(sample);
+    hp.encrypt_block(&mut mask);
+
+    let mut header = packet.get(..pn_offset)?.to_vec();
+    header[0] ^= mask[0] & 0x0f;
+    let pn_len = (header[0] & 0x03) as usize + 1;
+    // Length covers the packet number, a shorter one is a malformed or hostile packet
+    if length < pn_len {
+        return None;
+    }
+    let mut packet_number: u64 = 0;
+    for (byte, mask) in packet.get(pn_offset..pn_offset + pn_len)?.iter().zip(&mask[1..]) {
+        let byte = byte ^ mask;
+        header.push(byte);
+        packet_number = (packet_number << 8) | byte as u64;
+    }
+
+    let mut nonce = keys.iv;
+    for (i, byte) in packet_number.to_be_bytes().iter().enumerate() {
+        nonce[4 + i] ^= byte;
+    }
+
+    let cipher = Aes128Gcm::new(GenericArray::from_slice(&keys.key));
+    let plaintext = cipher
+        .decrypt(
+            GenericArray::from_slice(&nonce),
+            Payload {
+                msg: packet.get(pn_offset + pn_len..packet_end)?,
+                aad: &header,
+            },
+        )
+        .ok()?;
+
+    collect_crypto_frames(&plaintext)
+}
+
+// Walks the frames of a decrypted Initial and stitches the CRYPTO frames together.
+// Browsers deliberately scatter the ClientHello across out of order CRYPTO frames.
+fn collect_crypto_frames(frames: &[u8]) -> Option<Vec<u8>> {
+    let mut chunks: Vec<(usize, &[u8])> = Vec::new();
+    let mut pos = 0;
+
+    while pos < frames.len() {
+        let frame_type = read_varint(frames, &mut pos)?;
+        match frame_type {
+            // PADDING and PING
+            0x00 | 0x01 => {}
+            // ACK, optionally with ECN counts
+            0x02 | 0x03 => {
+                let _largest = read_varint(frames, &mut pos)?;
+                let _delay = read_varint(frames, &mut pos)?;
+                let range_count = read_varint(frames, &mut pos)?;
+                let _first_range = read_varint(frames, &mut pos)?;
+                for _ in 0..range_count {
+                    read_varint(frames, &mut pos)?;
+                    read_varint(frames, &mut pos)?;
+                }
+                if frame_type == 0x03 {
+                    for _ in 0..3 {
+                        read_varint(frames, &mut pos)?;
+                    }
+                }
+            }
+            // CRYPTO
+            0x06 => {
+                let offset = read_varint(frames, &mut pos)? as usize;
+                let len = read_varint(frames, &mut pos)? as usize;
+                let end = pos.checked_add(len)?;
+                chunks.push((offset, frames.get(pos..end)?));
+                pos = end;
+            }
+            // Anything else is not allowed in an Initial, stop rather than misparse
+            _ => break,
+        }
+    }
+
+    chunks.sort_by_key(|(offset, _)| *offset);
+    let mut stream = Vec::new();
+    for (offset, data) in chunks {
+        if offset > stream.len() {
+            // Gap, the rest of the ClientHello is in another datagram
+            break;
+        }
+        let skip = stream.len() - offset;
+        if skip < data.len() {
+            stream.extend_from_slice(&data[skip..]);
+        }
+    }
+
+    if stream.is_empty() {
+        None
+    } else {
+        Some(stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 9001 Appendix A.2: the client Initial, 1200 bytes with header protection applied
+    const CLIENT_INITIAL: &str = "\
+    c000000001088394c8f03e5157080000449e7b9aec34d1b1c98dd7689fb8ec11\
+    d242b123dc9bd8bab936b47d92ec356c0bab7df5976d27cd449f63300099f399\
+    1c260ec4c60d17b31f8429157bb35a1282a643a8d2262cad67500cadb8e7378c\
+    8eb7539ec4d4905fed1bee1fc8aafba17c750e2c7ace01e6005f80fcb7df6212\
+    30c83711b39343fa028cea7f7fb5ff89eac2308249a02252bebd947d62ae86b9\
+    a071d9321441bcbdf9439241437912154682e9cf012f9021a6f0be17ddd0c208\
+    4dce25ff9b06cde535d0f920a2db1bf362c23e596d11a4f5a6cf3948838a3aec\
+    4e15daf8500a6ef69ec4e3feb6b1d98e610ac8b7ec3faf6ad760b7bad1db4ba3\
+    485e8a94dc250ae3fdb41ed15fb6a8e5eba0fc3dd60bc8e30c5c4287e53805db\
+    059ae0648db2f64264ed5e39be2e20d82df566da8dd5998ccabdae053060ae6c\
+    7b4378e846d29f37ed7b4ea9ec5d82e7961b7f25a9323851f681d582363aa5f8\
+    9937f5a67258bf63ad6f1a0b1d96dbd4faddfcefc5266ba6611722395c906556\
+    be52afe3f565636ad1b17d508b73d8743eeb524be22b3dcbc2c7468d54119c74\
+    68449a13d8e3b95811a198f3491de3e7fe942b330407abf82a4ed7c1b311663a\
+    c69890f4157015853d91e923037c227a33cdd5ec281ca3f79c44546b9d90ca00\
+    f064c99e3dd97911d39fe9c5d0b23a229a234cb36186c4819e8b9c5927726632\
+    291d6a418211cc2962e20fe47feb3edf330f2c603a9d48c0fcb5699dbfe58964\
+    25c5bac4aee82e57a85aaf4e2513e4f05796b07ba2ee47d80506f8d2c25e50fd\
+    14de71e6c418559302f939b0e1abd576f279c4b2e0feb85c1f28ff18f58891ff\
+    ef132eef2fa09346aee33c28eb130ff28f5b766953334113211996d20011a198\
+    e3fc433f9f2541010ae17c1bf202580f6047472fb36857fe843b19f5984009dd\
+    c324044e847a4f4a0ab34f719595de37252d6235365e9b84392b061085349d73\
+    203a4a13e96f5432ec0fd4a1ee65accdd5e3904df54c1da510b0ff20dcc0c77f\
+    cb2c0e0eb605cb0504db87632cf3d8b4dae6e705769d1de354270123cb11450e\
+    fc60ac47683d7b8d0f811365565fd98c4c8eb936bcab8d069fc33bd801b03ade\
+    a2e1fbc5aa463d08ca19896d2bf59a071b851e6c239052172f296bfb5e724047\
+    90a2181014f3b94a4e97d117b438130368cc39dbb2d198065ae3986547926cd2\
+    162f40a29f0c3c8745c0f50fba3852e566d44575c29d39a03f0cda721984b6f4\
+    40591f355e12d439ff150aab7613499dbd49adabc8676eef023b15b65bfc5ca0\
+    6948109f23f350db82123535eb8a7433bdabcb909271a6ecbcb58b936a88cd4e\
+    8f2e6ff5800175f113253d8fa9ca8885c2f552e657dc603f252e1a8e308f76f0\
+    be79e2fb8f5d5fbbe2e30ecadd220723c8c0aea8078cdfcb3868263ff8f09400\
+    54da48781893a7e49ad5aff4af300cd804a6b6279ab3ff3afb64491c85194aab\
+    760d58a606654f9f4400e8b38591356fbf6425aca26dc85244259ff2b19c41b9\
+    f96f3ca9ec1dde434da7d2d392b905ddf3d1f9af93d1af5950bd493f5aa731b4\
+    056df31bd267b6b90a079831aaf579be0a39013137aac6d404f518cfd4684064\
+    7e78bfe706ca4cf5e9c5453e9f7cfd2b8b4c8d169a44e55c88d4a9a7f9474241\
+    6f1b442b7c9d44808b003871ee6c05cb";
+
+    fn client_initial() -> Vec<u8> {
+        (0..CLIENT_INITIAL.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&CLIENT_INITIAL[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn derives_rfc_client_keys() {
+        let keys = client_initial_keys(VERSION_1, &[0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08]).unwrap();
+        assert_eq!(keys.key, [0x1f, 0x36, 0x96, 0x13, 0xdd, 0x76, 0xd5, 0x46, 0x77, 0x30, 0xef, 0xcb, 0xe3, 0xb1, 0xa2, 0x2d]);
+        assert_eq!(keys.iv, [0xfa, 0x04, 0x4b, 0x2f, 0x42, 0xa3, 0xfd, 0x3b, 0x46, 0xfb, 0x25, 0x5c]);
+        assert_eq!(keys.hp, [0x9f, 0x50, 0x44, 0x9e, 0x04, 0xa0, 0xe8, 0x10, 0x28, 0x3a, 0x1e, 0x99, 0x33, 0xad, 0xed, 0xd2]);
+    }
+
+    #[test]
+    fn decrypts_rfc_client_initial() {
+        let packet = client_initial();
+        assert_eq!(packet.len(), 1200);
+        let header = parse(&packet).unwrap();
+        assert_eq!(header.packet_type, QuicPacketType::Initial);
+        assert_eq!(header.version, Some(VERSION_1));
+        assert_eq!(header.dcid, [0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08]);
+        assert!(header.decrypted);
+        let hello = header.client_hello.unwrap();
+        assert_eq!(hello.sni.as_deref(), Some("example.com"));
+        assert_eq!(hello.alpn, ["alpn"]);
+        assert!(hello.complete);
+    }
+
+    #[test]
+    fn truncated_initial_is_not_decrypted() {
+        let packet = client_initial();
+        for len in [7, 18, 21, 40, 600, 1199] {
+            let header = parse(&packet[..len]);
+            assert!(header.map(|header| !header.decrypted).unwrap_or(true), "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn short_length_is_rejected() {
+        let mut packet = client_initial();
+        // The Length varint sits at 16..18 (0x449e); shrink it below every packet number length
+        for length in [0u8, 1, 2, 3] {
+            packet[16] = 0x40;
+            packet[17] = length;
+            let header = parse(&packet).unwrap();
+            assert!(!header.decrypted);
+        }
+        // And past the end of the datagram
+        packet[16] = 0x7f;
+        packet[17] = 0xff;
+        assert!(!parse(&packet).unwrap().decrypted);
+    }
+}
@@ -0,0 +1,127 @@
+// Just enough of TLS to pull the interesting bits out of a ClientHello.
+// The parser is lenient on purpose: a ClientHello carried in QUIC can span several
+// datagrams, so whatever extensions fit in the bytes we have are still reported.
+
+/// The parts of a ClientHello the dissector reports
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientHello {
+    pub sni: Option<String>,
+    pub alpn: Vec<String>,
+    // False when the handshake message was cut off before the end of the extensions
+    pub complete: bool,
+}
+
+const HANDSHAKE_CLIENT_HELLO: u8 = 0x01;
+const EXTENSION_SERVER_NAME: u16 = 0x0000;
+const EXTENSION_ALPN: u16 = 0x0010;
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, pos: 0 }
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        let value = *self.data.get(self.pos)?;
+        self.pos += 1;
+        Some(value)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.bytes(2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u24(&mut self) -> Option<usize> {
+        let bytes = self.bytes(3)?;
+        Some(((bytes[0] as usize) << 16) | ((bytes[1] as usize) << 8) | bytes[2] as usize)
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(len)?;
+        let bytes = self.data.get(self.pos..end)?;
+        self.pos = end;
+        Some(bytes)
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+}
+
+/// Parses a handshake message (type + 24 bit length + body), which is what
+/// QUIC CRYPTO frames and TLS handshake records carry
+pub fn parse_client_hello(handshake: &[u8]) -> Option<ClientHello> {
+    let mut reader = Reader::new(handshake);
+    if reader.u8()? != HANDSHAKE_CLIENT_HELLO {
+        return None;
+    }
+    let length = reader.u24()?;
+
+    let _legacy_version = reader.u16()?;
+    let _random = reader.bytes(32)?;
+    let session_id_len = reader.u8()? as usize;
+    reader.bytes(session_id_len)?;
+    let cipher_suites_len = reader.u16()? as usize;
+    reader.bytes(cipher_suites_len)?;
+    let compression_len = reader.u8()? as usize;
+    reader.bytes(compression_len)?;
+
+    let mut hello = ClientHello::default();
+    let extensions_len = match reader.u16() {
+        Some(len) => len as usize,
+        None => return Some(hello),
+    };
+    let extensions_end = reader.pos + extensions_len;
+
+    while reader.pos < extensions_end {
+        let (Some(extension_type), Some(extension_len)) = (reader.u16(), reader.u16()) else {
+            break;
+        };
+        let Some(body) = reader.bytes(extension_len as usize) else {
+            break;
+        };
+        match extension_type {
+            EXTENSION_SERVER_NAME => hello.sni = parse_server_name(body),
+            EXTENSION_ALPN => hello.alpn = parse_alpn(body),
+            _ => {}
+        }
+    }
+
+    hello.complete = handshake.len() >= length + 4 && reader.pos >= extensions_end;
+    Some(hello)
+}
+
+fn parse_server_name(body: &[u8]) -> Option<String> {
+    let mut reader = Reader::new(body);
+    let _list_len = reader.u16()?;
+    while reader.remaining() > 0 {
+        let name_type = reader.u8()?;
+        let name_len = reader.u16()? as usize;
+        let name = reader.bytes(name_len)?;
+        // 0 is host_name, the only type ever defined
+        if name_type == 0 {
+            return Some(String::from_utf8_lossy(name).into_owned());
+        }
+    }
+    None
+}
+
+fn parse_alpn(body: &[u8]) -> Vec<String> {
+    let mut reader = Reader::new(body);
+    let mut protocols = Vec::new();
+    if reader.u16().is_none() {
+        return protocols;
+    }
+    while let Some(len) = reader.u8() {
+        match reader.bytes(len as usize) {
+            Some(protocol) => protocols.push(String::from_utf8_lossy(protocol).into_owned()),
+            None => break,
+        }
+    }
+    protocols
+}
@@ -0,0 +1,35 @@
+//! Packet dissection engine behind Metal Sniffer.
+//!
+//! Nothing in here depends on Tauri or on a live capture: hand [`link::decode`] the bytes
+//! of a frame and its link type and get back a [`custom_packet::MyPacket`], then walk its
+//! [`field_tree::Field`]s, match them with a [`display_filter::DisplayFilter`], group packets
+//! with [`flow::FlowTable`] or put TCP streams back together with [`reassembly::TcpReassembler`].
+//!
+//! ```
+//! use metal_sniffer_core::custom_packet::CustomPacket;
+//! use metal_sniffer_core::decode_as::DecodeAsTable;
+//! use metal_sniffer_core::link::{self, LinkType};
+//!
+//! # // An Ethernet frame carrying IPv4/UDP from 10.0.0.1:5000 to 10.0.0.2:53
+//! # let mut frame = vec![0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 6, 0x08, 0x00];
+//! # frame.extend_from_slice(&[0x45, 0, 0, 32, 0, 0, 0x40, 0, 64, 17, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+//! # frame.extend_from_slice(&[0x13, 0x88, 0, 53, 0, 12, 0, 0, 0xde, 0xad, 0xbe, 0xef]);
+//! let packet = link::decode(LinkType::Ethernet, &frame, &DecodeAsTable::default())?;
+//! for root in packet.get_field_tree() {
+//!     for field in root.iter() {
+//!         println!("{} = {}", field.name, field.display);
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+pub mod custom_packet;
+pub mod decode_as;
+pub mod dhcp_leases;
+pub mod display_filter;
+pub mod field_tree;
+pub mod flow;
+pub mod layers;
+pub mod link;
+pub mod payload_view;
+pub mod reassembly;
@@ -0,0 +1,113 @@
+use pnet::packet::ethernet::{EtherType, EtherTypes, EthernetPacket};
+use pnet::packet::Packet;
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+
+use crate::custom_packet::MyPacket;
+use crate::decode_as::DecodeAsTable;
+
+const ETHERNET_HEADER_LEN: usize = 14;
+const LINUX_SLL_HEADER_LEN: usize = 16;
+const NULL_HEADER_LEN: usize = 4;
+
+/// Link layer framing of captured packets, as reported by libpcap for the handle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkType {
+    /// DLT_EN10MB
+    Ethernet,
+    /// DLT_RAW, the frame starts at the IP header
+    Raw,
+    /// DLT_LINUX_SLL, what "any" captures on Linux produce
+    LinuxSll,
+    /// DLT_NULL, BSD loopback
+    Null,
+}
+
+impl LinkType {
+    /// Maps a libpcap DLT/LINKTYPE value, `None` for link types that are not dissected
+    pub fn from_dlt(dlt: i32) -> Option<LinkType> {
+        match dlt {
+            0 => Some(LinkType::Null),
+            1 => Some(LinkType::Ethernet),
+            // DLT_RAW is 12 or 14 on some BSDs, LINKTYPE_RAW is 101 in files
+            12 | 14 | 101 => Some(LinkType::Raw),
+            113 => Some(LinkType::LinuxSll),
+            _ => None,
+        }
+    }
+
+    /// The LINKTYPE value written to pcap/pcapng files
+    pub fn dlt(&self) -> i32 {
+        match self {
+            LinkType::Null => 0,
+            LinkType::Ethernet => 1,
+            LinkType::Raw => 101,
+            LinkType::LinuxSll => 113,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LinkType::Ethernet => "Ethernet",
+            LinkType::Raw => "Raw IP",
+            LinkType::LinuxSll => "Linux cooked",
+            LinkType::Null => "Loopback",
+        }
+    }
+
+    /// Length of the link header in front of the network layer
+    pub fn header_len(&self) -> usize {
+        match self {
+            LinkType::Ethernet => ETHERNET_HEADER_LEN,
+            LinkType::Raw => 0,
+            LinkType::LinuxSll => LINUX_SLL_HEADER_LEN,
+            LinkType::Null => NULL_HEADER_LEN,
+        }
+    }
+}
+
+/// A link layer address as colon separated lowercase hex, e.g. `00:11:22:aa:bb:cc`
+pub fn format_mac(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(":")
+}
+
+/// Dissects one captured frame of the given link type
+pub fn decode(link: LinkType, frame: &[u8], decode_as: &DecodeAsTable) -> Result<MyPacket, Error> {
+    if frame.len() < link.header_len() {
+        return Err(Error::new(ErrorKind::InvalidData, format!("Frame too short for {}", link.name())));
+    }
+    match link {
+        LinkType::Ethernet => {
+            let ethernet_packet = EthernetPacket::new(frame)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Frame too short for Ethernet"))?;
+            MyPacket::from_network(link, ethernet_packet.get_ethertype(), ethernet_packet.payload(), decode_as)
+        }
+        LinkType::Raw => {
+            // No header, the IP version nibble says which it is
+            let ethertype = match frame.first().map(|byte| byte >> 4) {
+                Some(4) => EtherTypes::Ipv4,
+                Some(6) => EtherTypes::Ipv6,
+                _ => return Err(Error::new(ErrorKind::Unsupported, "Unsupported LayerOne protocol")),
+            };
+            MyPacket::from_network(link, ethertype, frame, decode_as)
+        }
+        LinkType::LinuxSll => {
+            // Packet type, ARPHRD type, address length, 8 bytes of address, then the protocol
+            let ethertype = EtherType(u16::from_be_bytes([frame[14], frame[15]]));
+            MyPacket::from_network(link, ethertype, &frame[LINUX_SLL_HEADER_LEN..], decode_as)
+        }
+        LinkType::Null => {
+            // The address family is in the byte order of the machine that captured it
+            let family = u32::from_le_bytes([frame[0], frame[1], frame[2], frame[3]]);
+            let family = if family > 0xffff { family.swap_bytes() } else { family };
+            let ethertype = match family {
+                2 => EtherTypes::Ipv4,
+                // AF_INET6 differs between BSDs, macOS and Linux
+                10 | 24 | 28 | 30 => EtherTypes::Ipv6,
+                _ => return Err(Error::new(ErrorKind::Unsupported, "Unsupported LayerOne protocol")),
+            };
+            MyPacket::from_network(link, ethertype, &frame[NULL_HEADER_LEN..], decode_as)
+        }
+    }
+}
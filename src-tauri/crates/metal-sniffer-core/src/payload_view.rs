@@ -0,0 +1,292 @@
+use serde::Serialize;
+
+use super::custom_packet::{FieldOffset, LayerRange};
+
+const BYTES_PER_LINE: usize = 16;
+// Same default as the `strings` tool
+const MIN_TEXT_LEN: usize = 4;
+
+/// One line of a hex dump, `offset` is the frame offset of its first byte
+#[derive(Debug, Clone, Serialize)]
+pub struct HexLine {
+    pub offset: usize,
+    pub hex: String,
+    pub ascii: String,
+}
+
+/// How a `TextRun` was decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TextEncoding {
+    #[serde(rename = "ascii")]
+    Ascii,
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "utf-16le")]
+    Utf16Le,
+    #[serde(rename = "utf-16be")]
+    Utf16Be,
+}
+
+/// A run of readable text, offset and length are in bytes of the frame
+#[derive(Debug, Clone, Serialize)]
+pub struct TextRun {
+    pub offset: usize,
+    pub length: usize,
+    pub encoding: TextEncoding,
+    pub text: String,
+}
+
+/// Everything the byte view needs to show a frame
+#[derive(Debug, Clone, Serialize)]
+pub struct PayloadView {
+    pub length: usize,
+    pub hexdump: Vec<HexLine>,
+    pub text_runs: Vec<TextRun>,
+    pub layers: Vec<LayerRange>,
+    pub fields: Vec<FieldOffset>,
+}
+
+/// Hex dump and text runs of `frame`, plus the layer and field ranges to highlight
+pub fn render(frame: &[u8], layers: Vec<LayerRange>, fields: Vec<FieldOffset>) -> PayloadView {
+    let mut text_runs = printable_strings(frame);
+    text_runs.extend(utf8_runs(frame));
+    text_runs.extend(utf16_runs(frame, TextEncoding::Utf16Le));
+    text_runs.extend(utf16_runs(frame, TextEncoding::Utf16Be));
+    text_runs.sort_by_key(|run| (run.offset, run.length));
+
+    PayloadView {
+        length: frame.len(),
+        hexdump: hexdump(frame),
+        text_runs,
+        layers,
+        fields,
+    }
+}
+
+fn is_printable(byte: u8) -> bool {
+    byte.is_ascii_graphic() || byte == b' ' || byte == b'\t'
+}
+
+/// 16 bytes per line, split into two groups of 8, with non printable bytes shown as `.`
+pub fn hexdump(frame: &[u8]) -> Vec<HexLine> {
+    frame
+        .chunks(BYTES_PER_LINE)
+        .enumerate()
+        .map(|(line, chunk)| {
+            let mut hex = String::with_capacity(BYTES_PER_LINE * 3 + 1);
+            for (i, byte) in chunk.iter().enumerate() {
+                if i > 0 {
+                    hex.push(' ');
+                }
+                // Extra gap between the two groups of eight, like hexdump -C
+                if i == BYTES_PER_LINE / 2 {
+                    hex.push(' ');
+                }
+                hex.push_str(&format!("{:02x}", byte));
+            }
+            let ascii = chunk
+                .iter()
+                .map(|&byte| if is_printable(byte) && byte != b'\t' { byte as char } else { '.' })
+                .collect();
+            HexLine { offset: line * BYTES_PER_LINE, hex, ascii }
+        })
+        .collect()
+}
+
+/// Plain ASCII runs, what `strings` would print
+pub fn printable_strings(frame: &[u8]) -> Vec<TextRun> {
+    let mut runs = Vec::new();
+    let mut start = None;
+
+    for (i, &byte) in frame.iter().chain(std::iter::once(&0)).enumerate() {
+        match (is_printable(byte) && i < frame.len(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(run_start)) => {
+                if i - run_start >= MIN_TEXT_LEN {
+                    runs.push(TextRun {
+                        offset: run_start,
+                        length: i - run_start,
+                        encoding: TextEncoding::Ascii,
+                        text: String::from_utf8_lossy(&frame[run_start..i]).into_owned(),
+                    });
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    runs
+}
+
+/// Runs of valid UTF-8 that contain at least one multi byte character;
+/// pure ASCII runs are already reported by printable_strings
+pub fn utf8_runs(frame: &[u8]) -> Vec<TextRun> {
+    let mut runs = Vec::new();
+    let mut pos = 0;
+
+    while pos < frame.len() {
+        let start = pos;
+        let mut chars = 0;
+        let mut multibyte = false;
+
+        while pos < frame.len() {
+            let Some((c, len)) = next_utf8_char(&frame[pos..]) else {
+                break;
+            };
+            if c.is_control() && c != '\t' {
+                break;
+            }
+            multibyte |= len > 1;
+            chars += 1;
+            pos += len;
+        }
+
+        if multibyte && chars >= MIN_TEXT_LEN {
+            runs.push(TextRun {
+                offset: start,
+                length: pos - start,
+                encoding: TextEncoding::Utf8,
+                text: String::from_utf8_lossy(&frame[start..pos]).into_owned(),
+            });
+        }
+        if pos == start {
+            pos += 1;
+        }
+    }
+    runs
+}
+
+fn next_utf8_char(bytes: &[u8]) -> Option<(char, usize)> {
+    let len = match bytes[0] {
+        0x00..=0x7f => 1,
+        0xc2..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf4 => 4,
+        _ => return None,
+    };
+    let text = std::str::from_utf8(bytes.get(..len)?).ok()?;
+    text.chars().next().map(|c| (c, len))
+}
+
+/// UTF-16 text in the ASCII range (Windows/SMB strings, etc.), the same
+/// heuristic as `strings -el` / `strings -eb`
+pub fn utf16_runs(frame: &[u8], encoding: TextEncoding) -> Vec<TextRun> {
+    let mut runs = Vec::new();
+
+    // Text can start on either byte alignment
+    for alignment in 0..2 {
+        let mut start: Option<usize> = None;
+        let mut text = String::new();
+        let mut pos = alignment;
+
+        loop {
+            let unit = frame.get(pos..pos + 2).map(|pair| match encoding {
+                TextEncoding::Utf16Be => [pair[1], pair[0]],
+                _ => [pair[0], pair[1]],
+            });
+            let printable = matches!(unit, Some([low, 0]) if is_printable(low));
+
+            if printable {
+                start.get_or_insert(pos);
+                text.push(unit.unwrap()[0] as char);
+            } else {
+                if let Some(run_start) = start.take() {
+                    if text.len() >= MIN_TEXT_LEN {
+                        runs.push(TextRun {
+                            offset: run_start,
+                            length: pos - run_start,
+                            encoding,
+                            text: std::mem::take(&mut text),
+                        });
+                    }
+                    text.clear();
+                }
+                if unit.is_none() {
+                    break;
+                }
+            }
+            pos += 2;
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(runs: &[TextRun]) -> Vec<(usize, usize, &str)> {
+        runs.iter().map(|run| (run.offset, run.length, run.text.as_str())).collect()
+    }
+
+    #[test]
+    fn hexdump_splits_lines_in_two_groups() {
+        let frame: Vec<u8> = (0..20).map(|i| if i == 16 { b'A' } else { i }).collect();
+        let lines = hexdump(&frame);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].offset, 0);
+        assert_eq!(lines[0].hex, "00 01 02 03 04 05 06 07  08 09 0a 0b 0c 0d 0e 0f");
+        assert_eq!(lines[0].ascii, "................");
+        // The last line is only as long as what is left
+        assert_eq!(lines[1].offset, 16);
+        assert_eq!(lines[1].hex, "41 11 12 13");
+        assert_eq!(lines[1].ascii, "A...");
+        assert!(hexdump(&[]).is_empty());
+    }
+
+    #[test]
+    fn ascii_runs_need_the_minimum_length() {
+        let frame = b"\x01abc\x02abcd\x03";
+        assert_eq!(texts(&printable_strings(frame)), [(5, 4, "abcd")]);
+        assert!(printable_strings(&frame[..4]).is_empty());
+    }
+
+    #[test]
+    fn runs_can_end_at_the_end_of_the_frame() {
+        assert_eq!(texts(&printable_strings(b"\x00GET /")), [(1, 5, "GET /")]);
+        assert_eq!(texts(&printable_strings(b"host")), [(0, 4, "host")]);
+        assert_eq!(texts(&utf8_runs("\0café".as_bytes())), [(1, 5, "café")]);
+        assert_eq!(texts(&utf16_runs(b"\0\0a\0b\0c\0d\0", TextEncoding::Utf16Le)), [(2, 8, "abcd")]);
+    }
+
+    #[test]
+    fn utf8_runs_only_report_multi_byte_text() {
+        let frame = [b"\x00plain ascii\x00".as_slice(), "naïve ☃ ok".as_bytes(), b"\xff"].concat();
+        let runs = utf8_runs(&frame);
+        assert_eq!(texts(&runs), [(13, 13, "naïve ☃ ok")]);
+        assert_eq!(runs[0].encoding, TextEncoding::Utf8);
+
+        // Four characters are needed, however many bytes they take
+        assert!(utf8_runs("☃☃☃".as_bytes()).is_empty());
+        assert_eq!(texts(&utf8_runs("☃☃☃☃".as_bytes())), [(0, 12, "☃☃☃☃")]);
+        // An invalid continuation byte ends the run
+        assert!(utf8_runs(b"ab\xc3\x28cd").is_empty());
+    }
+
+    #[test]
+    fn utf16_runs_are_found_at_either_alignment() {
+        let le_even = b"t\0e\0x\0t\0\xff\xff";
+        let le_odd = b"\xfft\0e\0x\0t\0";
+        assert_eq!(texts(&utf16_runs(le_even, TextEncoding::Utf16Le)), [(0, 8, "text")]);
+        assert_eq!(texts(&utf16_runs(le_odd, TextEncoding::Utf16Le)), [(1, 8, "text")]);
+
+        let be_even = b"\0t\0e\0x\0t\xff\xff";
+        let be_odd = b"\xff\0t\0e\0x\0t";
+        assert_eq!(texts(&utf16_runs(be_even, TextEncoding::Utf16Be)), [(0, 8, "text")]);
+        assert_eq!(texts(&utf16_runs(be_odd, TextEncoding::Utf16Be)), [(1, 8, "text")]);
+        // Little endian text read as big endian is not text
+        assert!(utf16_runs(le_even, TextEncoding::Utf16Be).iter().all(|run| run.text != "text"));
+
+        assert!(utf16_runs(b"a\0b\0c\0", TextEncoding::Utf16Le).is_empty());
+    }
+
+    #[test]
+    fn render_sorts_runs_by_offset() {
+        let frame = [b"GET /index".as_slice(), b"\0\0", b"w\0i\0n\0d\0"].concat();
+        let view = render(&frame, Vec::new(), Vec::new());
+        assert_eq!(view.length, frame.len());
+        let runs: Vec<(usize, TextEncoding)> = view.text_runs.iter().map(|run| (run.offset, run.encoding)).collect();
+        // Shifted by one byte, little endian ASCII text reads as big endian text too
+        assert_eq!(runs, [(0, TextEncoding::Ascii), (11, TextEncoding::Utf16Be), (12, TextEncoding::Utf16Le)]);
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::custom_packet::{MyPacket, TcpSegment};
+use crate::flow::{Direction, FlowKey};
+
+/// Default cap on the bytes one direction of a stream may hold, assembled or pending
+pub const DEFAULT_STREAM_LIMIT: usize = 16 * 1024 * 1024;
+
+/// Puts one direction of a TCP stream back in order. Retransmitted bytes are dropped,
+/// segments that arrive early wait until the gap before them is filled
+#[derive(Debug)]
+pub struct StreamReassembler {
+    // Sequence number of the next byte expected, unset until the first segment
+    next_seq: Option<u32>,
+    // Early segments keyed by their offset in the stream
+    pending: BTreeMap<u64, Vec<u8>>,
+    pending_bytes: usize,
+    data: Vec<u8>,
+    // Stream offset of data[0], bytes before it have been taken by the caller
+    consumed: u64,
+    limit: usize,
+    truncated: bool,
+    closed: bool,
+}
+
+impl Default for StreamReassembler {
+    fn default() -> StreamReassembler {
+        StreamReassembler::new(DEFAULT_STREAM_LIMIT)
+    }
+}
+
+impl StreamReassembler {
+    /// `limit` bounds the memory held for this direction, bytes past it are discarded
+    pub fn new(limit: usize) -> StreamReassembler {
+        StreamReassembler {
+            next_seq: None,
+            pending: BTreeMap::new(),
+            pending_bytes: 0,
+            data: Vec::new(),
+            consumed: 0,
+            limit,
+            truncated: false,
+            closed: false,
+        }
+    }
+
+    /// Adds a segment, returns how many bytes became contiguous because of it
+    pub fn push(&mut self, segment: &TcpSegment) -> usize {
+        if segment.rst {
+            self.closed = true;
+        }
+        // SYN takes up a sequence number but carries no stream data
+        let seq = if segment.syn { segment.seq.wrapping_add(1) } else { segment.seq };
+        let next_seq = *self.next_seq.get_or_insert(seq);
+        if segment.fin {
+            self.closed = true;
+        }
+        if segment.payload.is_empty() {
+            return 0;
+        }
+
+        // Sequence numbers wrap, so positions are taken relative to the next expected byte
+        let delta = seq.wrapping_sub(next_seq) as i32 as i64;
+        let mut payload = segment.payload;
+        if delta < 0 {
+            let overlap = (-delta) as usize;
+            if overlap >= payload.len() {
+                return 0;
+            }
+            payload = &payload[overlap..];
+        }
+        let offset = self.end_offset() + delta.max(0) as u64;
+
+        if offset != self.end_offset() {
+            self.hold(offset, payload);
+            return 0;
+        }
+        // Counted on the data itself, bytes discarded at the limit never became available
+        let before = self.data.len();
+        self.append(payload);
+        self.drain_pending();
+        self.data.len() - before
+    }
+
+    /// The contiguous bytes assembled so far and not yet taken
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Hands over the assembled bytes, later data continues from where they end
+    pub fn take(&mut self) -> Vec<u8> {
+        self.consumed += self.data.len() as u64;
+        std::mem::take(&mut self.data)
+    }
+
+    /// Bytes waiting for a gap in front of them to be filled
+    pub fn pending_bytes(&self) -> usize {
+        self.pending_bytes
+    }
+
+    /// True once data had to be thrown away because of the limit
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// True once a FIN or RST has been seen
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    // Stream offset one past the last contiguous byte
+    fn end_offset(&self) -> u64 {
+        self.consumed + self.data.len() as u64
+    }
+
+    fn room(&self) -> usize {
+        self.limit.saturating_sub(self.data.len() + self.pending_bytes)
+    }
+
+    fn append(&mut self, payload: &[u8]) {
+        let keep = payload.len().min(self.room());
+        if keep < payload.len() {
+            self.truncated = true;
+        }
+        self.data.extend_from_slice(&payload[..keep]);
+        if let Some(next_seq) = self.next_seq.as_mut() {
+            // Move past the whole payload so a truncated stream does not stall forever
+            *next_seq = next_seq.wrapping_add(payload.len() as u32);
+        }
+        self.consumed += (payload.len() - keep) as u64;
+    }
+
+    fn hold(&mut self, offset: u64, payload: &[u8]) {
+        if self.pending.contains_key(&offset) {
+            return;
+        }
+        if payload.len() > self.room() {
+            self.truncated = true;
+            return;
+        }
+        self.pending_bytes += payload.len();
+        self.pending.insert(offset, payload.to_vec());
+    }
+
+    fn drain_pending(&mut self) {
+        while let Some((&offset, _)) = self.pending.first_key_value() {
+            let end = self.end_offset();
+            if offset > end {
+                break;
+            }
+            let payload = self.pending.remove(&offset).unwrap_or_default();
+            self.pending_bytes -= payload.len();
+            let overlap = (end - offset) as usize;
+            if overlap < payload.len() {
+                self.append(&payload[overlap..]);
+            }
+        }
+    }
+}
+
+/// Reassembles every TCP stream it is fed, one `StreamReassembler` per flow direction
+#[derive(Debug)]
+pub struct TcpReassembler {
+    streams: HashMap<(FlowKey, Direction), StreamReassembler>,
+    limit: usize,
+}
+
+impl Default for TcpReassembler {
+    fn default() -> TcpReassembler {
+        TcpReassembler::new(DEFAULT_STREAM_LIMIT)
+    }
+}
+
+impl TcpReassembler {
+    pub fn new(limit: usize) -> TcpReassembler {
+        TcpReassembler { streams: HashMap::new(), limit }
+    }
+
+    /// Feeds a dissected packet, returns its stream when it was TCP
+    pub fn push(&mut self, packet: &MyPacket) -> Option<(FlowKey, Direction, &mut StreamReassembler)> {
+        let segment = packet.tcp_segment()?;
+        let (key, direction) = FlowKey::of(packet)?;
+        let limit = self.limit;
+        let stream = self
+            .streams
+            .entry((key, direction))
+            .or_insert_with(|| StreamReassembler::new(limit));
+        stream.push(&segment);
+        Some((key, direction, stream))
+    }
+
+    pub fn stream(&self, key: &FlowKey, direction: Direction) -> Option<&StreamReassembler> {
+        self.streams.get(&(*key, direction))
+    }
+
+    /// Forgets both directions of a flow
+    pub fn remove(&mut self, key: &FlowKey) {
+        self.streams.remove(&(*key, Direction::AToB));
+        self.streams.remove(&(*key, Direction::BToA));
+    }
+
+    pub fn clear(&mut self) {
+        self.streams.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_as::DecodeAsTable;
+    use crate::link::{self, LinkType};
+
+    fn segment(seq: u32, payload: &[u8]) -> TcpSegment<'_> {
+        TcpSegment { seq, flags: 0, syn: false, fin: false, rst: false, payload }
+    }
+
+    fn syn(seq: u32) -> TcpSegment<'static> {
+        TcpSegment { syn: true, ..segment(seq, &[]) }
+    }
+
+    #[test]
+    fn in_order_data_is_appended() {
+        let mut stream = StreamReassembler::default();
+        assert_eq!(stream.push(&syn(1000)), 0);
+        assert_eq!(stream.push(&segment(1001, b"GET ")), 4);
+        assert_eq!(stream.push(&segment(1005, b"/ HTTP/1.1")), 10);
+        assert_eq!(stream.data(), b"GET / HTTP/1.1");
+
+        assert_eq!(stream.take(), b"GET / HTTP/1.1");
+        assert_eq!(stream.push(&segment(1015, b"\r\n")), 2);
+        assert_eq!(stream.data(), b"\r\n");
+    }
+
+    #[test]
+    fn early_segments_wait_for_the_gap() {
+        let mut stream = StreamReassembler::default();
+        stream.push(&syn(0));
+        assert_eq!(stream.push(&segment(7, b"world")), 0);
+        assert_eq!(stream.push(&segment(12, b"!")), 0);
+        assert_eq!(stream.pending_bytes(), 6);
+        assert_eq!(stream.data(), b"");
+
+        assert_eq!(stream.push(&segment(1, b"hello ")), 12);
+        assert_eq!(stream.data(), b"hello world!");
+        assert_eq!(stream.pending_bytes(), 0);
+    }
+
+    #[test]
+    fn retransmitted_bytes_are_dropped() {
+        let mut stream = StreamReassembler::default();
+        stream.push(&syn(0));
+        stream.push(&segment(1, b"abcdef"));
+        // A full retransmission adds nothing, a partial one only its new tail
+        assert_eq!(stream.push(&segment(1, b"abcdef")), 0);
+        assert_eq!(stream.push(&segment(4, b"defghi")), 3);
+        assert_eq!(stream.data(), b"abcdefghi");
+
+        // Pending segments that overlap what arrived before them are trimmed too
+        stream.push(&segment(14, b"nop"));
+        stream.push(&segment(12, b"lmno"));
+        assert_eq!(stream.push(&segment(10, b"jk")), 7);
+        assert_eq!(stream.data(), b"abcdefghijklmnop");
+    }
+
+    #[test]
+    fn sequence_numbers_wrap() {
+        let mut stream = StreamReassembler::default();
+        stream.push(&syn(u32::MAX - 3));
+        stream.push(&segment(1, b"after"));
+        assert_eq!(stream.push(&segment(u32::MAX - 2, b"wrap")), 9);
+        // u32::MAX - 2 ..= 0 is "wrap", 1 follows straight on
+        assert_eq!(stream.data(), b"wrapafter");
+        assert_eq!(stream.push(&segment(u32::MAX, b"ap")), 0);
+    }
+
+    #[test]
+    fn fin_and_rst_close_the_stream() {
+        let mut stream = StreamReassembler::default();
+        stream.push(&syn(0));
+        assert!(!stream.is_closed());
+        // Data carried with the FIN is still kept
+        let fin = TcpSegment { fin: true, ..segment(1, b"bye") };
+        assert_eq!(stream.push(&fin), 3);
+        assert!(stream.is_closed());
+        assert_eq!(stream.data(), b"bye");
+
+        let mut stream = StreamReassembler::default();
+        stream.push(&segment(500, b"x"));
+        stream.push(&TcpSegment { rst: true, ..segment(501, &[]) });
+        assert!(stream.is_closed());
+        assert_eq!(stream.data(), b"x");
+    }
+
+    #[test]
+    fn data_past_the_limit_is_discarded() {
+        let mut stream = StreamReassembler::new(8);
+        stream.push(&syn(0));
+        assert_eq!(stream.push(&segment(1, b"0123456789")), 8);
+        assert_eq!(stream.data(), b"01234567");
+        assert!(stream.is_truncated());
+        // The stream does not stall on the discarded bytes
+        assert_eq!(stream.take(), b"01234567");
+        assert_eq!(stream.push(&segment(11, b"ab")), 2);
+        assert_eq!(stream.data(), b"ab");
+
+        // Early segments that do not fit are not held either
+        let mut stream = StreamReassembler::new(4);
+        stream.push(&syn(0));
+        assert_eq!(stream.push(&segment(3, b"cdefg")), 0);
+        assert_eq!(stream.pending_bytes(), 0);
+        assert!(stream.is_truncated());
+    }
+
+    fn tcp_frame(src: ([u8; 4], u16), dst: ([u8; 4], u16), seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let total_len = (20 + 20 + payload.len()) as u16;
+        let mut frame = vec![0x45, 0, 0, 0, 0, 0, 0x40, 0, 64, 6, 0, 0];
+        frame[2..4].copy_from_slice(&total_len.to_be_bytes());
+        frame.extend_from_slice(&src.0);
+        frame.extend_from_slice(&dst.0);
+        frame.extend_from_slice(&src.1.to_be_bytes());
+        frame.extend_from_slice(&dst.1.to_be_bytes());
+        frame.extend_from_slice(&seq.to_be_bytes());
+        frame.extend_from_slice(&[0, 0, 0, 0, 0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn each_direction_is_reassembled_separately() {
+        let client = ([10, 0, 0, 1], 50000);
+        let server = ([10, 0, 0, 2], 80);
+        let frames = [
+            tcp_frame(client, server, 100, 0x02, b""),
+            tcp_frame(server, client, 900, 0x12, b""),
+            tcp_frame(client, server, 105, 0x18, b"o"),
+            tcp_frame(client, server, 101, 0x18, b"hell"),
+            tcp_frame(server, client, 901, 0x18, b"hi"),
+        ];
+
+        let mut reassembler = TcpReassembler::default();
+        let mut key = None;
+        for frame in &frames {
+            let packet = link::decode(LinkType::Raw, frame, &DecodeAsTable::default()).unwrap();
+            let (flow, _, _) = reassembler.push(&packet).unwrap();
+            key = Some(flow);
+        }
+
+        // 10.0.0.1 sorts first, so the client is side a
+        let key = key.unwrap();
+        assert_eq!((key.a_port, key.b_port), (50000, 80));
+        let (client_direction, server_direction) = (Direction::AToB, Direction::BToA);
+        assert_eq!(reassembler.stream(&key, client_direction).unwrap().data(), b"hello");
+        assert_eq!(reassembler.stream(&key, server_direction).unwrap().data(), b"hi");
+
+        reassembler.remove(&key);
+        assert!(reassembler.stream(&key, client_direction).is_none());
+    }
+}
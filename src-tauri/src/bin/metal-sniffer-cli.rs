@@ -0,0 +1,339 @@
+// Headless capture: the same dissectors as the app, printed to stdout
+use chrono::{DateTime, Local};
+use clap::{Parser, ValueEnum};
+use metal_sniffer_app_lib::utils::capture::{self, list_devices, CaptureSource, NextFrame, PacketSource};
+use metal_sniffer_app_lib::utils::capture_error::CaptureError;
+use metal_sniffer_app_lib::utils::custom_packet::CustomPacket;
+use metal_sniffer_app_lib::utils::decode_as::DecodeAsTable;
+use metal_sniffer_app_lib::utils::display_filter::DisplayFilter;
+use metal_sniffer_app_lib::utils::export::{ExportFormat, ExportOptions, Exporter};
+use metal_sniffer_app_lib::utils::field_tree::Field;
+use metal_sniffer_app_lib::utils::link::{self, LinkType};
+use metal_sniffer_app_lib::utils::packet_store::StoredPacket;
+use metal_sniffer_app_lib::utils::pcap_stream::StreamCapture;
+use metal_sniffer_app_lib::utils::remote;
+use std::io::{self, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const READ_TIMEOUT_MS: i32 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Output {
+    // One line per packet
+    Summary,
+    // The full field tree of every packet
+    Verbose,
+    // A JSON array of packet records, the shape the app receives
+    Json,
+    // One JSON packet record per line
+    Ndjson,
+    // One row per packet with the fields picked by --columns
+    Csv,
+}
+
+impl Output {
+    fn export_format(&self) -> Option<ExportFormat> {
+        match self {
+            Output::Summary | Output::Verbose => None,
+            Output::Json => Some(ExportFormat::Json),
+            Output::Ndjson => Some(ExportFormat::Ndjson),
+            Output::Csv => Some(ExportFormat::Csv),
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+#[command(name = "metal-sniffer-cli", version, about = "Capture and dissect packets without the UI")]
+struct Args {
+    /// Interface to capture on, the default device if omitted
+    #[arg(short, long, conflicts_with_all = ["read", "remote"])]
+    interface: Option<String>,
+
+    /// Read packets from a pcap/pcapng file instead of capturing; a named pipe or - (stdin) is
+    /// decoded as it is written, e.g. `tcpdump -w - | metal-sniffer-cli -r -`
+    #[arg(short, long, conflicts_with = "remote")]
+    read: Option<PathBuf>,
+
+    /// Capture on a remote agent (host:port) started with --serve
+    #[arg(long, requires = "token")]
+    remote: Option<String>,
+
+    /// Run as a capture agent: stream the interface as pcapng to clients connecting to this address
+    #[arg(long, value_name = "ADDRESS", requires = "token", conflicts_with_all = ["read", "remote"])]
+    serve: Option<String>,
+
+    /// Shared secret between agent and client
+    #[arg(long, env = "METAL_SNIFFER_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// BPF capture filter, e.g. "tcp port 443"
+    #[arg(short, long, default_value = "")]
+    filter: String,
+
+    /// Display filter over decoded fields, e.g. "dns and ip.src == 10.0.0.0/8"
+    #[arg(short = 'Y', long)]
+    display_filter: Option<String>,
+
+    /// Stop after this many packets have been printed
+    #[arg(short, long)]
+    count: Option<u64>,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = Output::Summary)]
+    output: Output,
+
+    /// CSV columns: field names such as ip.src or dns.qry.name, frame.number/time/len/protocol,
+    /// `a|b` for whichever is present
+    #[arg(long, value_delimiter = ',')]
+    columns: Vec<String>,
+
+    /// Keep the base64 frame bytes in JSON output
+    #[arg(long)]
+    include_raw: bool,
+
+    /// "Decode as" rules file, as saved by the app
+    #[arg(long)]
+    decode_as: Option<PathBuf>,
+
+    /// List capture interfaces and exit
+    #[arg(short = 'D', long)]
+    list_interfaces: bool,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            if let Some(hint) = e.hint() {
+                eprintln!("hint: {}", hint);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), CaptureError> {
+    if args.list_interfaces {
+        for (index, device) in list_devices()?.iter().enumerate() {
+            match &device.desc {
+                Some(desc) => println!("{}. {} ({})", index + 1, device.name, desc),
+                None => println!("{}. {}", index + 1, device.name),
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(address) = &args.serve {
+        let listener = TcpListener::bind(address).map_err(|e| CaptureError::Open {
+            device: address.clone(),
+            message: e.to_string(),
+        })?;
+        eprintln!("Capture agent listening on {}", address);
+        let token = args.token.as_deref().unwrap_or_default();
+        return remote::serve(listener, token, args.interface.as_deref()).map_err(|e| CaptureError::Internal(e.to_string()));
+    }
+
+    let display_filter = match &args.display_filter {
+        Some(filter) => Some(DisplayFilter::compile(filter).map_err(|e| CaptureError::InvalidFilter {
+            filter: filter.clone(),
+            message: e.to_string(),
+        })?),
+        None => None,
+    };
+    let decode_as = match &args.decode_as {
+        Some(path) => DecodeAsTable::load(path).map_err(CaptureError::Internal)?,
+        None => DecodeAsTable::default(),
+    };
+
+    let defaults = ExportOptions::default();
+    let export = args.output.export_format().map(|format| ExportOptions {
+        format,
+        columns: if args.columns.is_empty() { defaults.columns } else { args.columns.clone() },
+        include_raw: args.include_raw,
+    });
+
+    let mut printer = Printer {
+        output: args.output,
+        export,
+        display_filter,
+        decode_as,
+        count: args.count,
+        printed: 0,
+        frame_number: 0,
+        errors: 0,
+    };
+    let source = match (&args.read, &args.remote) {
+        (Some(path), _) if capture::is_stream(path) => {
+            PacketSource::Stream(StreamCapture::open(&path.to_string_lossy(), &args.filter, READ_TIMEOUT_MS)?)
+        }
+        (Some(path), _) => PacketSource::File(capture::open_file(path, &args.filter)?),
+        (None, Some(address)) => {
+            let source = CaptureSource::Remote {
+                address: address.clone(),
+                token: args.token.clone().unwrap_or_default(),
+            };
+            PacketSource::open(&source, &args.filter, READ_TIMEOUT_MS)?
+        }
+        (None, None) => PacketSource::Device(capture::open_device(args.interface.as_deref(), &args.filter, READ_TIMEOUT_MS)?),
+    };
+    printer.run(source)?;
+
+    if printer.errors > 0 {
+        eprintln!("{} packets could not be dissected", printer.errors);
+    }
+    Ok(())
+}
+
+struct Printer {
+    output: Output,
+    export: Option<ExportOptions>,
+    display_filter: Option<DisplayFilter>,
+    decode_as: DecodeAsTable,
+    count: Option<u64>,
+    printed: u64,
+    frame_number: u64,
+    errors: u64,
+}
+
+impl Printer {
+    fn run(&mut self, mut source: PacketSource) -> Result<(), CaptureError> {
+        let link = LinkType::from_dlt(source.link_type()).ok_or_else(|| CaptureError::Open {
+            device: "capture".to_string(),
+            message: format!("link type {} is not supported", source.link_type()),
+        })?;
+        let mut out = io::stdout().lock();
+        let mut exporter = match self.export.clone() {
+            Some(options) => Some(Exporter::new(io::BufWriter::new(io::stdout()), options).map_err(|e| CaptureError::Export(e.to_string()))?),
+            None => None,
+        };
+
+        while self.count.map(|count| self.printed < count).unwrap_or(true) {
+            let frame = match source.next_frame()? {
+                NextFrame::Frame(frame) => frame,
+                NextFrame::Timeout => continue,
+                NextFrame::End => break,
+            };
+
+            // Frame numbers count every packet read, like Wireshark, not just the printed ones
+            self.frame_number += 1;
+            let stored = StoredPacket {
+                frame_number: self.frame_number,
+                ts_sec: frame.ts_sec,
+                ts_usec: frame.ts_usec,
+                len: frame.len,
+                data: frame.data,
+            };
+            let custom_packet = match link::decode(link, &stored.data, &self.decode_as) {
+                Ok(custom_packet) => custom_packet,
+                Err(_) => {
+                    self.errors += 1;
+                    continue;
+                }
+            };
+
+            let fields = custom_packet.get_field_tree();
+            if let Some(display_filter) = &self.display_filter {
+                if !display_filter.matches(&fields) {
+                    continue;
+                }
+            }
+
+            let written = match (self.output, exporter.as_mut()) {
+                (_, Some(exporter)) => exporter.write(&stored, stored.record(&custom_packet)),
+                (Output::Verbose, None) => write_verbose(&mut out, &stored, &fields),
+                _ => writeln!(out, "{}", summary(&stored, &fields)),
+            };
+            // Stop quietly when piped into `head` and the like
+            if written.is_err() {
+                break;
+            }
+            self.printed += 1;
+        }
+        if let Some(exporter) = exporter {
+            // Closes the JSON array, a reader that went away is not an error
+            let _ = exporter.finish();
+        }
+        Ok(())
+    }
+}
+
+fn format_time(ts_sec: i64, ts_usec: u32) -> String {
+    match ts_usec.checked_mul(1000).and_then(|nsec| DateTime::from_timestamp(ts_sec, nsec)) {
+        Some(utc) => DateTime::<Local>::from(utc).format("%H:%M:%S%.6f").to_string(),
+        None => "-".to_string(),
+    }
+}
+
+fn find_display(fields: &[Field], names: &[&str]) -> Option<String> {
+    let mut found = None;
+    for root in fields {
+        root.walk(&mut |field| {
+            if found.is_none() && names.contains(&field.name.as_str()) {
+                found = Some(field.display.clone());
+            }
+        });
+    }
+    found
+}
+
+// "   12 10:15:02.123456 10.0.0.2:51234 → 1.1.1.1:53 DNS 74"
+fn summary(packet: &StoredPacket, fields: &[Field]) -> String {
+    let src = find_display(fields, &["ip.src", "ipv6.src"]).unwrap_or_default();
+    let dst = find_display(fields, &["ip.dst", "ipv6.dst"]).unwrap_or_default();
+    let endpoint = |address: String, port: Option<String>| match port {
+        Some(port) if address.contains(':') => format!("[{}]:{}", address, port),
+        Some(port) => format!("{}:{}", address, port),
+        None => address,
+    };
+    let src_port = find_display(fields, &["tcp.srcport", "udp.srcport"]);
+    let dst_port = find_display(fields, &["tcp.dstport", "udp.dstport"]);
+    // The innermost decoded layer names the packet
+    let protocol = fields.last().map(|root| root.display_name.clone()).unwrap_or_default();
+
+    format!(
+        "{:>5} {} {} → {} {} {}",
+        packet.frame_number,
+        format_time(packet.ts_sec, packet.ts_usec),
+        endpoint(src, src_port),
+        endpoint(dst, dst_port),
+        protocol,
+        packet.len
+    )
+}
+
+fn write_field(out: &mut impl Write, field: &Field, depth: usize) -> io::Result<()> {
+    if field.children.is_empty() || !field.display.is_empty() {
+        writeln!(out, "{}{}: {}", "  ".repeat(depth), field.display_name, field.display)?;
+    } else {
+        writeln!(out, "{}{}:", "  ".repeat(depth), field.display_name)?;
+    }
+    // Lists repeat their values as children, which the line above already shows
+    if field.children.iter().all(|child| child.name == field.name) {
+        return Ok(());
+    }
+    for child in &field.children {
+        write_field(out, child, depth + 1)?;
+    }
+    Ok(())
+}
+
+fn write_verbose(out: &mut impl Write, packet: &StoredPacket, fields: &[Field]) -> io::Result<()> {
+    writeln!(
+        out,
+        "=== Frame {} ({} bytes, {}) ===",
+        packet.frame_number,
+        packet.len,
+        format_time(packet.ts_sec, packet.ts_usec)
+    )?;
+    for root in fields {
+        writeln!(out, "{}:", root.display_name)?;
+        for child in &root.children {
+            write_field(out, child, 1)?;
+        }
+    }
+    writeln!(out)
+}
@@ -1,198 +1,977 @@
-use base64::Engine;
-use pcap::{Capture, Device};
-use pcap::BpfProgram;
-use pnet::packet::ethernet::EthernetPacket;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
 use std::sync::Arc;
 use std::thread;
-use tauri::{AppHandle, Emitter, State};
-mod utils;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+pub mod utils;
+use utils::bpf;
+use utils::capture::{CaptureSource, NextFrame, PacketSource};
+use utils::capture_error::{CaptureError, CaptureErrorEvent};
+use utils::capture_session::{CaptureSession, CaptureStateEvent, CaptureStatus};
+use utils::capture_stats::StatsTracker;
 use utils::custom_packet::{CustomPacket, MyPacket};
-use chrono::{DateTime, Local, NaiveDateTime, Timelike};
-use std::collections::HashMap;
-use std::sync::Mutex;
-use base64::{engine::general_purpose::STANDARD};
+use utils::decode_as::{DecodeAsRule, DecodeAsTable, DecodeAsTarget, Transport};
+use utils::dhcp_leases::{DhcpLease, LeaseTable};
+use utils::dns_stats::{DnsStats, DnsTracker};
+use utils::endpoints::{Endpoint, EndpointKind, EndpointSort, EndpointTable};
+use utils::display_filter::{DisplayFilter, FilterError};
+use utils::event_batcher::{BatchAcks, BatchConfig, EventBatcher};
+use utils::export::{ExportFormat, ExportOptions, Exporter};
+use utils::flow::FlowSample;
+use utils::flow_export::{FlowExportConfig, FlowExporter};
+use utils::io_graph::{IoGraph, IoGraphData};
+use utils::field_tree::PacketRecord;
+use utils::layers::layer3::LayerThree;
+use utils::link::LinkType;
+use utils::packet_store::{packet_record, PacketCounts, PacketStore, StoredPacket, ViewFilter};
+use utils::payload_view::{self, PayloadView};
+use utils::pipeline::{DissectedFrame, Ordered, Pipeline, PipelineConfig, RawFrame};
+use utils::protocol_hierarchy::{ProtocolHierarchy, ProtocolNode};
+use utils::ring_buffer::RingBufferConfig;
+use chrono::{NaiveDateTime, Timelike};
+use std::sync::{Mutex, MutexGuard, RwLock};
 
+use std::fs::File;
+use std::io::BufWriter;
 use std::mem;
+use std::path::PathBuf;
 
 // Modify CaptureState to include filter
 #[derive(Default)]
 struct CaptureState {
-    running: Arc<AtomicBool>,
+    session: Mutex<Option<CaptureSession>>, // The current capture thread, if any
+    next_session_id: AtomicU64,
     filter: Arc<Mutex<String>>, // Add filter storage
+    decode_as: Arc<RwLock<DecodeAsTable>>, // User port/flow -> dissector overrides
+    dhcp_leases: Arc<Mutex<LeaseTable>>, // MAC -> IP bindings seen in DHCP traffic
+    packets: Arc<Mutex<PacketStore>>, // Captured frames spilled to disk, the UI pages through it
+    batch_acks: Arc<BatchAcks>, // Packets-captured batches sent and processed by the UI
+    flow_export: Mutex<FlowExportConfig>, // NetFlow/IPFIX export, applied when a capture starts
+    io_graph: Arc<Mutex<Option<IoGraph>>>, // I/O graph the UI is showing, kept up to date while capturing
 }
 
-fn format_timeval(tv: libc::timeval) -> String {
-    // Extract seconds and microseconds
-    let local_time: DateTime<Local> = DateTime::from_timestamp(tv.tv_sec.into(), (tv.tv_usec * 1000) as u32)
-    .expect("Invalid timestamp")
-    .into();
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+const READ_TIMEOUT_MS: i32 = 100;
+// How often the flow cache is checked for flows that have timed out
+const FLOW_EXPIRY_INTERVAL: Duration = Duration::from_secs(1);
 
-// Format the DateTime into the desired string format
-    local_time.format("%m/%d/%Y %H:%M:%S").to_string()
+// Entries per list in get_dns_stats unless the UI asks for more
+const DNS_STATS_TOP: usize = 10;
+
+// Packets waiting to be batched, beyond this the capture thread drops them from the live view
+const EVENT_QUEUE_LEN: usize = 4096;
+
+fn decode_as_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_config_dir()
+        .map(|dir| dir.join("decode_as.json"))
+        .map_err(|e| format!("Config directory error: {}", e))
+}
+
+fn ring_buffer_config_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_config_dir()
+        .map(|dir| dir.join("ring_buffer.json"))
+        .map_err(|e| format!("Config directory error: {}", e))
+}
+
+fn flow_export_config_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_config_dir()
+        .map(|dir| dir.join("flow_export.json"))
+        .map_err(|e| format!("Config directory error: {}", e))
+}
+
+// Where the ring buffer spills packets, it is emptied on every start
+fn capture_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_cache_dir()
+        .map(|dir| dir.join("capture"))
+        .map_err(|e| format!("Cache directory error: {}", e))
+}
+
+#[tauri::command]
+fn get_decode_as(state: State<CaptureState>) -> Result<Vec<DecodeAsRule>, String> {
+    let decode_as = state.decode_as.read().map_err(|_| "Failed to read decode as rules".to_string())?;
+    Ok(decode_as.rules().to_vec())
+}
+
+#[tauri::command]
+fn set_decode_as(app: AppHandle, rule: DecodeAsRule, state: State<CaptureState>) -> Result<(), String> {
+    let mut decode_as = state.decode_as.write().map_err(|_| "Failed to set decode as rule".to_string())?;
+    decode_as.set(rule);
+    decode_as.save(&decode_as_path(&app)?)?;
+    // Stored packets are dissected on read, the UI only needs to fetch its rows again
+    app.emit("decode-as-changed", ()).map_err(|e| format!("Failed to emit event: {}", e))
+}
+
+#[tauri::command]
+fn remove_decode_as(
+    app: AppHandle,
+    transport: Transport,
+    target: DecodeAsTarget,
+    state: State<CaptureState>,
+) -> Result<bool, String> {
+    let mut decode_as = state.decode_as.write().map_err(|_| "Failed to remove decode as rule".to_string())?;
+    let removed = decode_as.remove(transport, target);
+    decode_as.save(&decode_as_path(&app)?)?;
+    app.emit("decode-as-changed", ()).map_err(|e| format!("Failed to emit event: {}", e))?;
+    Ok(removed)
+}
+
+#[tauri::command]
+fn get_dhcp_leases(state: State<CaptureState>) -> Result<Vec<DhcpLease>, String> {
+    let leases = state.dhcp_leases.lock().map_err(|_| "Failed to read DHCP leases".to_string())?;
+    Ok(leases.leases())
+}
+
+#[tauri::command]
+fn clear_dhcp_leases(state: State<CaptureState>) -> Result<(), String> {
+    let mut leases = state.dhcp_leases.lock().map_err(|_| "Failed to clear DHCP leases".to_string())?;
+    leases.clear();
+    Ok(())
+}
+
+fn lock_packets<'a>(state: &'a State<CaptureState>) -> Result<MutexGuard<'a, PacketStore>, CaptureError> {
+    state
+        .packets
+        .lock()
+        .map_err(|_| CaptureError::Internal("Packet store is unavailable".to_string()))
+}
+
+#[tauri::command]
+fn get_packet_count(state: State<CaptureState>) -> Result<PacketCounts, CaptureError> {
+    let packets = lock_packets(&state)?;
+    Ok(packets.counts())
+}
+
+// A page of the packets passing the current filter, oldest first
+#[tauri::command]
+fn get_packets(offset: usize, limit: usize, state: State<CaptureState>) -> Result<Vec<PacketRecord>, CaptureError> {
+    let decode_as = state.decode_as.read().map(|decode_as| decode_as.clone()).unwrap_or_default();
+    let packets = lock_packets(&state)?;
+    Ok(packets
+        .page(offset, limit)
+        .map_err(|e| CaptureError::Storage(e.to_string()))?
+        .into_iter()
+        .filter_map(|packet| packet.to_record(&decode_as).ok())
+        .collect())
+}
+
+#[tauri::command]
+fn get_packet(frame_number: u64, state: State<CaptureState>) -> Result<PacketRecord, CaptureError> {
+    let decode_as = state.decode_as.read().map(|decode_as| decode_as.clone()).unwrap_or_default();
+    let packets = lock_packets(&state)?;
+    let packet = packets
+        .get(frame_number)
+        .map_err(|e| CaptureError::Storage(e.to_string()))?
+        .ok_or(CaptureError::NoPacket(frame_number))?;
+    packet.to_record(&decode_as).map_err(|e| CaptureError::Decode(e.to_string()))
+}
+
+#[tauri::command]
+fn get_ring_buffer_config(state: State<CaptureState>) -> Result<RingBufferConfig, String> {
+    let packets = state.packets.lock().map_err(|_| "Failed to read ring buffer config".to_string())?;
+    Ok(packets.config().clone())
+}
+
+// New caps apply straight away, evicting the oldest segments if needed
+#[tauri::command]
+fn set_ring_buffer_config(app: AppHandle, config: RingBufferConfig, state: State<CaptureState>) -> Result<(), String> {
+    if config.segment_bytes == 0 {
+        return Err("Segment size must be greater than zero".to_string());
+    }
+    let mut packets = state.packets.lock().map_err(|_| "Failed to set ring buffer config".to_string())?;
+    packets.set_config(config.clone());
+    config.save(&ring_buffer_config_path(&app)?)
+}
+
+#[tauri::command]
+fn get_flow_export_config(state: State<CaptureState>) -> Result<FlowExportConfig, String> {
+    let config = state.flow_export.lock().map_err(|_| "Failed to read flow export config".to_string())?;
+    Ok(config.clone())
+}
+
+// Takes effect on the next start_packet_capture
+#[tauri::command]
+fn set_flow_export_config(app: AppHandle, config: FlowExportConfig, state: State<CaptureState>) -> Result<(), String> {
+    if config.enabled && config.collector.is_none() && config.file.is_none() {
+        return Err("Flow export needs a collector or a file".to_string());
+    }
+    if config.idle_timeout_secs == 0 || config.active_timeout_secs == 0 {
+        return Err("Flow timeouts must be greater than zero".to_string());
+    }
+    let mut current = state.flow_export.lock().map_err(|_| "Failed to set flow export config".to_string())?;
+    *current = config.clone();
+    config.save(&flow_export_config_path(&app)?)
+}
+
+#[tauri::command]
+fn clear_packets(state: State<CaptureState>) -> Result<(), CaptureError> {
+    let mut packets = lock_packets(&state)?;
+    packets.clear().map_err(|e| CaptureError::Storage(e.to_string()))?;
+    if let Some(io_graph) = state.io_graph.lock().ok().as_mut().and_then(|io_graph| io_graph.as_mut()) {
+        io_graph.clear();
+    }
+    Ok(())
+}
+
+// Calls `visit` with every packet passing the current filter and its dissection. The store is
+// locked one frame at a time so a running capture is not held up by a long scan
+fn scan_visible_packets(
+    packets: &Mutex<PacketStore>,
+    decode_as: &DecodeAsTable,
+    mut visit: impl FnMut(&StoredPacket, Option<&MyPacket>) -> Result<(), CaptureError>,
+) -> Result<(), CaptureError> {
+    let frames = packets
+        .lock()
+        .map_err(|_| CaptureError::Internal("Packet store is unavailable".to_string()))?
+        .visible_frames();
+    for frame_number in frames {
+        let packet = packets
+            .lock()
+            .map_err(|_| CaptureError::Internal("Packet store is unavailable".to_string()))?
+            .get(frame_number)
+            .map_err(|e| CaptureError::Storage(e.to_string()))?;
+        // Evicted since the scan started
+        let Some(packet) = packet else {
+            continue;
+        };
+        let custom_packet = packet.dissect(decode_as).ok();
+        visit(&packet, custom_packet.as_ref())?;
+    }
+    Ok(())
+}
+
+// Frame numbers of the retained packets `keep` accepts, oldest first. The store is locked per
+// frame so a running capture is not held up; frames stored while the scan ran are checked at
+// the end, and the guard is handed back so the caller installs the view before more arrive
+fn filter_frames(
+    packets: &Mutex<PacketStore>,
+    mut keep: impl FnMut(&StoredPacket) -> bool,
+) -> Result<(MutexGuard<'_, PacketStore>, Vec<u64>), CaptureError> {
+    let lock = move || packets.lock().map_err(|_| CaptureError::Internal("Packet store is unavailable".to_string()));
+    let mut frames = Vec::new();
+    let mut check = |store: &PacketStore, frame_number: u64| match store.get(frame_number) {
+        Ok(Some(packet)) if keep(&packet) => frames.push(frame_number),
+        // Evicted since the scan started, or not a match
+        Ok(_) => {}
+        Err(e) => eprintln!("Failed to read stored packet: {:?}", e),
+    };
+
+    let (first, last) = {
+        let store = lock()?;
+        (store.first_frame(), store.last_frame())
+    };
+    let mut next = first.unwrap_or(1);
+    if let Some(last) = last {
+        while next <= last {
+            check(&*lock()?, next);
+            next += 1;
+        }
+    }
+
+    let store = lock()?;
+    if let Some(newest) = store.last_frame() {
+        for frame_number in next.max(store.first_frame().unwrap_or(next))..=newest {
+            check(&store, frame_number);
+        }
+    }
+    Ok((store, frames))
+}
+
+// Writes the packets passing the current filter to `path`, returns how many were written
+#[tauri::command]
+async fn export_packets(
+    path: PathBuf,
+    format: ExportFormat,
+    columns: Option<Vec<String>>,
+    include_raw: Option<bool>,
+    state: State<'_, CaptureState>,
+) -> Result<u64, CaptureError> {
+    let defaults = ExportOptions::default();
+    let options = ExportOptions {
+        format,
+        columns: columns.unwrap_or(defaults.columns),
+        include_raw: include_raw.unwrap_or(defaults.include_raw),
+    };
+    let decode_as = state.decode_as.read().map(|decode_as| decode_as.clone()).unwrap_or_default();
+    let packets = state.packets.clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let file = File::create(&path).map_err(|e| CaptureError::Export(e.to_string()))?;
+        let mut exporter = Exporter::new(BufWriter::new(file), options).map_err(|e| CaptureError::Export(e.to_string()))?;
+        scan_visible_packets(&packets, &decode_as, |packet, custom_packet| {
+            let Some(custom_packet) = custom_packet else {
+                return Ok(());
+            };
+            exporter
+                .write(packet, packet.record(custom_packet))
+                .map_err(|e| CaptureError::Export(e.to_string()))
+        })?;
+        exporter.finish().map_err(|e| CaptureError::Export(e.to_string()))
+    })
+    .await
+    .map_err(|e| CaptureError::Internal(format!("Export failed: {}", e)))?
+}
+
+// Share of packets and bytes per protocol path over the packets passing the current filter
+#[tauri::command]
+async fn get_protocol_hierarchy(state: State<'_, CaptureState>) -> Result<Vec<ProtocolNode>, CaptureError> {
+    let decode_as = state.decode_as.read().map(|decode_as| decode_as.clone()).unwrap_or_default();
+    let packets = state.packets.clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut hierarchy = ProtocolHierarchy::new();
+        scan_visible_packets(&packets, &decode_as, |packet, custom_packet| {
+            match custom_packet {
+                Some(custom_packet) => hierarchy.add(&custom_packet.protocols(), packet.len),
+                // Stored frames are Ethernet, whatever is inside was not decoded
+                None => hierarchy.add(&[LinkType::Ethernet.name()], packet.len),
+            }
+            Ok(())
+        })?;
+        Ok(hierarchy.tree())
+    })
+    .await
+    .map_err(|e| CaptureError::Internal(format!("Protocol hierarchy failed: {}", e)))?
+}
+
+// Traffic per MAC, IPv4, IPv6 or TCP/UDP endpoint over the packets passing the current filter,
+// largest first by `sort_by` (bytes by default), the top `limit` when given
+#[tauri::command]
+async fn get_endpoints(
+    kind: EndpointKind,
+    sort_by: Option<EndpointSort>,
+    limit: Option<usize>,
+    state: State<'_, CaptureState>,
+) -> Result<Vec<Endpoint>, CaptureError> {
+    let decode_as = state.decode_as.read().map(|decode_as| decode_as.clone()).unwrap_or_default();
+    let packets = state.packets.clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut endpoints = EndpointTable::new();
+        scan_visible_packets(&packets, &decode_as, |packet, custom_packet| {
+            endpoints.observe(&packet.data, custom_packet, packet.ts_sec, packet.len);
+            Ok(())
+        })?;
+        Ok(endpoints.list(kind, sort_by.unwrap_or_default(), limit))
+    })
+    .await
+    .map_err(|e| CaptureError::Internal(format!("Endpoint statistics failed: {}", e)))?
+}
+
+// DNS queries paired with their responses over the packets passing the current filter:
+// response times, reply codes, unanswered queries and NXDOMAIN storms. Lists hold the
+// `top` (10 by default) busiest names, slowest resolvers and latest unanswered queries
+#[tauri::command]
+async fn get_dns_stats(top: Option<usize>, state: State<'_, CaptureState>) -> Result<DnsStats, CaptureError> {
+    let decode_as = state.decode_as.read().map(|decode_as| decode_as.clone()).unwrap_or_default();
+    let packets = state.packets.clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut dns = DnsTracker::new();
+        scan_visible_packets(&packets, &decode_as, |packet, custom_packet| {
+            if let Some(custom_packet) = custom_packet {
+                dns.observe(custom_packet, packet.ts_sec, packet.ts_usec);
+            }
+            Ok(())
+        })?;
+        Ok(dns.report(top.unwrap_or(DNS_STATS_TOP)))
+    })
+    .await
+    .map_err(|e| CaptureError::Internal(format!("DNS statistics failed: {}", e)))?
+}
+
+// Adds a stored packet to a graph being built, if it passes the graph's filter
+fn count_io_graph_packet(graph: &mut IoGraph, packet: &StoredPacket, decode_as: &DecodeAsTable) {
+    let Ok(custom_packet) = packet.dissect(decode_as) else {
+        return;
+    };
+    if graph.has_filter() && !graph.matches(&custom_packet.get_field_tree()) {
+        return;
+    }
+    let protocol = custom_packet.protocols().last().copied().unwrap_or_default();
+    graph.add(packet.frame_number, packet.ts_sec, packet.ts_usec, packet.len, protocol);
+}
+
+// Packets and bytes per `interval_ms` over every stored packet, optionally only those matching
+// a display filter and split by protocol. The graph stays live: while capturing, changed
+// buckets are sent as `io-graph` events every second until stop_io_graph
+#[tauri::command]
+async fn start_io_graph(
+    interval_ms: u64,
+    filter: Option<String>,
+    per_protocol: Option<bool>,
+    state: State<'_, CaptureState>,
+) -> Result<IoGraphData, CaptureError> {
+    let display_filter = match filter.as_deref().map(str::trim).filter(|filter| !filter.is_empty()) {
+        Some(filter) => Some(DisplayFilter::compile(filter).map_err(|e| CaptureError::InvalidFilter {
+            filter: filter.to_string(),
+            message: e.to_string(),
+        })?),
+        None => None,
+    };
+    let decode_as = state.decode_as.read().map(|decode_as| decode_as.clone()).unwrap_or_default();
+    let packets = state.packets.clone();
+    let io_graph = state.io_graph.clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut graph = IoGraph::new(interval_ms, display_filter, per_protocol.unwrap_or(false));
+        let store_error = || CaptureError::Internal("Packet store is unavailable".to_string());
+
+        // Everything stored so far, locking the store one frame at a time
+        let (first, last) = {
+            let store = packets.lock().map_err(|_| store_error())?;
+            (store.first_frame(), store.last_frame())
+        };
+        let mut next = first.unwrap_or(1);
+        if let Some(last) = last {
+            while next <= last {
+                let packet = packets
+                    .lock()
+                    .map_err(|_| store_error())?
+                    .get(next)
+                    .map_err(|e| CaptureError::Storage(e.to_string()))?;
+                // None when evicted since the scan started
+                if let Some(packet) = packet {
+                    count_io_graph_packet(&mut graph, &packet, &decode_as);
+                }
+                next += 1;
+            }
+        }
+
+        // Frames stored during the scan are counted with the store locked, then the graph goes
+        // live before the lock is released. The storer adds frames to the live graph after
+        // storing them, and the graph ignores the ones it already counted, so every frame is
+        // counted exactly once. Locks are taken store first, as the storer does
+        let store = packets.lock().map_err(|_| store_error())?;
+        if let Some(newest) = store.last_frame() {
+            for frame_number in next.max(store.first_frame().unwrap_or(next))..=newest {
+                if let Some(packet) = store.get(frame_number).map_err(|e| CaptureError::Storage(e.to_string()))? {
+                    count_io_graph_packet(&mut graph, &packet, &decode_as);
+                }
+            }
+        }
+        let data = graph.data();
+        *io_graph
+            .lock()
+            .map_err(|_| CaptureError::Internal("I/O graph is unavailable".to_string()))? = Some(graph);
+        drop(store);
+        Ok(data)
+    })
+    .await
+    .map_err(|e| CaptureError::Internal(format!("Building the I/O graph failed: {}", e)))?
+}
+
+#[tauri::command]
+fn stop_io_graph(state: State<CaptureState>) -> Result<(), CaptureError> {
+    *state
+        .io_graph
+        .lock()
+        .map_err(|_| CaptureError::Internal("I/O graph is unavailable".to_string()))? = None;
+    Ok(())
+}
+
+// Hex/ASCII dump, readable text runs, per layer byte ranges and field offsets of a stored frame
+#[tauri::command]
+fn render_payload(frame_number: u64, state: State<CaptureState>) -> Result<PayloadView, CaptureError> {
+    let decode_as = state.decode_as.read().map(|decode_as| decode_as.clone()).unwrap_or_default();
+    let packets = lock_packets(&state)?;
+    let packet = packets
+        .get(frame_number)
+        .map_err(|e| CaptureError::Storage(e.to_string()))?
+        .ok_or(CaptureError::NoPacket(frame_number))?;
+
+    let (layers, fields) = packet
+        .dissect(&decode_as)
+        .map(|custom_packet| (custom_packet.get_layer_ranges(), custom_packet.get_field_offsets()))
+        .unwrap_or_default();
+
+    Ok(payload_view::render(&packet.data, layers, fields))
+}
+
+// Lets the UI validate a display filter as it is typed
+#[tauri::command]
+fn check_display_filter(filter: String) -> Result<(), FilterError> {
+    DisplayFilter::compile(&filter).map(|_| ())
+}
+
+// Display filters run over the decoded fields, unlike filter_packets which runs BPF over raw bytes
+#[tauri::command]
+async fn apply_display_filter(filter: String, state: State<'_, CaptureState>) -> Result<PacketCounts, FilterError> {
+    let display_filter = DisplayFilter::compile(&filter)?;
+    let decode_as = state.decode_as.read().map(|decode_as| decode_as.clone()).unwrap_or_default();
+    let packets = state.packets.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let (mut packets, frames) = filter_frames(&packets, |packet| {
+            packet
+                .dissect(&decode_as)
+                .map(|custom_packet| display_filter.matches(&custom_packet.get_field_tree()))
+                .unwrap_or(false)
+        })
+        .map_err(|_| FilterError::new("Failed to read packets", 0))?;
+        packets.set_view(ViewFilter::Display(display_filter), frames);
+        Ok(packets.counts())
+    })
+    .await
+    .map_err(|_| FilterError::new("Failed to read packets", 0))?
+}
+
+#[tauri::command]
+fn clear_packet_filter(state: State<CaptureState>) -> Result<PacketCounts, CaptureError> {
+    let mut packets = lock_packets(&state)?;
+    packets.clear_view();
+    Ok(packets.counts())
+}
+
+// The UI acknowledges each packets-captured batch, unacknowledged batches throttle emission
+#[tauri::command]
+fn ack_packets(batch_id: u64, state: State<CaptureState>) {
+    state.batch_acks.ack(batch_id);
+}
+
+// Groups captured packets into packets-captured events so the IPC channel is not hit once per packet
+fn emit_batches(app: AppHandle, records: Receiver<PacketRecord>, queue_skipped: Arc<AtomicU64>, acks: Arc<BatchAcks>) {
+    let mut batcher = EventBatcher::new(BatchConfig::default(), acks);
+    loop {
+        let received = records.recv_timeout(batcher.time_to_tick());
+        batcher.add_skipped(queue_skipped.swap(0, Ordering::SeqCst));
+        let batch = match received {
+            Ok(record) => batcher.push(record),
+            Err(RecvTimeoutError::Timeout) => batcher.tick(),
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if let Some(batch) = batch {
+            if let Err(e) = app.emit("packets-captured", batch) {
+                eprintln!("Failed to emit packet event: {:?}", e);
+            }
+        }
+    }
+
+    // Whatever is left when the capture stops
+    batcher.add_skipped(queue_skipped.swap(0, Ordering::SeqCst));
+    if let Some(batch) = batcher.flush() {
+        if let Err(e) = app.emit("packets-captured", batch) {
+            eprintln!("Failed to emit packet event: {:?}", e);
+        }
+    }
+}
+
+// What the dissector workers hand back for each frame
+struct Dissected {
+    application: Option<LayerThree>,
+    // Innermost protocol decoded, for the I/O graph
+    protocol: &'static str,
+    flow: Option<FlowSample>,
+    record: PacketRecord,
+}
+
+// Tables the storer keeps up to date next to the packet store
+struct LiveTables {
+    dhcp_leases: Arc<Mutex<LeaseTable>>,
+    io_graph: Arc<Mutex<Option<IoGraph>>>,
+}
+
+// Takes dissected frames in capture order: stores them, feeds the DHCP lease table, the I/O
+// graph and the flow exporter, and keeps the active view filter up to date before handing
+// them to the event batcher
+fn store_packets(
+    mut dissected: Ordered<Dissected>,
+    packets: Arc<Mutex<PacketStore>>,
+    tables: LiveTables,
+    mut flow_exporter: Option<FlowExporter>,
+    record_sender: SyncSender<PacketRecord>,
+    queue_skipped: Arc<AtomicU64>,
+    dissection_errors: Arc<AtomicU64>,
+) {
+    // BPF view filters are compiled once per filter string, not per packet
+    let mut view_program: Option<(String, pcap::BpfProgram)> = None;
+    let mut last_flow_expiry = Instant::now();
+
+    loop {
+        let DissectedFrame { frame, packet } = match dissected.recv_timeout(FLOW_EXPIRY_INTERVAL) {
+            Ok(dissected) => dissected,
+            // Lets idle flows go out while no packets arrive
+            Err(RecvTimeoutError::Timeout) => {
+                if let Some(exporter) = flow_exporter.as_mut() {
+                    last_flow_expiry = Instant::now();
+                    if let Err(e) = exporter.expire(exporter.clock()) {
+                        eprintln!("Failed to export flows: {:?}", e);
+                    }
+                }
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        let Dissected { application, protocol, flow, record: mut packet_record } = match packet {
+            Ok(packet) => packet,
+            Err(_) => {
+                // Reported through capture-stats instead of one log line per frame
+                dissection_errors.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+        };
+        if let Some(layer_3) = application {
+            if let Ok(mut leases) = tables.dhcp_leases.lock() {
+                leases.observe(&layer_3, frame.ts_sec);
+            }
+        }
+        if let Some(exporter) = flow_exporter.as_mut() {
+            if let Some(flow) = &flow {
+                exporter.observe(flow, frame.ts_sec, frame.ts_usec, frame.len);
+            }
+            if last_flow_expiry.elapsed() >= FLOW_EXPIRY_INTERVAL {
+                last_flow_expiry = Instant::now();
+                if let Err(e) = exporter.expire(exporter.clock()) {
+                    eprintln!("Failed to export flows: {:?}", e);
+                }
+            }
+        }
+
+        let Ok(mut store) = packets.lock() else {
+            continue;
+        };
+        let stored = match store.push(frame.ts_sec, frame.ts_usec, frame.len, frame.data) {
+            Ok(stored) => stored,
+            Err(e) => {
+                eprintln!("Failed to store packet: {:?}", e);
+                continue;
+            }
+        };
+        packet_record.frame_number = Some(stored.frame_number);
+
+        // Keep an active view filter up to date while capturing
+        let in_view = match store.view_filter() {
+            Some(ViewFilter::Display(display_filter)) => display_filter.matches(&packet_record.fields),
+            Some(ViewFilter::Bpf(text)) => {
+                if view_program.as_ref().map(|(cached, _)| cached != text).unwrap_or(true) {
+                    view_program = bpf::compile_ethernet(text).ok().map(|program| (text.clone(), program));
+                }
+                view_program
+                    .as_ref()
+                    .map(|(_, program)| program.filter(&stored.data))
+                    .unwrap_or(false)
+            }
+            None => false,
+        };
+        if in_view {
+            store.add_to_view(stored.frame_number);
+        }
+        drop(store);
+
+        if let Some(io_graph) = tables.io_graph.lock().ok().as_mut().and_then(|io_graph| io_graph.as_mut()) {
+            if io_graph.matches(&packet_record.fields) {
+                io_graph.add(stored.frame_number, stored.ts_sec, stored.ts_usec, stored.len, protocol);
+            }
+        }
+
+        // The raw bytes stay in the store, the UI fetches them by frame number
+        if let Err(TrySendError::Full(_)) = record_sender.try_send(packet_record) {
+            queue_skipped.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    if let Some(exporter) = flow_exporter {
+        if let Err(e) = exporter.finish() {
+            eprintln!("Failed to export flows: {:?}", e);
+        }
+    }
+}
+
+fn emit_capture_stats(app: &AppHandle, source: &mut PacketSource, stats: &mut StatsTracker, pipeline_dropped: u64) {
+    let kernel = source.kernel_counters();
+    if let Err(e) = app.emit("capture-stats", stats.sample(kernel, pipeline_dropped)) {
+        eprintln!("Failed to emit stats event: {:?}", e);
+    }
+}
+
+// Sends the I/O graph buckets that changed since the last event, if a graph is open
+fn emit_io_graph(app: &AppHandle, io_graph: &Mutex<Option<IoGraph>>) {
+    let update = io_graph
+        .lock()
+        .ok()
+        .as_mut()
+        .and_then(|io_graph| io_graph.as_mut())
+        .and_then(|io_graph| io_graph.take_update());
+    if let Some(update) = update {
+        if let Err(e) = app.emit("io-graph", update) {
+            eprintln!("Failed to emit I/O graph event: {:?}", e);
+        }
+    }
 }
 
 // Add new command to set filter
 #[tauri::command]
-fn set_packet_filter(filter: String, state: State<CaptureState>) -> Result<(), String> {
+fn set_packet_filter(filter: String, state: State<CaptureState>) -> Result<(), CaptureError> {
     // Validate filter syntax before storing
-    // Create a temporary capture to test the filter
-    let device = Device::lookup()
-        .map_err(|e| format!("Device lookup error: {}", e))?
-        .ok_or("No device found")?;
-    
-    let cap_builder = Capture::from_device(device)
-        .map_err(|e| format!("Capture error: {}", e))?;
-    
-    let mut test_capture = cap_builder
-        .open()
-        .map_err(|e| format!("Open capture error: {}", e))?;
-    
-    // Test if filter is valid
-    test_capture
-        .filter(&filter, true)
-        .map_err(|e| format!("Invalid filter syntax: {}", e))?;
+    bpf::compile_ethernet(&filter)?;
     
     // If we get here, filter is valid - store it
     if let Ok(mut current_filter) = state.filter.lock() {
         *current_filter = filter;
         Ok(())
     } else {
-        Err("Failed to set filter".to_string())
+        Err(CaptureError::Internal("Failed to set filter".to_string()))
     }
 }
 
-// Modify start_packet_capture to use the stored filter
-#[tauri::command]
-fn start_packet_capture(app: AppHandle, state: State<CaptureState>) {
-    let running = state.running.clone();
-    let filter = state.filter.clone(); // Clone filter reference
-    running.store(true, Ordering::SeqCst);
+fn emit_capture_state(app: &AppHandle, event: CaptureStateEvent) {
+    if let Err(e) = app.emit("capture-state", event) {
+        eprintln!("Failed to emit capture state: {:?}", e);
+    }
+}
 
-    thread::spawn(move || {
-        println!("Starting packet capture...");
-        let device = match Device::lookup() {
-            Ok(Some(device)) => device,
-            _ => {
-                eprintln!("Failed to find a network device.");
-                return;
-            }
-        };
+// Anything that stops a capture also goes out as a capture-error event, with a hint when there is one
+fn report_capture_error(app: &AppHandle, session_id: Option<u64>, error: &CaptureError) {
+    eprintln!("{}", error);
+    let event = CaptureErrorEvent { session_id, error: error.clone() };
+    if let Err(e) = app.emit("capture-error", event) {
+        eprintln!("Failed to emit capture error: {:?}", e);
+    }
+}
 
-        let cap_builder = Capture::from_device(device)
-            .unwrap()
-            .immediate_mode(true)
-            .snaplen(65535)
-            .promisc(true);
+// Opens the default device, a remote agent or a pipe, with the stored filter applied
+fn open_capture(source: &CaptureSource, filter: &Mutex<String>) -> Result<PacketSource, CaptureError> {
+    let current_filter = filter.lock().map(|filter| filter.clone()).unwrap_or_default();
+    // The read timeout lets the capture loop notice a stop request on a quiet network
+    PacketSource::open(source, &current_filter, READ_TIMEOUT_MS)
+}
 
-        let mut capture = match cap_builder.open() {
-            Ok(capture) => capture,
-            Err(e) => {
-                eprintln!("Failed to open capture: {:?}", e);
-                return;
+// The source is opened before returning so failures reach the caller; returns the new session ID.
+// Opening can take seconds (a remote agent's handshake, a pipe's file header), so it runs off
+// the main thread
+#[tauri::command]
+async fn start_packet_capture(
+    app: AppHandle,
+    source: Option<CaptureSource>,
+    state: State<'_, CaptureState>,
+) -> Result<u64, CaptureError> {
+    // The session is claimed up front so a second start fails while this one is still opening
+    let (session_id, running) = {
+        let mut session = state
+            .session
+            .lock()
+            .map_err(|_| CaptureError::Internal("Failed to start capture".to_string()))?;
+        if let Some(current) = session.as_ref() {
+            if current.is_active() {
+                return Err(CaptureError::AlreadyRunning(current.id));
             }
-        };
+        }
+        let session_id = state.next_session_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let new_session = CaptureSession::new(session_id);
+        let running = new_session.running();
+        *session = Some(new_session);
+        (session_id, running)
+    };
 
-        // Apply the stored filter if one exists
-        if let Ok(current_filter) = filter.lock() {
-            if !current_filter.is_empty() {
-                if let Err(e) = capture.filter(&current_filter, true) {
-                    eprintln!("Failed to set filter: {:?}", e);
-                    return;
+    emit_capture_state(&app, CaptureStateEvent::new(session_id, CaptureStatus::Starting));
+    let flow_export = state.flow_export.lock().map(|config| config.clone()).unwrap_or_default();
+    let filter = state.filter.clone();
+    let opened = tauri::async_runtime::spawn_blocking(move || {
+        open_capture(&source.unwrap_or_default(), &filter).and_then(|source| {
+            // A collector that does not resolve or a file that cannot be created fails the start
+            let flow_exporter = if flow_export.enabled {
+                Some(FlowExporter::new(flow_export).map_err(|e| CaptureError::FlowExport(e.to_string()))?)
+            } else {
+                None
+            };
+            Ok((source, flow_exporter))
+        })
+    })
+    .await
+    .map_err(|e| CaptureError::Internal(format!("Opening the capture failed: {}", e)))
+    .and_then(|opened| opened);
+    let (mut source, flow_exporter) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+            if let Ok(mut session) = state.session.lock() {
+                if session.as_ref().map(|session| session.id) == Some(session_id) {
+                    *session = None;
                 }
             }
+            report_capture_error(&app, Some(session_id), &e);
+            emit_capture_state(&app, CaptureStateEvent::error(session_id, e.to_string()));
+            return Err(e);
+        }
+    };
+
+    let decode_as = state.decode_as.clone();
+    let tables = LiveTables {
+        dhcp_leases: state.dhcp_leases.clone(),
+        io_graph: state.io_graph.clone(),
+    };
+    let io_graph = state.io_graph.clone();
+    let packets = state.packets.clone();
+    let batch_acks = state.batch_acks.clone();
+    emit_capture_state(&app, CaptureStateEvent::new(session_id, CaptureStatus::Running));
+
+    let handle = thread::spawn(move || {
+        println!("Starting packet capture...");
+        let (record_sender, records) = mpsc::sync_channel(EVENT_QUEUE_LEN);
+        let queue_skipped = Arc::new(AtomicU64::new(0));
+        {
+            let app = app.clone();
+            let queue_skipped = queue_skipped.clone();
+            thread::spawn(move || emit_batches(app, records, queue_skipped, batch_acks));
         }
 
+        // This thread only copies frames out of pcap, dissection happens on the workers
+        let (mut pipeline, dissected) = Pipeline::start(PipelineConfig::default(), move |frame: &RawFrame| {
+            let custom_packet = match decode_as.read() {
+                Ok(decode_as) => MyPacket::from_frame(&frame.data, &decode_as)?,
+                Err(_) => MyPacket::from_frame(&frame.data, &DecodeAsTable::default())?,
+            };
+            Ok(Dissected {
+                application: custom_packet.get_application(),
+                protocol: custom_packet.protocols().last().copied().unwrap_or_default(),
+                flow: FlowSample::of(&custom_packet),
+                record: packet_record(&custom_packet, frame.ts_sec, frame.ts_usec),
+            })
+        });
+        let mut stats = StatsTracker::new(STATS_INTERVAL);
+        let dissection_errors = stats.dissection_errors();
+        let storer = thread::spawn(move || {
+            store_packets(dissected, packets, tables, flow_exporter, record_sender, queue_skipped, dissection_errors)
+        });
+
         // Rest of your capture loop remains the same
+        let mut error = None;
         while running.load(Ordering::SeqCst) {
-                if let Ok(new_packet) = capture.next_packet() {
-                    if let Some(ethernet_packet) = EthernetPacket::new(&new_packet.data) {
-                        match MyPacket::new(&ethernet_packet) {
-                            Ok(custom_packet) => {
-                                let unix_timestamp: libc::timeval = new_packet.header.ts; // Your timestamp
-                                let time: String = format_timeval(unix_timestamp);
-                                let mut packet_info: HashMap<String, HashMap<String, String>> = custom_packet.get_packet_info();
-                                let encode = STANDARD.encode(&new_packet.data);
-                                packet_info.insert("raw_data".to_string(), HashMap::from([
-                                    ("data".to_string(), encode),
-                                    ("header".to_string(), format!("{:?}", new_packet.header))
-                                ]));
-                                packet_info.insert("timestamp".to_string(), HashMap::from([("timestamp".to_string(), time)]));
-                                if let Err(e) = app.emit("packet-captured", packet_info) {
-                                    eprintln!("Failed to emit packet event: {:?}", e);
-                                }
-                            }
-                            Err(e) => {
-                                eprintln!("Failed to process packet: {:?}", e);
-                            }
-                        }
-                    }
+            match source.next_frame() {
+                Ok(NextFrame::Frame(frame)) => {
+                    stats.record(frame.len);
+                    pipeline.submit(frame.ts_sec, frame.ts_usec, frame.len, frame.data);
+                }
+                Ok(NextFrame::Timeout) => {}
+                Ok(NextFrame::End) => break,
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+            if stats.due() {
+                emit_capture_stats(&app, &mut source, &mut stats, pipeline.dropped());
+                emit_io_graph(&app, &io_graph);
             }
         }
 
+        let pipeline_dropped = pipeline.dropped();
+        pipeline.finish();
+        if storer.join().is_err() {
+            eprintln!("Packet store thread panicked");
+        }
+        // Final totals, including the errors from frames dissected after the loop ended
+        emit_capture_stats(&app, &mut source, &mut stats, pipeline_dropped);
+        emit_io_graph(&app, &io_graph);
         println!("Packet capture stopped.");
+        match error {
+            Some(error) => {
+                report_capture_error(&app, Some(session_id), &error);
+                emit_capture_state(&app, CaptureStateEvent::error(session_id, error.to_string()));
+            }
+            None => emit_capture_state(&app, CaptureStateEvent::new(session_id, CaptureStatus::Stopped)),
+        }
     });
+
+    // A stop that came in while the source was opening has already cleared `running`, and the
+    // thread exits on its first check
+    if let Ok(mut session) = state.session.lock() {
+        if let Some(session) = session.as_mut().filter(|session| session.id == session_id) {
+            session.attach(handle);
+        }
+    }
+    Ok(session_id)
 }
 
+// Returns once the capture thread has exited, which takes at most one read timeout
 #[tauri::command]
-fn stop_packet_capture(state: State<CaptureState>) {
-    state.running.store(false, Ordering::SeqCst); // Set the flag to false
+async fn stop_packet_capture(state: State<'_, CaptureState>) -> Result<(), CaptureError> {
+    let session = state
+        .session
+        .lock()
+        .map_err(|_| CaptureError::Internal("Failed to stop capture".to_string()))?
+        .take();
+    let Some(handle) = session.and_then(|session| session.stop()) else {
+        return Ok(());
+    };
     println!("Stopping packet capture...");
+    tauri::async_runtime::spawn_blocking(move || handle.join())
+        .await
+        .map_err(|e| CaptureError::Internal(format!("Failed to stop capture: {}", e)))?
+        .map_err(|_| CaptureError::Internal("Capture thread panicked".to_string()))
 }
 
+// Narrows the stored packets to those matching a BPF filter, get_packets then pages through them
 #[tauri::command]
-fn filter_packets(packets: Vec<HashMap<String, HashMap<String, String>>>, filter: String) -> Result<Vec<HashMap<String, HashMap<String, String>>>, String> {
-    // Create a temporary capture for filter compilation
+async fn filter_packets(filter: String, state: State<'_, CaptureState>) -> Result<PacketCounts, CaptureError> {
     println!("Filter: {:?}", filter);
-    let device = Device::lookup()
-        .map_err(|e| format!("Device lookup error: {}", e))?
-        .ok_or("No device found")?;
-    
-    let cap_builder = Capture::from_device(device)
-        .map_err(|e| format!("Capture error: {}", e))?;
-    
-    let mut capture = cap_builder
-        .open()
-        .map_err(|e| format!("Open capture error: {}", e))?;
-
-    // Compile the filter using the capture
-    let program = capture.compile(filter.as_str(), true)
-        .map_err(|e| format!("Failed to compile filter: {}", e))?;
-    
-    // Filter packets using stored raw data
-    let filtered_packets: Vec<HashMap<String, HashMap<String, String>>> = packets
-        .into_iter()
-        .filter(|packet| {
-            if let Some(raw_data) = packet.get("raw_data") {
-                if let Some(data_str) = raw_data.get("data") {
-                    // Decode base64 raw data
-                    if let Ok(packet_data) = STANDARD.decode(data_str) {
-                        // Apply the filter program to the packet data
-                        program.filter(&packet_data)
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
-            } else {
-                false
-            }
-        })
-        .collect();
-
-    Ok(filtered_packets)
+    let packets = state.packets.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        // Compile the filter without touching a live device
+        let program = bpf::compile_ethernet(&filter)?;
+        // Filter packets using stored raw data
+        let (mut packets, frames) = filter_frames(&packets, |packet| program.filter(&packet.data))?;
+        packets.set_view(ViewFilter::Bpf(filter), frames);
+        Ok(packets.counts())
+    })
+    .await
+    .map_err(|e| CaptureError::Internal(format!("Filtering failed: {}", e)))?
 }
 // Update the run function to include the new command
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .manage(CaptureState::default())
+        .setup(|app| {
+            // Restore the persisted decode as rules
+            let decode_as = DecodeAsTable::load(&decode_as_path(app.handle())?)?;
+            let state = app.state::<CaptureState>();
+            if let Ok(mut current) = state.decode_as.write() {
+                *current = decode_as;
+            }
+
+            let flow_export = FlowExportConfig::load(&flow_export_config_path(app.handle())?)?;
+            if let Ok(mut current) = state.flow_export.lock() {
+                *current = flow_export;
+            }
+
+            let ring_buffer_config = RingBufferConfig::load(&ring_buffer_config_path(app.handle())?)?;
+            if let Ok(mut packets) = state.packets.lock() {
+                packets.open(capture_dir(app.handle())?, ring_buffer_config)?;
+            }
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             start_packet_capture, 
             stop_packet_capture,
             set_packet_filter,  // Add the new command
-            filter_packets
+            filter_packets,
+            get_decode_as,
+            set_decode_as,
+            remove_decode_as,
+            get_dhcp_leases,
+            clear_dhcp_leases,
+            render_payload,
+            check_display_filter,
+            apply_display_filter,
+            clear_packet_filter,
+            get_packet_count,
+            get_packets,
+            get_packet,
+            get_ring_buffer_config,
+            ack_packets,
+            set_ring_buffer_config,
+            clear_packets,
+            export_packets,
+            get_protocol_hierarchy,
+            get_endpoints,
+            get_dns_stats,
+            start_io_graph,
+            stop_io_graph,
+            get_flow_export_config,
+            set_flow_export_config
         ])
         .run(tauri::generate_context!())
         .expect("Error while running Tauri application");
@@ -0,0 +1,63 @@
+use pcap::{BpfProgram, Capture, Linktype};
+
+use super::capture_error::CaptureError;
+
+// Compiles a BPF filter against a dead capture, so no device, root or
+// CAP_NET_RAW is needed just to validate or evaluate a filter
+pub fn compile(filter: &str, linktype: Linktype) -> Result<BpfProgram, CaptureError> {
+    let capture = Capture::dead(linktype).map_err(|e| CaptureError::Internal(format!("Capture error: {}", e)))?;
+    capture
+        .compile(filter, true)
+        .map_err(|e| CaptureError::invalid_filter(filter, e))
+}
+
+// Frames from start_packet_capture are always Ethernet
+pub fn compile_ethernet(filter: &str) -> Result<BpfProgram, CaptureError> {
+    compile(filter, Linktype::ETHERNET)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An Ethernet/IPv4/UDP frame from 10.0.0.2:40000 to 10.0.0.1 with an 8 byte payload
+    fn udp_frame(dst_port: u16) -> Vec<u8> {
+        let mut frame = vec![0x02, 0, 0, 0, 0, 1, 0x02, 0, 0, 0, 0, 2, 0x08, 0x00];
+        frame.extend([0x45, 0, 0, 36, 0, 0, 0x40, 0, 64, 17, 0, 0, 10, 0, 0, 2, 10, 0, 0, 1]);
+        frame.extend(40000u16.to_be_bytes());
+        frame.extend(dst_port.to_be_bytes());
+        frame.extend([0, 16, 0, 0]);
+        frame.extend([0; 8]);
+        frame
+    }
+
+    // Runs without a device or capture privileges, the filter is compiled against a dead handle
+    #[test]
+    fn compiled_filters_match_frames() {
+        let dns = udp_frame(53);
+        let http = udp_frame(80);
+
+        let program = compile_ethernet("udp dst port 53").unwrap();
+        assert!(program.filter(&dns));
+        assert!(!program.filter(&http));
+
+        let program = compile_ethernet("src host 10.0.0.2 and not tcp").unwrap();
+        assert!(program.filter(&dns) && program.filter(&http));
+        assert!(!compile_ethernet("host 192.168.1.1").unwrap().filter(&dns));
+        assert!(!compile_ethernet("ip6").unwrap().filter(&dns));
+    }
+
+    #[test]
+    fn syntax_errors_are_invalid_filters() {
+        for filter in ["udp prot 53", "host 10.0.0.300", "(tcp"] {
+            match compile_ethernet(filter) {
+                Err(CaptureError::InvalidFilter { filter: reported, message }) => {
+                    assert_eq!(reported, filter);
+                    assert!(!message.is_empty());
+                }
+                Err(other) => panic!("{}: expected an invalid filter, got {:?}", filter, other),
+                Ok(_) => panic!("{}: compiled", filter),
+            }
+        }
+    }
+}
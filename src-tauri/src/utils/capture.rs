@@ -0,0 +1,178 @@
+use pcap::{Active, Capture, Device, Offline};
+use serde::Deserialize;
+use std::path::Path;
+
+use super::capture_error::CaptureError;
+use super::capture_stats::KernelCounters;
+use super::link::LinkType;
+use super::pcap_stream::StreamCapture;
+use super::pcapng::Frame;
+use super::remote::RemoteCapture;
+
+pub fn list_devices() -> Result<Vec<Device>, CaptureError> {
+    Device::list().map_err(|e| CaptureError::DeviceLookup(e.to_string()))
+}
+
+// Opens `interface` (or the default device) for a live capture with a BPF filter applied.
+// `read_timeout_ms` bounds how long a read blocks on a quiet network
+pub fn open_device(interface: Option<&str>, filter: &str, read_timeout_ms: i32) -> Result<Capture<Active>, CaptureError> {
+    let device = match interface {
+        Some(name) => list_devices()?
+            .into_iter()
+            .find(|device| device.name == name)
+            .ok_or(CaptureError::NoDevice)?,
+        None => Device::lookup()
+            .map_err(|e| CaptureError::DeviceLookup(e.to_string()))?
+            .ok_or(CaptureError::NoDevice)?,
+    };
+    let device_name = device.name.clone();
+
+    let mut capture = Capture::from_device(device)
+        .map_err(|e| CaptureError::open(&device_name, e))?
+        .immediate_mode(true)
+        .snaplen(65535)
+        .promisc(true)
+        .timeout(read_timeout_ms)
+        .open()
+        .map_err(|e| CaptureError::open(&device_name, e))?;
+
+    if !filter.is_empty() {
+        capture
+            .filter(filter, true)
+            .map_err(|e| CaptureError::invalid_filter(filter, e))?;
+    }
+    Ok(capture)
+}
+
+// Reads a pcap or pcapng file with a BPF filter applied
+pub fn open_file(path: &Path, filter: &str) -> Result<Capture<Offline>, CaptureError> {
+    let path_name = path.display().to_string();
+    let mut capture = Capture::from_file(path).map_err(|e| CaptureError::open(&path_name, e))?;
+    if !filter.is_empty() {
+        capture
+            .filter(filter, true)
+            .map_err(|e| CaptureError::invalid_filter(filter, e))?;
+    }
+    Ok(capture)
+}
+
+// stdin ("-") and named pipes are read as they are written instead of as a finished file
+pub fn is_stream(path: &Path) -> bool {
+    if path.as_os_str() == "-" {
+        return true;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if let Ok(metadata) = std::fs::metadata(path) {
+            return metadata.file_type().is_fifo();
+        }
+    }
+    false
+}
+
+// Stored packets are re-dissected as Ethernet, so the app only takes Ethernet sources
+fn require_ethernet(name: &str, link_type: i32) -> Result<(), CaptureError> {
+    if LinkType::from_dlt(link_type) != Some(LinkType::Ethernet) {
+        return Err(CaptureError::Open {
+            device: name.to_string(),
+            message: format!("link type {} is not supported, capture on an Ethernet interface", link_type),
+        });
+    }
+    Ok(())
+}
+
+// Where start_packet_capture reads from, the local default device unless told otherwise
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum CaptureSource {
+    #[default]
+    Local,
+    // A `metal-sniffer-cli --serve` agent on another machine
+    Remote { address: String, token: String },
+    // A pcap/pcapng stream from a named pipe, or "-" for stdin, e.g. `tcpdump -w - | metal-sniffer`
+    Pipe { path: String },
+}
+
+pub enum PacketSource {
+    Device(Capture<Active>),
+    File(Capture<Offline>),
+    Remote(RemoteCapture),
+    Stream(StreamCapture),
+}
+
+pub enum NextFrame {
+    Frame(Frame),
+    // Nothing arrived within the read timeout, try again
+    Timeout,
+    // A file or stream has been read to the end
+    End,
+}
+
+impl PacketSource {
+    pub fn open(source: &CaptureSource, filter: &str, read_timeout_ms: i32) -> Result<PacketSource, CaptureError> {
+        match source {
+            CaptureSource::Local => open_device(None, filter, read_timeout_ms).map(PacketSource::Device),
+            CaptureSource::Remote { address, token } => {
+                let remote = RemoteCapture::connect(address, token, filter, read_timeout_ms)?;
+                require_ethernet(address, remote.link_type())?;
+                Ok(PacketSource::Remote(remote))
+            }
+            CaptureSource::Pipe { path } => {
+                let stream = StreamCapture::open(path, filter, read_timeout_ms)?;
+                require_ethernet(stream.name(), stream.link_type())?;
+                Ok(PacketSource::Stream(stream))
+            }
+        }
+    }
+
+    // libpcap link type of the frames next_frame returns
+    pub fn link_type(&self) -> i32 {
+        match self {
+            PacketSource::Device(capture) => capture.get_datalink().0,
+            PacketSource::File(capture) => capture.get_datalink().0,
+            PacketSource::Remote(remote) => remote.link_type(),
+            PacketSource::Stream(stream) => stream.link_type(),
+        }
+    }
+
+    pub fn next_frame(&mut self) -> Result<NextFrame, CaptureError> {
+        let next = match self {
+            PacketSource::Device(capture) => capture.next_packet(),
+            PacketSource::File(capture) => capture.next_packet(),
+            PacketSource::Remote(remote) => {
+                return Ok(remote.next_frame()?.map(NextFrame::Frame).unwrap_or(NextFrame::Timeout))
+            }
+            PacketSource::Stream(stream) => return stream.next_frame(),
+        };
+        match next {
+            Ok(packet) => Ok(NextFrame::Frame(Frame {
+                ts_sec: packet.header.ts.tv_sec as i64,
+                ts_usec: packet.header.ts.tv_usec as u32,
+                len: packet.header.len,
+                data: packet.data.to_vec(),
+            })),
+            Err(pcap::Error::TimeoutExpired) => Ok(NextFrame::Timeout),
+            Err(pcap::Error::NoMorePackets) => Ok(NextFrame::End),
+            Err(e) => Err(CaptureError::Read(e.to_string())),
+        }
+    }
+
+    // Only local devices have kernel counters, an agent keeps its own
+    pub fn kernel_counters(&mut self) -> Option<KernelCounters> {
+        match self {
+            PacketSource::Device(capture) => match capture.stats() {
+                Ok(stat) => Some(KernelCounters {
+                    received: stat.received,
+                    dropped: stat.dropped,
+                    if_dropped: stat.if_dropped,
+                }),
+                Err(e) => {
+                    eprintln!("Failed to read capture stats: {:?}", e);
+                    None
+                }
+            },
+            PacketSource::File(_) | PacketSource::Remote(_) | PacketSource::Stream(_) => None,
+        }
+    }
+}
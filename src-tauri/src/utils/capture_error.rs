@@ -0,0 +1,127 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Error)]
+pub enum CaptureError {
+    #[error("Failed to find a network device.")]
+    NoDevice,
+    #[error("Failed to look up network devices: {0}")]
+    DeviceLookup(String),
+    #[error("Permission denied opening {device}: {message}")]
+    PermissionDenied { device: String, message: String },
+    #[error("Failed to open capture on {device}: {message}")]
+    Open { device: String, message: String },
+    #[error("Invalid filter syntax: {message}")]
+    InvalidFilter { filter: String, message: String },
+    #[error("Capture session {0} is already running")]
+    AlreadyRunning(u64),
+    #[error("Capture failed: {0}")]
+    Read(String),
+    #[error("No packet {0}")]
+    NoPacket(u64),
+    #[error("Failed to read packets: {0}")]
+    Storage(String),
+    #[error("Failed to process packet: {0}")]
+    Decode(String),
+    #[error("Failed to export packets: {0}")]
+    Export(String),
+    #[error("Failed to start flow export: {0}")]
+    FlowExport(String),
+    #[error("Remote agent refused the capture: {0}")]
+    RemoteRejected(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl CaptureError {
+    // Classifies a pcap failure while opening a device, libpcap only reports these as text
+    pub fn open(device: &str, error: pcap::Error) -> CaptureError {
+        let message = error.to_string();
+        let lower = message.to_lowercase();
+        if lower.contains("permission denied") || lower.contains("not permitted") {
+            CaptureError::PermissionDenied { device: device.to_string(), message }
+        } else {
+            CaptureError::Open { device: device.to_string(), message }
+        }
+    }
+
+    pub fn invalid_filter(filter: &str, error: pcap::Error) -> CaptureError {
+        CaptureError::InvalidFilter { filter: filter.to_string(), message: error.to_string() }
+    }
+
+    // Stable identifier the UI can switch on
+    pub fn code(&self) -> &'static str {
+        match self {
+            CaptureError::NoDevice => "no_device",
+            CaptureError::DeviceLookup(_) => "device_lookup",
+            CaptureError::PermissionDenied { .. } => "permission_denied",
+            CaptureError::Open { .. } => "open_failed",
+            CaptureError::InvalidFilter { .. } => "invalid_filter",
+            CaptureError::AlreadyRunning(_) => "already_running",
+            CaptureError::Read(_) => "read_failed",
+            CaptureError::NoPacket(_) => "no_packet",
+            CaptureError::Storage(_) => "storage",
+            CaptureError::Decode(_) => "decode_failed",
+            CaptureError::Export(_) => "export_failed",
+            CaptureError::FlowExport(_) => "flow_export_failed",
+            CaptureError::RemoteRejected(_) => "remote_rejected",
+            CaptureError::Internal(_) => "internal",
+        }
+    }
+
+    // What the user can do about it, if there is anything
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            CaptureError::NoDevice => Some("Check that a network interface is up and that libpcap/Npcap is installed."),
+            CaptureError::PermissionDenied { .. } => Some(permission_hint()),
+            CaptureError::InvalidFilter { .. } => {
+                Some("Capture filters use BPF syntax, e.g. \"tcp port 443\" or \"host 10.0.0.1\".")
+            }
+            CaptureError::AlreadyRunning(_) => Some("Stop the current capture before starting a new one."),
+            CaptureError::Storage(_) => Some("Check free disk space in the capture cache directory."),
+            CaptureError::FlowExport(_) => Some("Check the collector address and output file in the flow export settings."),
+            CaptureError::RemoteRejected(_) => Some("Check the token the agent was started with and that both sides run the same version."),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn permission_hint() -> &'static str {
+    "Capturing needs CAP_NET_RAW and CAP_NET_ADMIN: run `sudo setcap cap_net_raw,cap_net_admin=eip` on the binary, or run it as root."
+}
+
+#[cfg(target_os = "macos")]
+fn permission_hint() -> &'static str {
+    "Your user needs read access to /dev/bpf*: install Wireshark's ChmodBPF helper, or run with sudo."
+}
+
+#[cfg(target_os = "windows")]
+fn permission_hint() -> &'static str {
+    "Install Npcap and run Metal Sniffer as an administrator."
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn permission_hint() -> &'static str {
+    "Run Metal Sniffer with privileges that allow raw packet capture."
+}
+
+// Commands reject with { code, message, hint } so the UI does not have to parse messages
+impl Serialize for CaptureError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("CaptureError", 3)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.serialize_field("hint", &self.hint())?;
+        error.end()
+    }
+}
+
+// Payload of the `capture-error` event
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureErrorEvent {
+    pub session_id: Option<u64>,
+    #[serde(flatten)]
+    pub error: CaptureError,
+}
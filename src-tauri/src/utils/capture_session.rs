@@ -0,0 +1,73 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureStatus {
+    Starting,
+    Running,
+    Stopped,
+    Error,
+}
+
+// Payload of the `capture-state` event
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureStateEvent {
+    pub session_id: u64,
+    pub status: CaptureStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CaptureStateEvent {
+    pub fn new(session_id: u64, status: CaptureStatus) -> CaptureStateEvent {
+        CaptureStateEvent { session_id, status, error: None }
+    }
+
+    pub fn error(session_id: u64, error: String) -> CaptureStateEvent {
+        CaptureStateEvent { session_id, status: CaptureStatus::Error, error: Some(error) }
+    }
+}
+
+// One start/stop cycle of the capture thread
+pub struct CaptureSession {
+    pub id: u64,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl CaptureSession {
+    pub fn new(id: u64) -> CaptureSession {
+        CaptureSession {
+            id,
+            running: Arc::new(AtomicBool::new(true)),
+            handle: None,
+        }
+    }
+
+    // The flag the capture thread polls between reads
+    pub fn running(&self) -> Arc<AtomicBool> {
+        self.running.clone()
+    }
+
+    pub fn attach(&mut self, handle: JoinHandle<()>) {
+        self.handle = Some(handle);
+    }
+
+    // True while the source is still being opened, false once the thread has exited, whether
+    // it was stopped or failed
+    pub fn is_active(&self) -> bool {
+        match &self.handle {
+            Some(handle) => !handle.is_finished(),
+            None => self.running.load(Ordering::SeqCst),
+        }
+    }
+
+    // Asks the thread to stop and hands back its handle so the caller decides where to wait
+    pub fn stop(mut self) -> Option<JoinHandle<()>> {
+        self.running.store(false, Ordering::SeqCst);
+        self.handle.take()
+    }
+}
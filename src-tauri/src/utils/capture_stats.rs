@@ -0,0 +1,117 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Counters libpcap keeps for the open handle, cumulative since it was opened
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KernelCounters {
+    pub received: u32,
+    pub dropped: u32,
+    pub if_dropped: u32,
+}
+
+// One `capture-stats` event. Counts are totals for the capture, rates cover the last interval.
+// Sources without kernel counters (files, agents, pipes) report the frames read as received
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CaptureStats {
+    pub received: u64,
+    pub dropped_by_kernel: u64,
+    pub dropped_by_interface: u64,
+    // Frames the capture thread had to drop because the dissector workers were behind
+    pub dropped_by_pipeline: u64,
+    pub dissection_errors: u64,
+    pub packets_per_sec: f64,
+    pub bytes_per_sec: f64,
+}
+
+pub struct StatsTracker {
+    interval: Duration,
+    last_sample: Instant,
+    packets: u64,
+    bytes: u64,
+    // Frames read since the capture started, what `received` falls back to
+    total_packets: u64,
+    dissection_errors: Arc<AtomicU64>,
+}
+
+impl StatsTracker {
+    pub fn new(interval: Duration) -> StatsTracker {
+        StatsTracker {
+            interval,
+            last_sample: Instant::now(),
+            packets: 0,
+            bytes: 0,
+            total_packets: 0,
+            dissection_errors: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    // Shared with whoever dissects, bumped for every frame that fails to decode
+    pub fn dissection_errors(&self) -> Arc<AtomicU64> {
+        self.dissection_errors.clone()
+    }
+
+    // Called by the capture thread for every frame read, `len` is the length on the wire
+    pub fn record(&mut self, len: u32) {
+        self.packets += 1;
+        self.total_packets += 1;
+        self.bytes += len as u64;
+    }
+
+    pub fn due(&self) -> bool {
+        self.last_sample.elapsed() >= self.interval
+    }
+
+    pub fn sample(&mut self, kernel: Option<KernelCounters>, dropped_by_pipeline: u64) -> CaptureStats {
+        let elapsed = self.last_sample.elapsed().as_secs_f64().max(f64::EPSILON);
+        let (received, dropped_by_kernel, dropped_by_interface) = match kernel {
+            Some(kernel) => (kernel.received as u64, kernel.dropped as u64, kernel.if_dropped as u64),
+            None => (self.total_packets, 0, 0),
+        };
+        let stats = CaptureStats {
+            received,
+            dropped_by_kernel,
+            dropped_by_interface,
+            dropped_by_pipeline,
+            dissection_errors: self.dissection_errors.load(Ordering::Relaxed),
+            packets_per_sec: self.packets as f64 / elapsed,
+            bytes_per_sec: self.bytes as f64 / elapsed,
+        };
+        self.last_sample = Instant::now();
+        self.packets = 0;
+        self.bytes = 0;
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernel_counters_are_reported_when_available() {
+        let mut tracker = StatsTracker::new(Duration::from_secs(1));
+        tracker.record(100);
+        let kernel = KernelCounters { received: 10, dropped: 2, if_dropped: 1 };
+        let stats = tracker.sample(Some(kernel), 3);
+        assert_eq!((stats.received, stats.dropped_by_kernel, stats.dropped_by_interface), (10, 2, 1));
+        assert_eq!(stats.dropped_by_pipeline, 3);
+        assert!(stats.packets_per_sec > 0.0);
+    }
+
+    #[test]
+    fn frames_read_count_as_received_without_kernel_counters() {
+        let mut tracker = StatsTracker::new(Duration::from_secs(1));
+        tracker.record(60);
+        tracker.record(60);
+        assert_eq!(tracker.sample(None, 0).received, 2);
+
+        // The rates restart every interval, the total does not
+        tracker.record(60);
+        let stats = tracker.sample(None, 0);
+        assert_eq!(stats.received, 3);
+        assert_eq!((stats.dropped_by_kernel, stats.dropped_by_interface), (0, 0));
+        assert!(stats.bytes_per_sec > 0.0);
+    }
+}
@@ -0,0 +1,507 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::IpAddr;
+
+use super::custom_packet::{CustomPacket, MyPacket};
+use super::layers::dns::{self, DnsMessage};
+use super::layers::layer3::LayerThree;
+
+// A query without a response after this long is unanswered rather than pending
+const RESPONSE_TIMEOUT_US: i64 = 5_000_000;
+// This many NXDOMAIN answers to one client within the window is a storm (typos do not
+// do this, malware walking generated domains and broken search lists do)
+const STORM_THRESHOLD: usize = 20;
+const STORM_WINDOW_US: i64 = 10_000_000;
+const RCODE_NXDOMAIN: u8 = 3;
+
+// Transaction ID plus the 5-tuple as seen from the client (UDP is implied)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct QueryKey {
+    id: u16,
+    client: IpAddr,
+    client_port: u16,
+    server: IpAddr,
+    server_port: u16,
+}
+
+#[derive(Debug)]
+struct PendingQuery {
+    name: String,
+    record_type: u16,
+    ts: i64,
+}
+
+#[derive(Debug, Default)]
+struct NameCounts {
+    queries: u64,
+    failures: u64,
+    nxdomain: u64,
+}
+
+#[derive(Debug, Default)]
+struct ResolverCounts {
+    queries: u64,
+    responses: u64,
+    unanswered: u64,
+    failures: u64,
+    total_us: u64,
+    max_us: u64,
+}
+
+#[derive(Debug, Default)]
+struct ClientNxdomain {
+    recent: VecDeque<i64>,
+    // Index into storms while this client's storm is still going
+    storm: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResponseTimes {
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub median_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NameStats {
+    pub name: String,
+    pub queries: u64,
+    pub failures: u64,
+    pub nxdomain: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolverStats {
+    pub server: IpAddr,
+    pub queries: u64,
+    pub responses: u64,
+    pub unanswered: u64,
+    pub failures: u64,
+    // Share of responses with an error reply code
+    pub failure_percent: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UnansweredQuery {
+    pub name: String,
+    pub record_type: String,
+    pub client: IpAddr,
+    pub server: IpAddr,
+    // Unix time in microseconds the query was sent
+    pub ts: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NxdomainStorm {
+    pub client: IpAddr,
+    // Unix time in microseconds of the first and last NXDOMAIN
+    pub start: i64,
+    pub end: i64,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DnsStats {
+    pub queries: u64,
+    pub responses: u64,
+    // Responses matched to a query
+    pub answered: u64,
+    pub unanswered: u64,
+    // Queries too recent to call unanswered yet
+    pub pending: u64,
+    // Queries sent again with the same ID before an answer came back
+    pub retransmissions: u64,
+    // Responses to queries sent before the capture started
+    pub unmatched_responses: u64,
+    pub failures: u64,
+    pub failure_percent: f64,
+    pub rcodes: BTreeMap<String, u64>,
+    pub response_times: Option<ResponseTimes>,
+    pub top_names: Vec<NameStats>,
+    // Slowest first
+    pub resolvers: Vec<ResolverStats>,
+    // Most recent first
+    pub unanswered_queries: Vec<UnansweredQuery>,
+    pub nxdomain_storms: Vec<NxdomainStorm>,
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+fn ms(us: u64) -> f64 {
+    us as f64 / 1000.0
+}
+
+// Pairs DNS queries with their responses by transaction ID and 5-tuple
+#[derive(Debug, Default)]
+pub struct DnsTracker {
+    pending: HashMap<QueryKey, PendingQuery>,
+    queries: u64,
+    responses: u64,
+    retransmissions: u64,
+    unmatched_responses: u64,
+    failures: u64,
+    rcodes: BTreeMap<u8, u64>,
+    response_times_us: Vec<u64>,
+    names: HashMap<String, NameCounts>,
+    resolvers: HashMap<IpAddr, ResolverCounts>,
+    unanswered: Vec<UnansweredQuery>,
+    nxdomain: HashMap<IpAddr, ClientNxdomain>,
+    storms: Vec<NxdomainStorm>,
+    last_ts: i64,
+    last_sweep: i64,
+}
+
+impl DnsTracker {
+    pub fn new() -> DnsTracker {
+        DnsTracker::default()
+    }
+
+    // Feeds a dissected packet, anything but unicast DNS over UDP is ignored
+    pub fn observe(&mut self, packet: &MyPacket, ts_sec: i64, ts_usec: u32) {
+        let Some(LayerThree::DNS(payload)) = packet.get_application() else {
+            return;
+        };
+        let (Some(message), Some((src_port, dst_port))) = (dns::parse(&payload.payload), packet.ports()) else {
+            return;
+        };
+        let ts = ts_sec * 1_000_000 + ts_usec as i64;
+        self.last_ts = self.last_ts.max(ts);
+        let (src, dst) = packet.addresses();
+
+        if message.response {
+            let key = QueryKey { id: message.id, client: dst, client_port: dst_port, server: src, server_port: src_port };
+            self.response(key, &message, ts);
+        } else {
+            let key = QueryKey { id: message.id, client: src, client_port: src_port, server: dst, server_port: dst_port };
+            self.query(key, &message, ts);
+        }
+
+        if self.last_ts - self.last_sweep >= RESPONSE_TIMEOUT_US {
+            self.last_sweep = self.last_ts;
+            self.expire(self.last_ts - RESPONSE_TIMEOUT_US);
+        }
+    }
+
+    fn query(&mut self, key: QueryKey, message: &DnsMessage, ts: i64) {
+        let Some(question) = message.questions.first() else {
+            return;
+        };
+        // The timer keeps running from the first attempt
+        if self.pending.contains_key(&key) {
+            self.retransmissions += 1;
+            return;
+        }
+        self.queries += 1;
+        self.names.entry(question.name.clone()).or_default().queries += 1;
+        self.resolvers.entry(key.server).or_default().queries += 1;
+        self.pending.insert(key, PendingQuery { name: question.name.clone(), record_type: question.record_type, ts });
+    }
+
+    fn response(&mut self, key: QueryKey, message: &DnsMessage, ts: i64) {
+        self.responses += 1;
+        *self.rcodes.entry(message.rcode).or_default() += 1;
+        let Some(query) = self.pending.remove(&key) else {
+            self.unmatched_responses += 1;
+            return;
+        };
+
+        let elapsed = (ts - query.ts).max(0) as u64;
+        self.response_times_us.push(elapsed);
+        let resolver = self.resolvers.entry(key.server).or_default();
+        resolver.responses += 1;
+        resolver.total_us += elapsed;
+        resolver.max_us = resolver.max_us.max(elapsed);
+        if message.rcode == 0 {
+            return;
+        }
+
+        self.failures += 1;
+        resolver.failures += 1;
+        let name = self.names.entry(query.name).or_default();
+        name.failures += 1;
+        if message.rcode == RCODE_NXDOMAIN {
+            name.nxdomain += 1;
+            self.nxdomain_answer(key.client, ts);
+        }
+    }
+
+    fn nxdomain_answer(&mut self, client: IpAddr, ts: i64) {
+        let state = self.nxdomain.entry(client).or_default();
+        state.recent.push_back(ts);
+        while state.recent.front().map(|first| *first < ts - STORM_WINDOW_US).unwrap_or(false) {
+            state.recent.pop_front();
+        }
+        match state.storm {
+            Some(index) if ts - self.storms[index].end <= STORM_WINDOW_US => {
+                self.storms[index].end = ts;
+                self.storms[index].count += 1;
+            }
+            _ => {
+                state.storm = None;
+                if state.recent.len() >= STORM_THRESHOLD {
+                    state.storm = Some(self.storms.len());
+                    self.storms.push(NxdomainStorm {
+                        client,
+                        start: state.recent.front().copied().unwrap_or(ts),
+                        end: ts,
+                        count: state.recent.len() as u64,
+                    });
+                }
+            }
+        }
+    }
+
+    // Queries sent before `cutoff` will not be answered any more
+    fn expire(&mut self, cutoff: i64) {
+        let expired: Vec<QueryKey> = self
+            .pending
+            .iter()
+            .filter(|(_, query)| query.ts < cutoff)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in expired {
+            let Some(query) = self.pending.remove(&key) else {
+                continue;
+            };
+            self.resolvers.entry(key.server).or_default().unanswered += 1;
+            self.unanswered.push(UnansweredQuery {
+                name: query.name,
+                record_type: dns::type_name(query.record_type).to_string(),
+                client: key.client,
+                server: key.server,
+                ts: query.ts,
+            });
+        }
+    }
+
+    // Settles everything older than the timeout and summarizes, lists are cut to `top` entries
+    pub fn report(&mut self, top: usize) -> DnsStats {
+        self.expire(self.last_ts - RESPONSE_TIMEOUT_US);
+
+        let mut times = self.response_times_us.clone();
+        times.sort_unstable();
+        let response_times = (!times.is_empty()).then(|| ResponseTimes {
+            min_ms: ms(times[0]),
+            avg_ms: ms(times.iter().sum::<u64>() / times.len() as u64),
+            median_ms: ms(times[times.len() / 2]),
+            p95_ms: ms(times[(times.len() * 95 / 100).min(times.len() - 1)]),
+            max_ms: ms(times[times.len() - 1]),
+        });
+
+        let mut top_names: Vec<NameStats> = self
+            .names
+            .iter()
+            .map(|(name, counts)| NameStats {
+                name: name.clone(),
+                queries: counts.queries,
+                failures: counts.failures,
+                nxdomain: counts.nxdomain,
+            })
+            .collect();
+        top_names.sort_by(|a, b| b.queries.cmp(&a.queries).then_with(|| a.name.cmp(&b.name)));
+        top_names.truncate(top);
+
+        let mut resolvers: Vec<ResolverStats> = self
+            .resolvers
+            .iter()
+            .map(|(server, counts)| ResolverStats {
+                server: *server,
+                queries: counts.queries,
+                responses: counts.responses,
+                unanswered: counts.unanswered,
+                failures: counts.failures,
+                failure_percent: percent(counts.failures, counts.responses),
+                avg_ms: counts.total_us.checked_div(counts.responses).map(ms).unwrap_or(0.0),
+                max_ms: ms(counts.max_us),
+            })
+            .collect();
+        resolvers.sort_by(|a, b| b.avg_ms.total_cmp(&a.avg_ms).then_with(|| a.server.cmp(&b.server)));
+        resolvers.truncate(top);
+
+        let mut unanswered_queries = self.unanswered.clone();
+        unanswered_queries.sort_by_key(|query| std::cmp::Reverse(query.ts));
+        unanswered_queries.truncate(top);
+
+        // Every rcode from 11 up is "Unknown", so their counts are added together
+        let mut rcodes = BTreeMap::new();
+        for (rcode, count) in &self.rcodes {
+            *rcodes.entry(dns::rcode_name(*rcode).to_string()).or_default() += *count;
+        }
+
+        DnsStats {
+            queries: self.queries,
+            responses: self.responses,
+            answered: self.response_times_us.len() as u64,
+            unanswered: self.unanswered.len() as u64,
+            pending: self.pending.len() as u64,
+            retransmissions: self.retransmissions,
+            unmatched_responses: self.unmatched_responses,
+            failures: self.failures,
+            failure_percent: percent(self.failures, self.response_times_us.len() as u64),
+            rcodes,
+            response_times,
+            top_names,
+            resolvers,
+            unanswered_queries,
+            nxdomain_storms: self.storms.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::decode_as::DecodeAsTable;
+    use crate::utils::link::{self, LinkType};
+
+    const CLIENT: ([u8; 4], u16) = ([10, 0, 0, 2], 40000);
+    const RESOLVER: ([u8; 4], u16) = ([10, 0, 0, 53], 53);
+
+    // A DNS message for `name`/A, as the question of a query or a response with `rcode`
+    fn message(id: u16, name: &str, response: Option<u8>) -> Vec<u8> {
+        let flags: u16 = match response {
+            Some(rcode) => 0x8180 | rcode as u16,
+            None => 0x0100,
+        };
+        let mut message = Vec::new();
+        for field in [id, flags, 1, 0, 0, 0] {
+            message.extend(field.to_be_bytes());
+        }
+        for label in name.split('.') {
+            message.push(label.len() as u8);
+            message.extend(label.as_bytes());
+        }
+        message.extend([0, 0, 1, 0, 1]);
+        message
+    }
+
+    // A raw IPv4/UDP datagram carrying `payload`
+    fn packet(src: ([u8; 4], u16), dst: ([u8; 4], u16), payload: &[u8]) -> MyPacket {
+        let mut frame = vec![0x45, 0];
+        frame.extend((28 + payload.len() as u16).to_be_bytes());
+        frame.extend([0, 0, 0, 0, 64, 17, 0, 0]);
+        frame.extend(src.0);
+        frame.extend(dst.0);
+        frame.extend(src.1.to_be_bytes());
+        frame.extend(dst.1.to_be_bytes());
+        frame.extend((8 + payload.len() as u16).to_be_bytes());
+        frame.extend([0, 0]);
+        frame.extend(payload);
+        link::decode(LinkType::Raw, &frame, &DecodeAsTable::default()).unwrap()
+    }
+
+    fn query(tracker: &mut DnsTracker, client: ([u8; 4], u16), id: u16, name: &str, ts: i64) {
+        tracker.observe(&packet(client, RESOLVER, &message(id, name, None)), ts / 1_000_000, (ts % 1_000_000) as u32);
+    }
+
+    fn answer(tracker: &mut DnsTracker, client: ([u8; 4], u16), id: u16, name: &str, rcode: u8, ts: i64) {
+        let response = message(id, name, Some(rcode));
+        tracker.observe(&packet(RESOLVER, client, &response), ts / 1_000_000, (ts % 1_000_000) as u32);
+    }
+
+    const START: i64 = 1_700_000_000_000_000;
+
+    #[test]
+    fn pairs_a_query_with_its_response() {
+        let mut tracker = DnsTracker::new();
+        query(&mut tracker, CLIENT, 7, "example.com", START);
+        // Same ID from another port is another query
+        query(&mut tracker, ([10, 0, 0, 2], 40001), 7, "example.org", START);
+        answer(&mut tracker, CLIENT, 7, "example.com", 0, START + 12_500);
+
+        let stats = tracker.report(10);
+        assert_eq!((stats.queries, stats.responses, stats.answered, stats.pending), (2, 1, 1, 1));
+        let times = stats.response_times.unwrap();
+        assert_eq!((times.min_ms, times.max_ms), (12.5, 12.5));
+        assert_eq!(stats.resolvers[0].server, IpAddr::from(RESOLVER.0));
+        assert_eq!((stats.resolvers[0].queries, stats.resolvers[0].responses), (2, 1));
+        assert_eq!(stats.rcodes["NOERROR"], 1);
+    }
+
+    #[test]
+    fn a_retransmission_is_one_query_timed_from_the_first_attempt() {
+        let mut tracker = DnsTracker::new();
+        query(&mut tracker, CLIENT, 9, "slow.example", START);
+        query(&mut tracker, CLIENT, 9, "slow.example", START + 1_000_000);
+        answer(&mut tracker, CLIENT, 9, "slow.example", 0, START + 1_200_000);
+
+        let stats = tracker.report(10);
+        assert_eq!((stats.queries, stats.retransmissions, stats.answered), (1, 1, 1));
+        assert_eq!(stats.response_times.unwrap().max_ms, 1200.0);
+        assert_eq!(stats.top_names[0].queries, 1);
+    }
+
+    #[test]
+    fn unanswered_queries_expire_after_the_timeout() {
+        let mut tracker = DnsTracker::new();
+        query(&mut tracker, CLIENT, 1, "lost.example", START);
+        query(&mut tracker, CLIENT, 2, "later.example", START + RESPONSE_TIMEOUT_US - 1);
+        let stats = tracker.report(10);
+        assert_eq!((stats.unanswered, stats.pending), (0, 2));
+
+        query(&mut tracker, CLIENT, 3, "last.example", START + RESPONSE_TIMEOUT_US + 1);
+        let stats = tracker.report(10);
+        assert_eq!((stats.unanswered, stats.pending), (1, 2));
+        assert_eq!(stats.unanswered_queries[0].name, "lost.example");
+        assert_eq!(stats.unanswered_queries[0].record_type, "A");
+        assert_eq!(stats.resolvers[0].unanswered, 1);
+    }
+
+    #[test]
+    fn a_response_without_a_query_is_unmatched() {
+        let mut tracker = DnsTracker::new();
+        answer(&mut tracker, CLIENT, 5, "before.example", 2, START);
+        let stats = tracker.report(10);
+        assert_eq!((stats.responses, stats.unmatched_responses, stats.answered), (1, 1, 0));
+        assert!(stats.response_times.is_none());
+        assert_eq!(stats.rcodes["SERVFAIL"], 1);
+        // Only answered queries count as failures
+        assert_eq!(stats.failures, 0);
+    }
+
+    #[test]
+    fn unknown_rcodes_are_added_together() {
+        let mut tracker = DnsTracker::new();
+        answer(&mut tracker, CLIENT, 1, "a.example", 11, START);
+        answer(&mut tracker, CLIENT, 2, "b.example", 12, START);
+        answer(&mut tracker, CLIENT, 3, "c.example", 12, START);
+        assert_eq!(tracker.report(10).rcodes["Unknown"], 3);
+    }
+
+    #[test]
+    fn nxdomain_storms_start_at_the_threshold() {
+        let mut tracker = DnsTracker::new();
+        let mut ts = START;
+        for id in 0..STORM_THRESHOLD as u16 {
+            let name = format!("x{}.example", id);
+            query(&mut tracker, CLIENT, id, &name, ts);
+            answer(&mut tracker, CLIENT, id, &name, RCODE_NXDOMAIN, ts + 1_000);
+            if id as usize == STORM_THRESHOLD - 2 {
+                assert!(tracker.report(10).nxdomain_storms.is_empty());
+            }
+            ts += 100_000;
+        }
+        let stats = tracker.report(10);
+        assert_eq!(stats.nxdomain_storms.len(), 1);
+        let storm = &stats.nxdomain_storms[0];
+        assert_eq!((storm.client, storm.count, storm.start), (IpAddr::from(CLIENT.0), STORM_THRESHOLD as u64, START + 1_000));
+
+        // The storm goes on while answers keep coming within the window
+        query(&mut tracker, CLIENT, 500, "y.example", ts);
+        answer(&mut tracker, CLIENT, 500, "y.example", RCODE_NXDOMAIN, ts + 1_000);
+        let stats = tracker.report(10);
+        assert_eq!((stats.nxdomain_storms.len(), stats.nxdomain_storms[0].count), (1, STORM_THRESHOLD as u64 + 1));
+        assert_eq!(stats.top_names.len(), 10);
+        assert_eq!(stats.failures, STORM_THRESHOLD as u64 + 1);
+    }
+}
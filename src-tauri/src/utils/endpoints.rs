@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+
+use super::custom_packet::MyPacket;
+use super::decode_as::Transport;
+use super::link::{format_mac, LinkType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointKind {
+    Mac,
+    Ipv4,
+    Ipv6,
+    // IP:port pairs
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointSort {
+    Packets,
+    #[default]
+    Bytes,
+    TxPackets,
+    TxBytes,
+    RxPackets,
+    RxBytes,
+    Peers,
+}
+
+// One host (or socket) and the traffic it sent and received
+#[derive(Debug, Clone, Serialize)]
+pub struct Endpoint {
+    pub kind: EndpointKind,
+    pub address: String,
+    pub packets: u64,
+    pub bytes: u64,
+    pub tx_packets: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_bytes: u64,
+    // Distinct endpoints of the same kind it exchanged packets with
+    pub peers: usize,
+    pub first_seen: i64,
+    pub last_seen: i64,
+}
+
+#[derive(Debug)]
+struct Counters {
+    tx_packets: u64,
+    tx_bytes: u64,
+    rx_packets: u64,
+    rx_bytes: u64,
+    peers: HashSet<String>,
+    first_seen: i64,
+    last_seen: i64,
+}
+
+impl Counters {
+    fn new(ts_sec: i64) -> Counters {
+        Counters {
+            tx_packets: 0,
+            tx_bytes: 0,
+            rx_packets: 0,
+            rx_bytes: 0,
+            peers: HashSet::new(),
+            first_seen: ts_sec,
+            last_seen: ts_sec,
+        }
+    }
+
+    fn seen(&mut self, ts_sec: i64) {
+        self.first_seen = self.first_seen.min(ts_sec);
+        self.last_seen = self.last_seen.max(ts_sec);
+    }
+}
+
+fn socket(ip: IpAddr, port: u16) -> String {
+    match ip {
+        IpAddr::V4(ip) => format!("{}:{}", ip, port),
+        IpAddr::V6(ip) => format!("[{}]:{}", ip, port),
+    }
+}
+
+// Traffic per MAC, IPv4, IPv6 and TCP/UDP endpoint
+#[derive(Debug, Default)]
+pub struct EndpointTable {
+    endpoints: HashMap<(EndpointKind, String), Counters>,
+}
+
+impl EndpointTable {
+    pub fn new() -> EndpointTable {
+        EndpointTable::default()
+    }
+
+    // `frame` is the raw frame, Ethernet unless `packet` says otherwise; `packet` is its
+    // dissection, None when only the link layer could be read. `len` is the length on the wire
+    pub fn observe(&mut self, frame: &[u8], packet: Option<&MyPacket>, ts_sec: i64, len: u32) {
+        let ethernet = packet.map(|packet| packet.link_type() == LinkType::Ethernet).unwrap_or(true);
+        if ethernet && frame.len() >= 14 {
+            self.record(EndpointKind::Mac, format_mac(&frame[6..12]), format_mac(&frame[0..6]), ts_sec, len);
+        }
+        let Some(packet) = packet else {
+            return;
+        };
+
+        let (src, dst) = packet.addresses();
+        let kind = if src.is_ipv4() { EndpointKind::Ipv4 } else { EndpointKind::Ipv6 };
+        self.record(kind, src.to_string(), dst.to_string(), ts_sec, len);
+        let kind = match packet.transport() {
+            Some(Transport::TCP) => EndpointKind::Tcp,
+            Some(Transport::UDP) => EndpointKind::Udp,
+            None => return,
+        };
+        if let Some((src_port, dst_port)) = packet.ports() {
+            self.record(kind, socket(src, src_port), socket(dst, dst_port), ts_sec, len);
+        }
+    }
+
+    fn record(&mut self, kind: EndpointKind, src: String, dst: String, ts_sec: i64, len: u32) {
+        let sender = self.endpoints.entry((kind, src.clone())).or_insert_with(|| Counters::new(ts_sec));
+        sender.tx_packets += 1;
+        sender.tx_bytes += len as u64;
+        sender.seen(ts_sec);
+        sender.peers.insert(dst.clone());
+
+        let receiver = self.endpoints.entry((kind, dst)).or_insert_with(|| Counters::new(ts_sec));
+        receiver.rx_packets += 1;
+        receiver.rx_bytes += len as u64;
+        receiver.seen(ts_sec);
+        receiver.peers.insert(src);
+    }
+
+    // Endpoints of one kind, largest first by `sort`, at most `limit` of them
+    pub fn list(&self, kind: EndpointKind, sort: EndpointSort, limit: Option<usize>) -> Vec<Endpoint> {
+        let mut endpoints: Vec<Endpoint> = self
+            .endpoints
+            .iter()
+            .filter(|((endpoint_kind, _), _)| *endpoint_kind == kind)
+            .map(|((kind, address), counters)| Endpoint {
+                kind: *kind,
+                address: address.clone(),
+                packets: counters.tx_packets + counters.rx_packets,
+                bytes: counters.tx_bytes + counters.rx_bytes,
+                tx_packets: counters.tx_packets,
+                tx_bytes: counters.tx_bytes,
+                rx_packets: counters.rx_packets,
+                rx_bytes: counters.rx_bytes,
+                peers: counters.peers.len(),
+                first_seen: counters.first_seen,
+                last_seen: counters.last_seen,
+            })
+            .collect();
+
+        let key = |endpoint: &Endpoint| match sort {
+            EndpointSort::Packets => endpoint.packets,
+            EndpointSort::Bytes => endpoint.bytes,
+            EndpointSort::TxPackets => endpoint.tx_packets,
+            EndpointSort::TxBytes => endpoint.tx_bytes,
+            EndpointSort::RxPackets => endpoint.rx_packets,
+            EndpointSort::RxBytes => endpoint.rx_bytes,
+            EndpointSort::Peers => endpoint.peers as u64,
+        };
+        endpoints.sort_by(|a, b| key(b).cmp(&key(a)).then_with(|| a.address.cmp(&b.address)));
+        if let Some(limit) = limit {
+            endpoints.truncate(limit);
+        }
+        endpoints
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::decode_as::DecodeAsTable;
+    use crate::utils::link;
+
+    const MAC_A: [u8; 6] = [0x02, 0, 0, 0, 0, 0x0a];
+    const MAC_B: [u8; 6] = [0x02, 0, 0, 0, 0, 0x0b];
+
+    // An Ethernet frame carrying IPv4/UDP with `payload_len` bytes of payload
+    fn frame(macs: ([u8; 6], [u8; 6]), src: ([u8; 4], u16), dst: ([u8; 4], u16), payload_len: usize) -> Vec<u8> {
+        let mut frame = [macs.1, macs.0].concat();
+        frame.extend_from_slice(&[0x08, 0x00]);
+        frame.extend_from_slice(&[0x45, 0, 0, 0, 0, 0, 0x40, 0, 64, 17, 0, 0]);
+        frame[16..18].copy_from_slice(&((28 + payload_len) as u16).to_be_bytes());
+        frame.extend_from_slice(&src.0);
+        frame.extend_from_slice(&dst.0);
+        frame.extend_from_slice(&src.1.to_be_bytes());
+        frame.extend_from_slice(&dst.1.to_be_bytes());
+        frame.extend_from_slice(&((8 + payload_len) as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.resize(frame.len() + payload_len, 0);
+        frame
+    }
+
+    fn observe(table: &mut EndpointTable, frame: &[u8], ts_sec: i64) {
+        let packet = link::decode(LinkType::Ethernet, frame, &DecodeAsTable::default()).unwrap();
+        table.observe(frame, Some(&packet), ts_sec, frame.len() as u32);
+    }
+
+    #[test]
+    fn lists_endpoints_by_kind_sorted_and_cut() {
+        let client = ([10, 0, 0, 1], 5000);
+        let mut table = EndpointTable::new();
+        // 10.0.0.1 uploads to 10.0.0.2, and sends one small packet to 10.0.0.3
+        for ts_sec in 1..=3 {
+            observe(&mut table, &frame((MAC_A, MAC_B), client, ([10, 0, 0, 2], 6000), 958), ts_sec);
+        }
+        observe(&mut table, &frame((MAC_B, MAC_A), ([10, 0, 0, 2], 6000), client, 58), 4);
+        observe(&mut table, &frame((MAC_A, MAC_B), client, ([10, 0, 0, 3], 7000), 58), 5);
+
+        let ips = table.list(EndpointKind::Ipv4, EndpointSort::Bytes, None);
+        let addresses: Vec<&str> = ips.iter().map(|endpoint| endpoint.address.as_str()).collect();
+        assert_eq!(addresses, ["10.0.0.1", "10.0.0.2", "10.0.0.3"]);
+        let client_ip = &ips[0];
+        assert_eq!((client_ip.tx_packets, client_ip.rx_packets, client_ip.packets), (4, 1, 5));
+        assert_eq!((client_ip.tx_bytes, client_ip.rx_bytes), (3 * 1000 + 100, 100));
+        assert_eq!((client_ip.peers, client_ip.first_seen, client_ip.last_seen), (2, 1, 5));
+
+        // Receivers first, then the top one only
+        let rx = table.list(EndpointKind::Ipv4, EndpointSort::RxPackets, None);
+        assert_eq!((rx[0].address.as_str(), rx[0].rx_packets), ("10.0.0.2", 3));
+        let top = table.list(EndpointKind::Ipv4, EndpointSort::Peers, Some(1));
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].address, "10.0.0.1");
+
+        // MACs come from the Ethernet header, sockets are ip:port
+        let macs = table.list(EndpointKind::Mac, EndpointSort::Packets, None);
+        let addresses: Vec<(&str, u64)> = macs.iter().map(|endpoint| (endpoint.address.as_str(), endpoint.packets)).collect();
+        assert_eq!(addresses, [("02:00:00:00:00:0a", 5), ("02:00:00:00:00:0b", 5)]);
+        let sockets = table.list(EndpointKind::Udp, EndpointSort::TxBytes, Some(2));
+        let addresses: Vec<&str> = sockets.iter().map(|endpoint| endpoint.address.as_str()).collect();
+        assert_eq!(addresses, ["10.0.0.1:5000", "10.0.0.2:6000"]);
+        assert!(table.list(EndpointKind::Tcp, EndpointSort::Bytes, None).is_empty());
+    }
+}
@@ -0,0 +1,226 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::field_tree::PacketRecord;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BatchConfig {
+    // A batch is sent once it holds this many packets or `interval` has passed
+    pub max_packets: usize,
+    pub interval: Duration,
+    // Batches the UI may have outstanding before new packets are only counted
+    pub max_in_flight: u64,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig {
+            max_packets: 256,
+            interval: Duration::from_millis(100),
+            max_in_flight: 4,
+        }
+    }
+}
+
+// One `packets-captured` event. `skipped` packets were not sent because the UI was
+// behind, they are still in the packet store and can be fetched with get_packets
+#[derive(Debug, Clone, Serialize)]
+pub struct PacketBatch {
+    pub batch_id: u64,
+    pub packets: Vec<PacketRecord>,
+    pub coalesced: usize,
+    pub skipped: u64,
+}
+
+// Highest batch IDs sent and acknowledged, shared between the batcher and ack_packets. IDs
+// keep increasing across captures, so a late ack for an earlier capture's batch can never be
+// taken for one of the current capture's
+#[derive(Debug, Default)]
+pub struct BatchAcks {
+    sent: AtomicU64,
+    acked: AtomicU64,
+}
+
+impl BatchAcks {
+    pub fn ack(&self, batch_id: u64) {
+        // Never past what was sent, whatever the UI claims
+        self.acked.fetch_max(batch_id.min(self.sent.load(Ordering::SeqCst)), Ordering::SeqCst);
+    }
+
+    fn in_flight(&self) -> u64 {
+        self.sent.load(Ordering::SeqCst).saturating_sub(self.acked.load(Ordering::SeqCst))
+    }
+}
+
+pub struct EventBatcher {
+    config: BatchConfig,
+    pending: Vec<PacketRecord>,
+    skipped: u64,
+    last_flush: Instant,
+    next_batch_id: u64,
+    acks: Arc<BatchAcks>,
+}
+
+impl EventBatcher {
+    pub fn new(config: BatchConfig, acks: Arc<BatchAcks>) -> EventBatcher {
+        // Batches an earlier capture left unacknowledged do not hold this one back
+        let sent = acks.sent.load(Ordering::SeqCst);
+        acks.acked.fetch_max(sent, Ordering::SeqCst);
+        EventBatcher {
+            config,
+            pending: Vec::with_capacity(config.max_packets),
+            skipped: 0,
+            last_flush: Instant::now(),
+            next_batch_id: sent + 1,
+            acks,
+        }
+    }
+
+    fn ui_behind(&self) -> bool {
+        self.acks.in_flight() >= self.config.max_in_flight
+    }
+
+    pub fn push(&mut self, record: PacketRecord) -> Option<PacketBatch> {
+        if self.ui_behind() {
+            self.skipped += 1;
+            return None;
+        }
+        self.pending.push(record);
+        if self.pending.len() >= self.config.max_packets {
+            return self.flush();
+        }
+        self.tick()
+    }
+
+    // Packets that never reached the batcher (e.g. a full queue) still get reported
+    pub fn add_skipped(&mut self, count: u64) {
+        self.skipped += count;
+    }
+
+    // Sends whatever is pending once the interval has passed
+    pub fn tick(&mut self) -> Option<PacketBatch> {
+        if self.last_flush.elapsed() < self.config.interval || self.ui_behind() {
+            return None;
+        }
+        self.flush()
+    }
+
+    // Time until the next tick is due, for callers waiting on new packets
+    pub fn time_to_tick(&self) -> Duration {
+        if self.ui_behind() {
+            return self.config.interval;
+        }
+        self.config.interval.saturating_sub(self.last_flush.elapsed())
+    }
+
+    pub fn flush(&mut self) -> Option<PacketBatch> {
+        self.last_flush = Instant::now();
+        if self.pending.is_empty() && self.skipped == 0 {
+            return None;
+        }
+
+        let packets = std::mem::replace(&mut self.pending, Vec::with_capacity(self.config.max_packets));
+        let batch = PacketBatch {
+            batch_id: self.next_batch_id,
+            coalesced: packets.len(),
+            packets,
+            skipped: std::mem::take(&mut self.skipped),
+        };
+        self.acks.sent.store(self.next_batch_id, Ordering::SeqCst);
+        self.next_batch_id += 1;
+        Some(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn record(frame_number: u64) -> PacketRecord {
+        PacketRecord {
+            frame_number: Some(frame_number),
+            info: HashMap::new(),
+            fields: Vec::new(),
+        }
+    }
+
+    fn config(max_packets: usize, interval: Duration) -> BatchConfig {
+        BatchConfig {
+            max_packets,
+            interval,
+            max_in_flight: 2,
+        }
+    }
+
+    #[test]
+    fn full_batches_are_sent_at_once() {
+        let mut batcher = EventBatcher::new(config(3, Duration::from_secs(3600)), Arc::default());
+        assert!(batcher.push(record(1)).is_none());
+        assert!(batcher.push(record(2)).is_none());
+        let batch = batcher.push(record(3)).unwrap();
+        assert_eq!(batch.batch_id, 1);
+        assert_eq!(batch.coalesced, 3);
+        assert_eq!(batch.packets.iter().map(|p| p.frame_number).collect::<Vec<_>>(), [Some(1), Some(2), Some(3)]);
+        assert!(batcher.tick().is_none());
+    }
+
+    #[test]
+    fn partial_batches_wait_for_the_interval() {
+        let interval = Duration::from_millis(20);
+        let mut batcher = EventBatcher::new(config(100, interval), Arc::default());
+        assert!(batcher.push(record(1)).is_none());
+        assert!(batcher.tick().is_none());
+        assert!(batcher.time_to_tick() <= interval);
+
+        std::thread::sleep(interval);
+        let batch = batcher.tick().unwrap();
+        assert_eq!(batch.coalesced, 1);
+        // Nothing pending, nothing to send
+        std::thread::sleep(interval);
+        assert!(batcher.tick().is_none());
+    }
+
+    #[test]
+    fn packets_are_skipped_while_the_ui_is_behind() {
+        let acks = Arc::new(BatchAcks::default());
+        let mut batcher = EventBatcher::new(config(1, Duration::ZERO), acks.clone());
+        assert_eq!(batcher.push(record(1)).unwrap().batch_id, 1);
+        assert_eq!(batcher.push(record(2)).unwrap().batch_id, 2);
+
+        // Two batches outstanding, the rest are only counted
+        assert!(batcher.push(record(3)).is_none());
+        batcher.add_skipped(4);
+        assert!(batcher.tick().is_none());
+
+        acks.ack(1);
+        let batch = batcher.tick().unwrap();
+        assert_eq!(batch.batch_id, 3);
+        assert_eq!(batch.coalesced, 0);
+        assert_eq!(batch.skipped, 5);
+    }
+
+    #[test]
+    fn late_acks_from_an_earlier_capture_are_ignored() {
+        let acks = Arc::new(BatchAcks::default());
+        let mut first = EventBatcher::new(config(1, Duration::ZERO), acks.clone());
+        for frame in 1..=2 {
+            first.push(record(frame)).unwrap();
+        }
+
+        // The UI never acknowledged the first capture's batches, they do not hold the next one back
+        let mut second = EventBatcher::new(config(1, Duration::ZERO), acks.clone());
+        assert_eq!(second.push(record(1)).unwrap().batch_id, 3);
+        assert_eq!(second.push(record(2)).unwrap().batch_id, 4);
+        acks.ack(2);
+        assert!(second.push(record(3)).is_none());
+
+        // Nor can an ack run ahead of what was sent
+        acks.ack(100);
+        assert_eq!(second.push(record(4)).unwrap().batch_id, 5);
+        assert_eq!(second.push(record(5)).unwrap().batch_id, 6);
+        assert!(second.push(record(6)).is_none());
+    }
+}
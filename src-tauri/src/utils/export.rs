@@ -0,0 +1,311 @@
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind, Write};
+
+use super::field_tree::PacketRecord;
+use super::packet_store::{format_timestamp, StoredPacket};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    // One JSON array holding every packet record
+    Json,
+    // One packet record per line
+    Ndjson,
+    // One row per packet, one column per selected field
+    Csv,
+}
+
+// Spreadsheets evaluate cells starting with these as formulas. DNS names, HTTP hosts and the
+// like come straight off the network, so such cells get a leading `'` and stay text
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+// Used when the caller does not pick CSV columns. `a|b` takes whichever field is present
+pub const DEFAULT_COLUMNS: &[&str] = &[
+    "frame.number",
+    "frame.time",
+    "ip.src|ipv6.src",
+    "ip.dst|ipv6.dst",
+    "frame.protocol",
+    "frame.len",
+];
+
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    // CSV only: field names from the field tree ("ip.src", "dns.qry.name", ...) or frame.* columns
+    pub columns: Vec<String>,
+    // JSON only: keep the base64 frame bytes in "raw_data"
+    pub include_raw: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> ExportOptions {
+        ExportOptions {
+            format: ExportFormat::Json,
+            columns: DEFAULT_COLUMNS.iter().map(|column| column.to_string()).collect(),
+            include_raw: false,
+        }
+    }
+}
+
+// Streams packet records to `out` in the chosen format, so exports never hold the whole
+// capture in memory. Call finish to close the JSON array and flush
+pub struct Exporter<W: Write> {
+    out: W,
+    options: ExportOptions,
+    written: u64,
+}
+
+impl<W: Write> Exporter<W> {
+    pub fn new(mut out: W, options: ExportOptions) -> Result<Exporter<W>, Error> {
+        match options.format {
+            ExportFormat::Json => out.write_all(b"[")?,
+            ExportFormat::Ndjson => {}
+            ExportFormat::Csv => {
+                if options.columns.is_empty() {
+                    return Err(Error::new(ErrorKind::InvalidInput, "No CSV columns selected"));
+                }
+                write_csv_row(&mut out, options.columns.iter().map(|column| column.as_str()))?;
+            }
+        }
+        Ok(Exporter { out, options, written: 0 })
+    }
+
+    pub fn write(&mut self, packet: &StoredPacket, mut record: PacketRecord) -> Result<(), Error> {
+        match self.options.format {
+            ExportFormat::Json | ExportFormat::Ndjson => {
+                if !self.options.include_raw {
+                    record.info.remove("raw_data");
+                }
+                if self.options.format == ExportFormat::Json {
+                    self.out.write_all(if self.written == 0 { b"\n" } else { b",\n" })?;
+                }
+                serde_json::to_writer(&mut self.out, &record)?;
+                if self.options.format == ExportFormat::Ndjson {
+                    self.out.write_all(b"\n")?;
+                }
+            }
+            ExportFormat::Csv => {
+                let row: Vec<String> = self
+                    .options
+                    .columns
+                    .iter()
+                    .map(|column| column_value(packet, &record, column))
+                    .collect();
+                write_csv_row(&mut self.out, row.iter().map(|value| value.as_str()))?;
+            }
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    // Number of packets written, after closing the output
+    pub fn finish(mut self) -> Result<u64, Error> {
+        if self.options.format == ExportFormat::Json {
+            self.out.write_all(if self.written == 0 { b"]\n" } else { b"\n]\n" })?;
+        }
+        self.out.flush()?;
+        Ok(self.written)
+    }
+}
+
+// The value of one CSV column. Fields that repeat (DNS answers, DHCP options, ...) are joined with ";"
+fn column_value(packet: &StoredPacket, record: &PacketRecord, column: &str) -> String {
+    for name in column.split('|').map(str::trim) {
+        let value = match name {
+            "frame.number" => Some(packet.frame_number.to_string()),
+            "frame.time" => Some(format_timestamp(packet.ts_sec, packet.ts_usec)),
+            "frame.time_epoch" => Some(format!("{}.{:06}", packet.ts_sec, packet.ts_usec)),
+            "frame.len" => Some(packet.len.to_string()),
+            "frame.cap_len" => Some(packet.data.len().to_string()),
+            // The innermost decoded layer names the packet
+            "frame.protocol" => record.fields.last().map(|root| root.display_name.clone()),
+            _ => {
+                let values: Vec<&str> = record
+                    .fields
+                    .iter()
+                    .flat_map(|root| root.iter())
+                    .filter(|field| field.name == name && field.children.iter().all(|child| child.name != name))
+                    .map(|field| field.display.as_str())
+                    .collect();
+                if values.is_empty() {
+                    None
+                } else {
+                    Some(values.join(";"))
+                }
+            }
+        };
+        if let Some(value) = value {
+            return value;
+        }
+    }
+    String::new()
+}
+
+// RFC 4180: quote values holding separators, quotes or line breaks
+fn write_csv_row<'a>(out: &mut impl Write, values: impl Iterator<Item = &'a str>) -> Result<(), Error> {
+    let mut first = true;
+    for value in values {
+        if !first {
+            out.write_all(b",")?;
+        }
+        first = false;
+        let escaped;
+        let value = if value.starts_with(FORMULA_PREFIXES) {
+            escaped = format!("'{}", value);
+            escaped.as_str()
+        } else {
+            value
+        };
+        if value.contains([',', '"', '\n', '\r']) {
+            write!(out, "\"{}\"", value.replace('"', "\"\""))?;
+        } else {
+            out.write_all(value.as_bytes())?;
+        }
+    }
+    out.write_all(b"\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::field_tree::{Field, FieldValue};
+    use std::collections::HashMap;
+
+    fn packet(frame_number: u64, query: &str) -> (StoredPacket, PacketRecord) {
+        let packet = StoredPacket { frame_number, ts_sec: 1_700_000_000, ts_usec: 250_000, len: 74, data: vec![0; 60] };
+        let record = PacketRecord {
+            frame_number: Some(frame_number),
+            info: HashMap::from([(
+                "raw_data".to_string(),
+                HashMap::from([("data".to_string(), "AAAA".to_string())]),
+            )]),
+            fields: vec![Field::protocol("dns", "DNS").with_children(vec![Field::new(
+                "dns.qry.name",
+                "Name",
+                FieldValue::Str(query.to_string()),
+            )])],
+        };
+        (packet, record)
+    }
+
+    fn export(options: ExportOptions, queries: &[&str]) -> String {
+        let mut out = Vec::new();
+        let mut exporter = Exporter::new(&mut out, options).unwrap();
+        for (index, query) in queries.iter().enumerate() {
+            let (packet, record) = packet(index as u64 + 1, query);
+            exporter.write(&packet, record).unwrap();
+        }
+        assert_eq!(exporter.finish().unwrap(), queries.len() as u64);
+        String::from_utf8(out).unwrap()
+    }
+
+    fn csv(columns: &[&str]) -> ExportOptions {
+        ExportOptions {
+            format: ExportFormat::Csv,
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            include_raw: false,
+        }
+    }
+
+    // RFC 4180 reader, just enough to read back what write_csv_row writes
+    fn parse_csv(text: &str) -> Vec<Vec<String>> {
+        let mut rows = Vec::new();
+        let mut row = Vec::new();
+        let mut cell = String::new();
+        let mut quoted = false;
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (quoted, c) {
+                (true, '"') if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    cell.push('"');
+                }
+                (true, '"') => quoted = false,
+                (true, c) => cell.push(c),
+                (false, '"') => quoted = true,
+                (false, ',') => row.push(std::mem::take(&mut cell)),
+                (false, '\r') => {}
+                (false, '\n') => {
+                    row.push(std::mem::take(&mut cell));
+                    rows.push(std::mem::take(&mut row));
+                }
+                (false, c) => cell.push(c),
+            }
+        }
+        rows
+    }
+
+    #[test]
+    fn csv_quoting_round_trips() {
+        let queries = ["plain.example", "a,b", "say \"hi\"", "two\nlines", "cr\r\nlf", ""];
+        let rows = parse_csv(&export(csv(&["frame.number", "dns.qry.name"]), &queries));
+        assert_eq!(rows[0], ["frame.number", "dns.qry.name"]);
+        assert_eq!(rows.len(), queries.len() + 1);
+        for (index, query) in queries.iter().enumerate() {
+            assert_eq!(rows[index + 1], [(index + 1).to_string(), query.to_string()]);
+        }
+    }
+
+    #[test]
+    fn csv_rows_end_with_crlf_and_missing_fields_are_empty() {
+        let text = export(csv(&["frame.number", "ip.src|ipv6.src", "frame.len"]), &["a.example"]);
+        assert_eq!(text, "frame.number,ip.src|ipv6.src,frame.len\r\n1,,74\r\n");
+    }
+
+    #[test]
+    fn csv_formula_cells_are_escaped() {
+        let queries = ["=HYPERLINK(\"http://evil\")", "+1", "-2+3", "@SUM(A1)", "\tcmd", "=1,2", "a=b"];
+        let rows = parse_csv(&export(csv(&["dns.qry.name"]), &queries));
+        let cells: Vec<&str> = rows[1..].iter().map(|row| row[0].as_str()).collect();
+        assert_eq!(
+            cells,
+            ["'=HYPERLINK(\"http://evil\")", "'+1", "'-2+3", "'@SUM(A1)", "'\tcmd", "'=1,2", "a=b"]
+        );
+    }
+
+    #[test]
+    fn csv_needs_columns() {
+        assert!(Exporter::new(Vec::new(), csv(&[])).is_err());
+    }
+
+    fn json(format: ExportFormat, include_raw: bool) -> ExportOptions {
+        ExportOptions { format, include_raw, ..ExportOptions::default() }
+    }
+
+    #[test]
+    fn json_array_framing() {
+        for count in [0, 1, 3] {
+            let queries = vec!["example.com"; count];
+            let text = export(json(ExportFormat::Json, false), &queries);
+            let records: Vec<serde_json::Value> = serde_json::from_str(&text).unwrap();
+            assert_eq!(records.len(), count);
+            for (index, record) in records.iter().enumerate() {
+                assert_eq!(record["frame_number"], index as u64 + 1);
+                assert!(record.get("raw_data").is_none());
+            }
+            assert!(text.ends_with("]\n"));
+        }
+        assert_eq!(export(json(ExportFormat::Json, false), &[]), "[]\n");
+    }
+
+    #[test]
+    fn json_keeps_raw_data_when_asked() {
+        let text = export(json(ExportFormat::Json, true), &["example.com"]);
+        let records: Vec<PacketRecord> = serde_json::from_str(&text).unwrap();
+        assert_eq!(records[0].info["raw_data"]["data"], "AAAA");
+        assert_eq!(records[0].fields[0].children[0].display, "example.com");
+    }
+
+    #[test]
+    fn ndjson_is_one_record_per_line() {
+        assert_eq!(export(json(ExportFormat::Ndjson, false), &[]), "");
+        let text = export(json(ExportFormat::Ndjson, false), &["a.example", "b.example"]);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for (index, line) in lines.iter().enumerate() {
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(record["frame_number"], index as u64 + 1);
+        }
+    }
+}
@@ -0,0 +1,580 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufWriter, Error, ErrorKind, Write};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use super::flow::{FlowKey, FlowSample, FlowStats, FlowTable};
+
+const NETFLOW_V9_VERSION: u16 = 9;
+const IPFIX_VERSION: u16 = 10;
+const NETFLOW_V9_TEMPLATE_SET: u16 = 0;
+const IPFIX_TEMPLATE_SET: u16 = 2;
+const IPV4_TEMPLATE_ID: u16 = 256;
+const IPV6_TEMPLATE_ID: u16 = 257;
+// Keeps every message below a 1500 byte MTU with both templates attached
+const MAX_RECORDS_PER_MESSAGE: usize = 16;
+// UDP collectors that start late only learn the templates when they are resent
+const TEMPLATE_REFRESH_MESSAGES: u32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlowExportFormat {
+    NetflowV9,
+    Ipfix,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlowExportConfig {
+    pub enabled: bool,
+    pub format: FlowExportFormat,
+    // "host:port" of a collector to send to over UDP
+    pub collector: Option<String>,
+    // File the messages are also appended to, as a plain stream of export packets
+    pub file: Option<PathBuf>,
+    // A flow is exported once it has been quiet this long...
+    pub idle_timeout_secs: u64,
+    // ...or, for long-lived flows, every this often
+    pub active_timeout_secs: u64,
+    // Source ID (NetFlow v9) or observation domain (IPFIX)
+    pub observation_domain: u32,
+}
+
+impl Default for FlowExportConfig {
+    fn default() -> Self {
+        FlowExportConfig {
+            enabled: false,
+            format: FlowExportFormat::Ipfix,
+            collector: Some("127.0.0.1:4739".to_string()),
+            file: None,
+            idle_timeout_secs: 15,
+            active_timeout_secs: 60,
+            observation_domain: 0,
+        }
+    }
+}
+
+impl FlowExportConfig {
+    pub fn load(path: &Path) -> Result<FlowExportConfig, String> {
+        if !path.exists() {
+            return Ok(FlowExportConfig::default());
+        }
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read flow export config: {}", e))?;
+        serde_json::from_str(&contents)
+            .map_err(|e| format!("Invalid flow export config: {}", e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize flow export config: {}", e))?;
+        fs::write(path, contents).map_err(|e| format!("Failed to write flow export config: {}", e))
+    }
+}
+
+// One direction of a flow, the unit NetFlow and IPFIX records describe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlowRecord {
+    pub src: IpAddr,
+    pub dst: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub protocol: u8,
+    pub tcp_flags: u8,
+    pub packets: u64,
+    pub bytes: u64,
+    // Unix time in milliseconds
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+impl FlowRecord {
+    // A bidirectional flow becomes up to two records, one per direction that saw traffic
+    pub fn from_flow(key: &FlowKey, stats: &FlowStats) -> Vec<FlowRecord> {
+        let start_ms = unix_ms(stats.first_seen);
+        let end_ms = unix_ms(stats.last_seen);
+        let mut records = Vec::with_capacity(2);
+        if stats.packets_a_to_b > 0 {
+            records.push(FlowRecord {
+                src: key.a_ip,
+                dst: key.b_ip,
+                src_port: key.a_port,
+                dst_port: key.b_port,
+                protocol: key.protocol_number(),
+                tcp_flags: stats.tcp_flags_a_to_b,
+                packets: stats.packets_a_to_b,
+                bytes: stats.bytes_a_to_b,
+                start_ms,
+                end_ms,
+            });
+        }
+        if stats.packets_b_to_a > 0 {
+            records.push(FlowRecord {
+                src: key.b_ip,
+                dst: key.a_ip,
+                src_port: key.b_port,
+                dst_port: key.a_port,
+                protocol: key.protocol_number(),
+                tcp_flags: stats.tcp_flags_b_to_a,
+                packets: stats.packets_b_to_a,
+                bytes: stats.bytes_b_to_a,
+                start_ms,
+                end_ms,
+            });
+        }
+        records
+    }
+}
+
+fn unix_ms((sec, usec): (i64, u32)) -> u64 {
+    (sec.max(0) as u64) * 1000 + (usec / 1000) as u64
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// (information element / field type, length) pairs, IPv4 addresses first then IPv6
+fn template_fields(format: FlowExportFormat, ipv6: bool) -> Vec<(u16, u16)> {
+    let mut fields = if ipv6 { vec![(27, 16), (28, 16)] } else { vec![(8, 4), (12, 4)] };
+    // Ports, protocol, TCP flags, packets, bytes
+    fields.extend([(7, 2), (11, 2), (4, 1)]);
+    match format {
+        // FIRST_SWITCHED/LAST_SWITCHED are milliseconds of exporter uptime
+        FlowExportFormat::NetflowV9 => fields.extend([(6, 1), (2, 8), (1, 8), (22, 4), (21, 4)]),
+        // flowStartMilliseconds/flowEndMilliseconds are absolute
+        FlowExportFormat::Ipfix => fields.extend([(6, 2), (2, 8), (1, 8), (152, 8), (153, 8)]),
+    }
+    fields
+}
+
+// Builds NetFlow v9 or IPFIX export packets, keeping the sequence numbers collectors use
+// to spot loss
+pub struct FlowEncoder {
+    format: FlowExportFormat,
+    observation_domain: u32,
+    // Unix ms the exporter started, NetFlow v9 timestamps are relative to it
+    boot_ms: u64,
+    // Export packets sent (NetFlow v9) or data records sent (IPFIX)
+    sequence: u32,
+    // Messages sent since the templates were last included
+    since_templates: u32,
+}
+
+impl FlowEncoder {
+    pub fn new(format: FlowExportFormat, observation_domain: u32) -> FlowEncoder {
+        FlowEncoder {
+            format,
+            observation_domain,
+            boot_ms: now_ms(),
+            sequence: 0,
+            since_templates: 0,
+        }
+    }
+
+    // Splits `records` into as many export packets as needed
+    pub fn encode(&mut self, records: &[FlowRecord]) -> Vec<Vec<u8>> {
+        records.chunks(MAX_RECORDS_PER_MESSAGE).map(|chunk| self.message(chunk, now_ms())).collect()
+    }
+
+    fn message(&mut self, records: &[FlowRecord], now_ms: u64) -> Vec<u8> {
+        let mut body = Vec::new();
+        let mut record_count = 0u16;
+
+        if self.since_templates == 0 {
+            let set_id = match self.format {
+                FlowExportFormat::NetflowV9 => NETFLOW_V9_TEMPLATE_SET,
+                FlowExportFormat::Ipfix => IPFIX_TEMPLATE_SET,
+            };
+            let mut templates = Vec::new();
+            for (template_id, ipv6) in [(IPV4_TEMPLATE_ID, false), (IPV6_TEMPLATE_ID, true)] {
+                let fields = template_fields(self.format, ipv6);
+                templates.extend(template_id.to_be_bytes());
+                templates.extend((fields.len() as u16).to_be_bytes());
+                for (field_type, length) in fields {
+                    templates.extend(field_type.to_be_bytes());
+                    templates.extend(length.to_be_bytes());
+                }
+                record_count += 1;
+            }
+            push_set(&mut body, set_id, &templates);
+        }
+
+        for (template_id, ipv6) in [(IPV4_TEMPLATE_ID, false), (IPV6_TEMPLATE_ID, true)] {
+            let mut data = Vec::new();
+            for record in records.iter().filter(|record| record.src.is_ipv6() == ipv6) {
+                self.push_record(&mut data, record);
+                record_count += 1;
+            }
+            if !data.is_empty() {
+                push_set(&mut body, template_id, &data);
+            }
+        }
+        self.since_templates = (self.since_templates + 1) % TEMPLATE_REFRESH_MESSAGES;
+
+        let mut message = Vec::with_capacity(body.len() + 20);
+        let export_secs = (now_ms / 1000) as u32;
+        match self.format {
+            FlowExportFormat::NetflowV9 => {
+                message.extend(NETFLOW_V9_VERSION.to_be_bytes());
+                message.extend(record_count.to_be_bytes());
+                message.extend((now_ms.saturating_sub(self.boot_ms) as u32).to_be_bytes());
+                message.extend(export_secs.to_be_bytes());
+                message.extend(self.sequence.to_be_bytes());
+                message.extend(self.observation_domain.to_be_bytes());
+                self.sequence = self.sequence.wrapping_add(1);
+            }
+            FlowExportFormat::Ipfix => {
+                message.extend(IPFIX_VERSION.to_be_bytes());
+                message.extend(((body.len() + 16) as u16).to_be_bytes());
+                message.extend(export_secs.to_be_bytes());
+                message.extend(self.sequence.to_be_bytes());
+                message.extend(self.observation_domain.to_be_bytes());
+                self.sequence = self.sequence.wrapping_add(records.len() as u32);
+            }
+        }
+        message.extend(body);
+        message
+    }
+
+    fn push_record(&self, data: &mut Vec<u8>, record: &FlowRecord) {
+        match (record.src, record.dst) {
+            (IpAddr::V4(src), IpAddr::V4(dst)) => {
+                data.extend(src.octets());
+                data.extend(dst.octets());
+            }
+            (src, dst) => {
+                data.extend(ipv6_octets(src));
+                data.extend(ipv6_octets(dst));
+            }
+        }
+        data.extend(record.src_port.to_be_bytes());
+        data.extend(record.dst_port.to_be_bytes());
+        data.push(record.protocol);
+        match self.format {
+            FlowExportFormat::NetflowV9 => {
+                data.push(record.tcp_flags);
+                data.extend(record.packets.to_be_bytes());
+                data.extend(record.bytes.to_be_bytes());
+                data.extend((record.start_ms.saturating_sub(self.boot_ms) as u32).to_be_bytes());
+                data.extend((record.end_ms.saturating_sub(self.boot_ms) as u32).to_be_bytes());
+            }
+            FlowExportFormat::Ipfix => {
+                data.extend((record.tcp_flags as u16).to_be_bytes());
+                data.extend(record.packets.to_be_bytes());
+                data.extend(record.bytes.to_be_bytes());
+                data.extend(record.start_ms.to_be_bytes());
+                data.extend(record.end_ms.to_be_bytes());
+            }
+        }
+    }
+}
+
+fn ipv6_octets(address: IpAddr) -> [u8; 16] {
+    match address {
+        IpAddr::V4(v4) => v4.to_ipv6_mapped().octets(),
+        IpAddr::V6(v6) => v6.octets(),
+    }
+}
+
+// A set/FlowSet: ID, length, contents padded to four bytes
+fn push_set(body: &mut Vec<u8>, set_id: u16, contents: &[u8]) {
+    let padding = (4 - contents.len() % 4) % 4;
+    body.extend(set_id.to_be_bytes());
+    body.extend(((contents.len() + padding + 4) as u16).to_be_bytes());
+    body.extend(contents);
+    body.resize(body.len() + padding, 0);
+}
+
+// Keeps a flow cache for one capture and ships expired flows to the collector and/or file
+pub struct FlowExporter {
+    config: FlowExportConfig,
+    flows: FlowTable,
+    encoder: FlowEncoder,
+    socket: Option<(UdpSocket, SocketAddr)>,
+    file: Option<BufWriter<File>>,
+    exported: u64,
+    // Newest packet timestamp seen and when it arrived, see `clock`
+    newest_sec: i64,
+    newest_at: Instant,
+}
+
+impl FlowExporter {
+    pub fn new(config: FlowExportConfig) -> Result<FlowExporter, Error> {
+        let socket = match &config.collector {
+            Some(collector) => {
+                let address = collector
+                    .to_socket_addrs()?
+                    .next()
+                    .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("Collector {} did not resolve", collector)))?;
+                let bind: SocketAddr = if address.is_ipv6() { "[::]:0".parse() } else { "0.0.0.0:0".parse() }
+                    .map_err(|_| Error::new(ErrorKind::InvalidInput, "Invalid bind address"))?;
+                Some((UdpSocket::bind(bind)?, address))
+            }
+            None => None,
+        };
+        let file = match &config.file {
+            Some(path) => Some(BufWriter::new(File::create(path)?)),
+            None => None,
+        };
+        if socket.is_none() && file.is_none() {
+            return Err(Error::new(ErrorKind::InvalidInput, "Flow export needs a collector or a file"));
+        }
+
+        Ok(FlowExporter {
+            encoder: FlowEncoder::new(config.format, config.observation_domain),
+            config,
+            flows: FlowTable::new(),
+            socket,
+            file,
+            exported: 0,
+            newest_sec: 0,
+            newest_at: Instant::now(),
+        })
+    }
+
+    pub fn observe(&mut self, sample: &FlowSample, ts_sec: i64, ts_usec: u32, len: u32) {
+        self.flows.add(sample, ts_sec, ts_usec, len);
+        if ts_sec >= self.newest_sec {
+            self.newest_sec = ts_sec;
+            self.newest_at = Instant::now();
+        }
+    }
+
+    // Capture time in seconds: the newest packet timestamp, moved on by the wall time since
+    // it arrived. Flows are aged by packet timestamps, so a replayed capture has to be
+    // expired against them too, while the wall time lets idle flows go on a quiet network
+    pub fn clock(&self) -> i64 {
+        self.newest_sec + self.newest_at.elapsed().as_secs() as i64
+    }
+
+    // Exports the flows that have timed out as of `now_sec`
+    pub fn expire(&mut self, now_sec: i64) -> Result<(), Error> {
+        let expired = self.flows.expire(
+            now_sec,
+            self.config.idle_timeout_secs as i64,
+            self.config.active_timeout_secs as i64,
+        );
+        self.send(expired)
+    }
+
+    // Exports everything still in the cache and returns the number of records sent
+    pub fn finish(mut self) -> Result<u64, Error> {
+        let remaining = self.flows.drain();
+        self.send(remaining)?;
+        if let Some(file) = self.file.as_mut() {
+            file.flush()?;
+        }
+        Ok(self.exported)
+    }
+
+    fn send(&mut self, flows: Vec<(FlowKey, FlowStats)>) -> Result<(), Error> {
+        let records: Vec<FlowRecord> = flows
+            .iter()
+            .flat_map(|(key, stats)| FlowRecord::from_flow(key, stats))
+            .collect();
+        if records.is_empty() {
+            return Ok(());
+        }
+        for message in self.encoder.encode(&records) {
+            if let Some((socket, collector)) = &self.socket {
+                socket.send_to(&message, collector)?;
+            }
+            if let Some(file) = self.file.as_mut() {
+                file.write_all(&message)?;
+            }
+        }
+        self.exported += records.len() as u64;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::decode_as::Transport;
+    use std::time::Duration;
+
+    fn collector() -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        socket
+    }
+
+    fn exporter(format: FlowExportFormat, socket: &UdpSocket) -> FlowExporter {
+        FlowExporter::new(FlowExportConfig {
+            enabled: true,
+            format,
+            collector: Some(socket.local_addr().unwrap().to_string()),
+            file: None,
+            observation_domain: 7,
+            ..FlowExportConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn sample(transport: Transport, src: (&str, u16), dst: (&str, u16), tcp_flags: u8) -> FlowSample {
+        let (key, direction) = FlowKey::new(transport, (src.0.parse().unwrap(), src.1), (dst.0.parse().unwrap(), dst.1));
+        FlowSample { key, direction, tcp_flags }
+    }
+
+    // A UDP conversation answered once, and one direction of an IPv6 TCP connection
+    fn observe_traffic(exporter: &mut FlowExporter, ts_sec: i64) {
+        exporter.observe(&sample(Transport::UDP, ("10.0.0.1", 5000), ("10.0.0.2", 53), 0), ts_sec, 0, 100);
+        exporter.observe(&sample(Transport::UDP, ("10.0.0.2", 53), ("10.0.0.1", 5000), 0), ts_sec, 500_000, 200);
+        exporter.observe(&sample(Transport::TCP, ("fe80::1", 40000), ("fe80::2", 443), 0x02), ts_sec, 0, 80);
+    }
+
+    fn receive(socket: &UdpSocket) -> Vec<u8> {
+        let mut buffer = [0u8; 2048];
+        let (len, _) = socket.recv_from(&mut buffer).unwrap();
+        buffer[..len].to_vec()
+    }
+
+    fn u16_at(bytes: &[u8], at: usize) -> u16 {
+        u16::from_be_bytes([bytes[at], bytes[at + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    fn u64_at(bytes: &[u8], at: usize) -> u64 {
+        u64::from_be_bytes(bytes[at..at + 8].try_into().unwrap())
+    }
+
+    // (set ID, contents) for every set after a header of `header_len` bytes
+    fn sets(message: &[u8], header_len: usize) -> Vec<(u16, &[u8])> {
+        let mut sets = Vec::new();
+        let mut at = header_len;
+        while at < message.len() {
+            let length = u16_at(message, at + 2) as usize;
+            assert!(length >= 4 && length.is_multiple_of(4), "set length {}", length);
+            sets.push((u16_at(message, at), &message[at + 4..at + length]));
+            at += length;
+        }
+        assert_eq!(at, message.len());
+        sets
+    }
+
+    // Template ID and (field type, length) pairs of each template in a template set
+    fn templates(contents: &[u8]) -> Vec<(u16, Vec<(u16, u16)>)> {
+        let mut templates = Vec::new();
+        let mut at = 0;
+        while at + 4 <= contents.len() {
+            let field_count = u16_at(contents, at + 2) as usize;
+            let fields = (0..field_count)
+                .map(|i| (u16_at(contents, at + 4 + i * 4), u16_at(contents, at + 6 + i * 4)))
+                .collect();
+            templates.push((u16_at(contents, at), fields));
+            at += 4 + field_count * 4;
+        }
+        templates
+    }
+
+    fn record_len(fields: &[(u16, u16)]) -> usize {
+        fields.iter().map(|(_, length)| *length as usize).sum()
+    }
+
+    #[test]
+    fn netflow_v9_messages() {
+        let socket = collector();
+        let mut exporter = exporter(FlowExportFormat::NetflowV9, &socket);
+        observe_traffic(&mut exporter, 1_700_000_000);
+        assert_eq!(exporter.finish().unwrap(), 3);
+
+        let message = receive(&socket);
+        assert_eq!(u16_at(&message, 0), 9);
+        // Two templates and three data records
+        assert_eq!(u16_at(&message, 2), 5);
+        assert_eq!(u32_at(&message, 12), 0);
+        assert_eq!(u32_at(&message, 16), 7);
+
+        let sets = sets(&message, 20);
+        assert_eq!(sets.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![0, 256, 257]);
+        let templates = templates(sets[0].1);
+        assert_eq!(templates[0], (256, template_fields(FlowExportFormat::NetflowV9, false)));
+        assert_eq!(templates[1], (257, template_fields(FlowExportFormat::NetflowV9, true)));
+
+        // IPv4 records: src, dst, src port, dst port, protocol, flags, packets, bytes, first, last
+        let ipv4 = sets[1].1;
+        let ipv4_len = record_len(&templates[0].1);
+        assert_eq!(ipv4_len, 38);
+        assert_eq!(ipv4.len(), (2 * ipv4_len).div_ceil(4) * 4);
+        let mut records: Vec<&[u8]> = ipv4.chunks(ipv4_len).take(2).collect();
+        records.sort_by_key(|record| u16_at(record, 8));
+        assert_eq!(&records[0][0..8], &[10, 0, 0, 2, 10, 0, 0, 1]);
+        assert_eq!((u16_at(records[0], 8), u16_at(records[0], 10), records[0][12]), (53, 5000, 17));
+        assert_eq!((u64_at(records[0], 14), u64_at(records[0], 22)), (1, 200));
+        assert_eq!(&records[1][0..8], &[10, 0, 0, 1, 10, 0, 0, 2]);
+        assert_eq!((u64_at(records[1], 14), u64_at(records[1], 22)), (1, 100));
+
+        let ipv6 = sets[2].1;
+        assert_eq!(record_len(&templates[1].1), 62);
+        assert_eq!((u16_at(ipv6, 32), u16_at(ipv6, 34), ipv6[36], ipv6[37]), (40000, 443, 6, 0x02));
+        assert!(ipv6[62..].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn ipfix_messages() {
+        let socket = collector();
+        let mut exporter = exporter(FlowExportFormat::Ipfix, &socket);
+        observe_traffic(&mut exporter, 1_700_000_000);
+        exporter.expire(1_700_000_100).unwrap();
+        exporter.observe(&sample(Transport::UDP, ("10.0.0.3", 123), ("10.0.0.4", 123), 0), 1_700_000_200, 0, 90);
+        assert_eq!(exporter.finish().unwrap(), 4);
+
+        let first = receive(&socket);
+        assert_eq!(u16_at(&first, 0), 10);
+        assert_eq!(u16_at(&first, 2) as usize, first.len());
+        // IPFIX counts data records sent before this message
+        assert_eq!(u32_at(&first, 8), 0);
+        assert_eq!(u32_at(&first, 12), 7);
+
+        let first_sets = sets(&first, 16);
+        assert_eq!(first_sets.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![2, 256, 257]);
+        let templates = templates(first_sets[0].1);
+        assert_eq!(templates[0], (256, template_fields(FlowExportFormat::Ipfix, false)));
+        let ipv4_len = record_len(&templates[0].1);
+        assert_eq!(ipv4_len, 47);
+        assert_eq!(first_sets[1].1.len(), (2 * ipv4_len).div_ceil(4) * 4);
+        let record = &first_sets[1].1[..ipv4_len];
+        // flowStartMilliseconds is absolute
+        assert_eq!(u64_at(record, 31), 1_700_000_000_000);
+
+        // Templates are only resent every TEMPLATE_REFRESH_MESSAGES messages
+        let second = receive(&socket);
+        assert_eq!(u16_at(&second, 2) as usize, second.len());
+        assert_eq!(u32_at(&second, 8), 3);
+        let second_sets = sets(&second, 16);
+        assert_eq!(second_sets.len(), 1);
+        assert_eq!(second_sets[0].0, 256);
+        assert_eq!(&second_sets[0].1[..8], &[10, 0, 0, 3, 10, 0, 0, 4]);
+    }
+
+    // A replayed capture is aged by its own timestamps, not by the wall clock
+    #[test]
+    fn expiry_follows_packet_time() {
+        let socket = collector();
+        let mut exporter = exporter(FlowExportFormat::Ipfix, &socket);
+        exporter.observe(&sample(Transport::UDP, ("10.0.0.1", 1), ("10.0.0.2", 2), 0), 1_000, 0, 60);
+        assert_eq!(exporter.clock(), 1_000);
+        exporter.expire(exporter.clock()).unwrap();
+        assert_eq!(exporter.exported, 0);
+
+        exporter.observe(&sample(Transport::UDP, ("10.0.0.3", 3), ("10.0.0.4", 4), 0), 1_020, 0, 60);
+        exporter.expire(exporter.clock()).unwrap();
+        assert_eq!(exporter.exported, 1);
+        let message = receive(&socket);
+        assert_eq!(&sets(&message, 16)[1].1[..8], &[10, 0, 0, 1, 10, 0, 0, 2]);
+    }
+}
@@ -0,0 +1,206 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use super::display_filter::DisplayFilter;
+use super::field_tree::Field;
+
+// How many intervals back from the newest packet are kept: at 1ms buckets this is 100 seconds,
+// at 1s a bit over a day; older buckets are dropped
+const MAX_BUCKETS: i64 = 100_000;
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ProtocolCount {
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IoBucket {
+    // Unix time in milliseconds the bucket starts at
+    pub start_ms: i64,
+    pub packets: u64,
+    pub bytes: u64,
+    pub packets_per_sec: f64,
+    pub bytes_per_sec: f64,
+    // Per innermost protocol, only when the graph was asked to split by protocol
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub protocols: BTreeMap<String, ProtocolCount>,
+}
+
+// A whole graph, or in `io-graph` events the buckets that changed; the UI replaces its
+// buckets by `start_ms`. Intervals without packets are left out
+#[derive(Debug, Clone, Serialize)]
+pub struct IoGraphData {
+    pub interval_ms: u64,
+    pub buckets: Vec<IoBucket>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Bucket {
+    packets: u64,
+    bytes: u64,
+    protocols: HashMap<&'static str, ProtocolCount>,
+}
+
+// Packets and bytes per time bucket, optionally restricted by a display filter and split
+// by protocol. Built from the packet store, then fed every new packet while capturing
+#[derive(Debug)]
+pub struct IoGraph {
+    interval_ms: u64,
+    filter: Option<DisplayFilter>,
+    per_protocol: bool,
+    // Frames up to this one were already counted when the graph was built from the store
+    after_frame: u64,
+    // Buckets with packets in them, by index (unix ms / interval), so a gap in the
+    // timestamps costs nothing
+    buckets: BTreeMap<i64, Bucket>,
+    // Oldest bucket changed since the last update was taken
+    changed_from: Option<i64>,
+}
+
+impl IoGraph {
+    pub fn new(interval_ms: u64, filter: Option<DisplayFilter>, per_protocol: bool) -> IoGraph {
+        IoGraph {
+            interval_ms: interval_ms.max(1),
+            filter,
+            per_protocol,
+            after_frame: 0,
+            buckets: BTreeMap::new(),
+            changed_from: None,
+        }
+    }
+
+    pub fn has_filter(&self) -> bool {
+        self.filter.is_some()
+    }
+
+    // Whether a packet with these fields belongs in the graph, always true without a filter
+    pub fn matches(&self, fields: &[Field]) -> bool {
+        self.filter.as_ref().map(|filter| filter.matches(fields)).unwrap_or(true)
+    }
+
+    // Counts a packet that passed `matches`. `protocol` is the innermost one decoded, `len` the
+    // length on the wire. Frames at or below the last one already counted are ignored
+    pub fn add(&mut self, frame_number: u64, ts_sec: i64, ts_usec: u32, len: u32, protocol: &'static str) {
+        if frame_number <= self.after_frame {
+            return;
+        }
+        self.after_frame = frame_number;
+
+        let index = (ts_sec * 1000 + ts_usec as i64 / 1000).div_euclid(self.interval_ms as i64);
+        let per_protocol = self.per_protocol;
+        let Some(bucket) = self.bucket(index) else {
+            return;
+        };
+        bucket.packets += 1;
+        bucket.bytes += len as u64;
+        if per_protocol {
+            let count = bucket.protocols.entry(protocol).or_default();
+            count.packets += 1;
+            count.bytes += len as u64;
+        }
+        self.changed_from = Some(self.changed_from.map(|changed| changed.min(index)).unwrap_or(index));
+    }
+
+    // None when the bucket is older than the graph keeps
+    fn bucket(&mut self, index: i64) -> Option<&mut Bucket> {
+        let newest = self.buckets.last_key_value().map(|(newest, _)| *newest).unwrap_or(index);
+        if index <= newest - MAX_BUCKETS {
+            return None;
+        }
+        if index > newest {
+            self.buckets = self.buckets.split_off(&(index - MAX_BUCKETS + 1));
+        }
+        Some(self.buckets.entry(index).or_default())
+    }
+
+    // The whole graph
+    pub fn data(&mut self) -> IoGraphData {
+        self.changed_from = None;
+        self.data_from(i64::MIN)
+    }
+
+    // Buckets changed since the graph or the last update was taken, None if nothing changed
+    pub fn take_update(&mut self) -> Option<IoGraphData> {
+        let changed_from = self.changed_from.take()?;
+        Some(self.data_from(changed_from))
+    }
+
+    // Forgets the counts, for when the packet store is cleared and frame numbers start over
+    pub fn clear(&mut self) {
+        self.after_frame = 0;
+        self.buckets.clear();
+        self.changed_from = None;
+    }
+
+    fn data_from(&self, from: i64) -> IoGraphData {
+        let per_sec = 1000.0 / self.interval_ms as f64;
+        let buckets = self
+            .buckets
+            .range(from..)
+            .map(|(index, bucket)| IoBucket {
+                start_ms: index * self.interval_ms as i64,
+                packets: bucket.packets,
+                bytes: bucket.bytes,
+                packets_per_sec: bucket.packets as f64 * per_sec,
+                bytes_per_sec: bucket.bytes as f64 * per_sec,
+                protocols: bucket
+                    .protocols
+                    .iter()
+                    .map(|(protocol, count)| (protocol.to_string(), *count))
+                    .collect(),
+            })
+            .collect();
+        IoGraphData { interval_ms: self.interval_ms, buckets }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn starts(data: &IoGraphData) -> Vec<i64> {
+        data.buckets.iter().map(|bucket| bucket.start_ms).collect()
+    }
+
+    #[test]
+    fn a_gap_only_costs_the_buckets_with_packets() {
+        let mut graph = IoGraph::new(1, None, false);
+        graph.add(1, 0, 0, 60, "UDP");
+        // A bogus timestamp far ahead of the rest
+        graph.add(2, 4_000_000_000, 0, 60, "UDP");
+        graph.add(3, 4_000_000_000, 500, 40, "UDP");
+        let data = graph.data();
+        assert_eq!(starts(&data), vec![4_000_000_000_000]);
+        assert_eq!((data.buckets[0].packets, data.buckets[0].bytes), (2, 100));
+    }
+
+    #[test]
+    fn buckets_past_the_window_are_dropped() {
+        let mut graph = IoGraph::new(1000, None, false);
+        graph.add(1, 10, 0, 60, "TCP");
+        graph.add(2, 20, 0, 60, "TCP");
+        graph.add(3, 10 + MAX_BUCKETS, 0, 60, "TCP");
+        assert_eq!(starts(&graph.data()), vec![20_000, (10 + MAX_BUCKETS) * 1000]);
+        // Older than the window now reaches
+        graph.add(4, 5, 0, 60, "TCP");
+        assert_eq!(graph.data().buckets.len(), 2);
+    }
+
+    #[test]
+    fn frames_are_counted_once_and_updates_hold_what_changed() {
+        let mut graph = IoGraph::new(1000, None, true);
+        graph.add(1, 100, 0, 60, "DNS");
+        graph.add(2, 102, 0, 80, "HTTP");
+        assert_eq!(starts(&graph.data()), vec![100_000, 102_000]);
+        assert!(graph.take_update().is_none());
+
+        // Already counted while the graph was built from the store
+        graph.add(2, 102, 0, 80, "HTTP");
+        graph.add(3, 102, 10, 20, "DNS");
+        let update = graph.take_update().unwrap();
+        assert_eq!(starts(&update), vec![102_000]);
+        assert_eq!((update.buckets[0].packets, update.buckets[0].bytes), (2, 100));
+        assert_eq!(update.buckets[0].protocols["DNS"].packets, 1);
+    }
+}
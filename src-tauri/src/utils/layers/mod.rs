@@ -1,4 +0,0 @@
-pub mod layer1;
-pub mod layer2;
-pub mod layer3;
-pub mod packet_info;
\ No newline at end of file
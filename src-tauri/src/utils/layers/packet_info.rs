@@ -1,5 +0,0 @@
-use std::collections::HashMap;
-
-pub trait PacketInfo {
-    fn get_info(&self) -> HashMap<String, String>;
-}
\ No newline at end of file
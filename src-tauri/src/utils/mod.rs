@@ -1,2 +1,23 @@
-pub mod custom_packet;
-pub mod layers;
+// Dissection lives in the metal-sniffer-core crate, re-exported so the app keeps its paths
+pub use metal_sniffer_core::{
+    custom_packet, decode_as, dhcp_leases, display_filter, field_tree, flow, layers, link, payload_view, reassembly,
+};
+
+pub mod bpf;
+pub mod capture;
+pub mod capture_error;
+pub mod capture_session;
+pub mod capture_stats;
+pub mod dns_stats;
+pub mod endpoints;
+pub mod event_batcher;
+pub mod export;
+pub mod flow_export;
+pub mod io_graph;
+pub mod packet_store;
+pub mod pcap_stream;
+pub mod pcapng;
+pub mod pipeline;
+pub mod protocol_hierarchy;
+pub mod remote;
+pub mod ring_buffer;
\ No newline at end of file
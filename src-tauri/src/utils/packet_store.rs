@@ -0,0 +1,228 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Error;
+use std::path::PathBuf;
+
+use super::custom_packet::{CustomPacket, MyPacket};
+use super::decode_as::DecodeAsTable;
+use super::display_filter::DisplayFilter;
+use super::field_tree::PacketRecord;
+use super::ring_buffer::{RingBuffer, RingBufferConfig, SNAPLEN};
+
+// A captured frame as it came off the wire. Dissection happens when the packet is
+// read, so "decode as" changes apply to everything already captured
+#[derive(Debug, Clone)]
+pub struct StoredPacket {
+    pub frame_number: u64,
+    pub ts_sec: i64,
+    pub ts_usec: u32,
+    // Length on the wire, `data` may be shorter if the snaplen cut it
+    pub len: u32,
+    pub data: Vec<u8>,
+}
+
+pub fn format_timestamp(ts_sec: i64, ts_usec: u32) -> String {
+    match ts_usec.checked_mul(1000).and_then(|nsec| DateTime::from_timestamp(ts_sec, nsec)) {
+        Some(utc) => {
+            let local_time: DateTime<Local> = utc.into();
+            local_time.format("%m/%d/%Y %H:%M:%S").to_string()
+        }
+        None => "Invalid timestamp".to_string(),
+    }
+}
+
+impl StoredPacket {
+    pub fn dissect(&self, decode_as: &DecodeAsTable) -> Result<MyPacket, Error> {
+        MyPacket::from_frame(&self.data, decode_as)
+    }
+
+    // The shape emitted to the UI: legacy layer maps plus raw data, timestamp and field tree
+    pub fn to_record(&self, decode_as: &DecodeAsTable) -> Result<PacketRecord, Error> {
+        let custom_packet = self.dissect(decode_as)?;
+        Ok(self.record(&custom_packet))
+    }
+
+    // Same as to_record for a packet that has already been dissected
+    pub fn record(&self, custom_packet: &MyPacket) -> PacketRecord {
+        let mut record = packet_record(custom_packet, self.ts_sec, self.ts_usec);
+        record.frame_number = Some(self.frame_number);
+        record.info.insert("raw_data".to_string(), HashMap::from([
+            ("data".to_string(), STANDARD.encode(&self.data)),
+            ("header".to_string(), format!(
+                "PacketHeader {{ ts: {}.{:06}, caplen: {}, len: {} }}",
+                self.ts_sec, self.ts_usec, self.data.len(), self.len
+            )),
+        ]));
+        record
+    }
+}
+
+// Layer maps, timestamp and field tree of a packet that has not been stored yet;
+// the store adds the frame number and raw bytes
+pub fn packet_record(custom_packet: &MyPacket, ts_sec: i64, ts_usec: u32) -> PacketRecord {
+    let mut packet_info = custom_packet.get_packet_info();
+    packet_info.insert("timestamp".to_string(), HashMap::from([
+        ("timestamp".to_string(), format_timestamp(ts_sec, ts_usec)),
+    ]));
+
+    PacketRecord {
+        frame_number: None,
+        info: packet_info,
+        fields: custom_packet.get_field_tree(),
+    }
+}
+
+// The filter currently narrowing what the UI pages through
+#[derive(Debug, Clone)]
+pub enum ViewFilter {
+    Bpf(String),
+    Display(DisplayFilter),
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PacketCounts {
+    pub total: usize,
+    pub visible: usize,
+}
+
+// All retained packets, indexed by frame number (frames are numbered from 1 and keep
+// their number when older frames are evicted from the ring buffer)
+#[derive(Debug, Default)]
+pub struct PacketStore {
+    packets: RingBuffer,
+    view: Option<(ViewFilter, Vec<u64>)>,
+}
+
+impl PacketStore {
+    pub fn open(&mut self, dir: PathBuf, config: RingBufferConfig) -> Result<(), Error> {
+        self.view = None;
+        self.packets.set_config(config);
+        self.packets.open(dir)
+    }
+
+    pub fn config(&self) -> &RingBufferConfig {
+        self.packets.config()
+    }
+
+    pub fn set_config(&mut self, config: RingBufferConfig) {
+        self.packets.set_config(config);
+        self.prune_view();
+    }
+
+    pub fn push(&mut self, ts_sec: i64, ts_usec: u32, len: u32, mut data: Vec<u8>) -> Result<StoredPacket, Error> {
+        // Keeps the returned packet the same as what a later `get` reads back
+        data.truncate(SNAPLEN as usize);
+        let len = len.max(data.len() as u32);
+        let frame_number = self.packets.push(ts_sec, ts_usec, len, &data)?;
+        self.prune_view();
+        Ok(StoredPacket { frame_number, ts_sec, ts_usec, len, data })
+    }
+
+    pub fn get(&self, frame_number: u64) -> Result<Option<StoredPacket>, Error> {
+        self.packets.get(frame_number)
+    }
+
+    pub fn first_frame(&self) -> Option<u64> {
+        self.packets.first_frame()
+    }
+
+    pub fn last_frame(&self) -> Option<u64> {
+        self.packets.last_frame()
+    }
+
+    // Unreadable frames are logged and skipped
+    pub fn iter(&self) -> impl Iterator<Item = StoredPacket> + '_ {
+        self.packets.iter().filter_map(|packet| match packet {
+            Ok(packet) => Some(packet),
+            Err(e) => {
+                eprintln!("Failed to read stored packet: {:?}", e);
+                None
+            }
+        })
+    }
+
+    pub fn counts(&self) -> PacketCounts {
+        let total = self.packets.len() as usize;
+        PacketCounts {
+            total,
+            visible: match &self.view {
+                Some((_, frames)) => frames.len(),
+                None => total,
+            },
+        }
+    }
+
+    // A page of the packets that pass the current view filter, oldest first
+    pub fn page(&self, offset: usize, limit: usize) -> Result<Vec<StoredPacket>, Error> {
+        let frames: Vec<u64> = match (&self.view, self.packets.first_frame(), self.packets.last_frame()) {
+            (Some((_, frames)), _, _) => frames.iter().skip(offset).take(limit).copied().collect(),
+            (None, Some(first), Some(last)) => (first..=last).skip(offset).take(limit).collect(),
+            _ => Vec::new(),
+        };
+        let mut packets = Vec::with_capacity(frames.len());
+        for frame_number in frames {
+            if let Some(packet) = self.packets.get(frame_number)? {
+                packets.push(packet);
+            }
+        }
+        Ok(packets)
+    }
+
+    // Frame numbers passing the current view filter, oldest first
+    pub fn visible_frames(&self) -> Vec<u64> {
+        match (&self.view, self.packets.first_frame(), self.packets.last_frame()) {
+            (Some((_, frames)), _, _) => frames.clone(),
+            (None, Some(first), Some(last)) => (first..=last).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn view_filter(&self) -> Option<&ViewFilter> {
+        self.view.as_ref().map(|(filter, _)| filter)
+    }
+
+    // `frames` may name frames evicted while they were being filtered, they are dropped here
+    pub fn set_view(&mut self, filter: ViewFilter, frames: Vec<u64>) {
+        self.view = Some((filter, frames));
+        self.prune_view();
+    }
+
+    // New packets that match the active view filter while capturing
+    pub fn add_to_view(&mut self, frame_number: u64) {
+        if let Some((_, frames)) = self.view.as_mut() {
+            frames.push(frame_number);
+        }
+    }
+
+    pub fn clear_view(&mut self) {
+        self.view = None;
+    }
+
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.view = None;
+        self.packets.clear()
+    }
+
+    // Forgets evicted frames so the view never points past the start of the ring buffer
+    fn prune_view(&mut self) {
+        let first_frame = self.packets.first_frame().unwrap_or(u64::MAX);
+        if let Some((_, frames)) = self.view.as_mut() {
+            let evicted = frames.partition_point(|frame_number| *frame_number < first_frame);
+            frames.drain(..evicted);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_range_microseconds_are_not_a_panic() {
+        assert_eq!(format_timestamp(0, u32::MAX), "Invalid timestamp");
+        assert_ne!(format_timestamp(1_700_000_000, 999_999), "Invalid timestamp");
+    }
+}
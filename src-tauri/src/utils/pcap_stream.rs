@@ -0,0 +1,284 @@
+use pcap::Linktype;
+use std::fs::File;
+use std::io::{self, Chain, Cursor, Error, ErrorKind, Read};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::thread;
+use std::time::Duration;
+
+use super::bpf;
+use super::capture::NextFrame;
+use super::capture_error::CaptureError;
+use super::pcapng::{Frame, PcapngReader};
+
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const PCAPNG_MAGIC: u32 = 0x0a0d_0d0a;
+const PCAP_RECORD_HEADER_LEN: usize = 16;
+const MAX_RECORD_LEN: usize = 16 * 1024 * 1024;
+// Frames read ahead of the capture loop, the writer is blocked beyond this
+const STREAM_QUEUE_LEN: usize = 4096;
+// How long opening waits for the writer to send a file header
+const HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Classic pcap, read record by record as the bytes arrive
+pub struct PcapReader<R: Read> {
+    input: R,
+    big_endian: bool,
+    nanos: bool,
+    link_type: i32,
+}
+
+impl<R: Read> PcapReader<R> {
+    // Reads the global header; `magic` is its first four bytes, already consumed
+    fn new(mut input: R, magic: [u8; 4]) -> Result<PcapReader<R>, Error> {
+        let (big_endian, nanos) = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (PCAP_MAGIC_MICROS, _) => (false, false),
+            (PCAP_MAGIC_NANOS, _) => (false, true),
+            (_, PCAP_MAGIC_MICROS) => (true, false),
+            (_, PCAP_MAGIC_NANOS) => (true, true),
+            _ => return Err(Error::new(ErrorKind::InvalidData, "Not a pcap stream")),
+        };
+        let mut header = [0u8; 20];
+        input.read_exact(&mut header)?;
+        let mut reader = PcapReader { input, big_endian, nanos, link_type: 0 };
+        // The top bits of the link type field carry FCS information
+        reader.link_type = (reader.u32(&header[16..20]) & 0x0fff_ffff) as i32;
+        Ok(reader)
+    }
+
+    // None at a clean end of stream
+    fn next_frame(&mut self) -> Result<Option<Frame>, Error> {
+        let mut header = [0u8; PCAP_RECORD_HEADER_LEN];
+        match self.input.read(&mut header[..1]) {
+            Ok(0) => return Ok(None),
+            Ok(_) => self.input.read_exact(&mut header[1..])?,
+            Err(e) if e.kind() == ErrorKind::Interrupted => return self.next_frame(),
+            Err(e) => return Err(e),
+        }
+        let ts_sec = self.u32(&header[0..4]) as i64;
+        let ts_frac = self.u32(&header[4..8]);
+        let captured = self.u32(&header[8..12]) as usize;
+        let len = self.u32(&header[12..16]);
+        if captured > MAX_RECORD_LEN {
+            return Err(Error::new(ErrorKind::InvalidData, format!("Invalid pcap record length {}", captured)));
+        }
+        // A fraction of a second or more means the stream is corrupt or hostile
+        let frac_per_sec = if self.nanos { 1_000_000_000 } else { 1_000_000 };
+        if ts_frac >= frac_per_sec {
+            return Err(Error::new(ErrorKind::InvalidData, format!("Invalid pcap record timestamp fraction {}", ts_frac)));
+        }
+        let mut data = vec![0u8; captured];
+        self.input.read_exact(&mut data)?;
+        let ts_usec = if self.nanos { ts_frac / 1000 } else { ts_frac };
+        Ok(Some(Frame { ts_sec, ts_usec, len, data }))
+    }
+
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+    }
+}
+
+// pcap or pcapng, whichever the stream turns out to be
+pub enum FrameReader<R: Read> {
+    Pcap(PcapReader<R>),
+    Pcapng(PcapngReader<Chain<Cursor<[u8; 4]>, R>>),
+}
+
+impl<R: Read> FrameReader<R> {
+    // Blocks until the file header has been read, then knows the link type
+    pub fn new(mut input: R) -> Result<FrameReader<R>, Error> {
+        let mut magic = [0u8; 4];
+        input.read_exact(&mut magic)?;
+        if u32::from_le_bytes(magic) == PCAPNG_MAGIC {
+            let mut reader = PcapngReader::new(Cursor::new(magic).chain(input));
+            reader.read_header()?;
+            Ok(FrameReader::Pcapng(reader))
+        } else {
+            Ok(FrameReader::Pcap(PcapReader::new(input, magic)?))
+        }
+    }
+
+    pub fn link_type(&self) -> i32 {
+        match self {
+            FrameReader::Pcap(reader) => reader.link_type,
+            FrameReader::Pcapng(reader) => reader.link_type().unwrap_or(0),
+        }
+    }
+
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, Error> {
+        match self {
+            FrameReader::Pcap(reader) => reader.next_frame(),
+            FrameReader::Pcapng(reader) => reader.next_frame(),
+        }
+    }
+}
+
+enum StreamEvent {
+    Header(i32),
+    Frame(Frame),
+    Error(String),
+}
+
+// A pcap/pcapng byte stream from stdin ("-") or a named pipe, e.g. `tcpdump -w - | ...`.
+// A thread does the blocking reads, so frames are decoded as they arrive and the capture
+// loop can still time out and notice a stop request
+pub struct StreamCapture {
+    name: String,
+    link_type: i32,
+    events: Receiver<StreamEvent>,
+    read_timeout: Duration,
+}
+
+impl StreamCapture {
+    pub fn open(path: &str, filter: &str, read_timeout_ms: i32) -> Result<StreamCapture, CaptureError> {
+        let name = if path == "-" { "stdin".to_string() } else { path.to_string() };
+        let (sender, events) = mpsc::sync_channel(STREAM_QUEUE_LEN);
+        {
+            let path = path.to_string();
+            let filter = filter.to_string();
+            // Opening a FIFO blocks until a writer shows up, so it happens on the reader thread
+            thread::spawn(move || read_stream(&path, &filter, sender));
+        }
+
+        let open_error = |message: String| CaptureError::Open { device: name.clone(), message };
+        let link_type = match events.recv_timeout(HEADER_TIMEOUT) {
+            Ok(StreamEvent::Header(link_type)) => link_type,
+            Ok(StreamEvent::Error(message)) => return Err(open_error(message)),
+            Ok(StreamEvent::Frame(_)) | Err(RecvTimeoutError::Disconnected) => {
+                return Err(open_error("stream ended before a file header".to_string()))
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                return Err(open_error(format!("no pcap header within {} seconds", HEADER_TIMEOUT.as_secs())))
+            }
+        };
+        // The reader thread applies the filter, compiling it here reports syntax errors to the caller
+        if !filter.is_empty() {
+            bpf::compile(filter, Linktype(link_type))?;
+        }
+
+        Ok(StreamCapture {
+            name,
+            link_type,
+            events,
+            read_timeout: Duration::from_millis(read_timeout_ms.max(1) as u64),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn link_type(&self) -> i32 {
+        self.link_type
+    }
+
+    // NextFrame::End once the writer has closed the stream
+    pub fn next_frame(&mut self) -> Result<NextFrame, CaptureError> {
+        match self.events.recv_timeout(self.read_timeout) {
+            Ok(StreamEvent::Frame(frame)) => Ok(NextFrame::Frame(frame)),
+            Ok(StreamEvent::Header(_)) | Err(RecvTimeoutError::Timeout) => Ok(NextFrame::Timeout),
+            Ok(StreamEvent::Error(message)) => Err(CaptureError::Read(format!("{}: {}", self.name, message))),
+            Err(RecvTimeoutError::Disconnected) => Ok(NextFrame::End),
+        }
+    }
+}
+
+fn open_input(path: &str) -> Result<Box<dyn Read + Send>, Error> {
+    if path == "-" {
+        return Ok(Box::new(io::stdin()));
+    }
+    Ok(Box::new(File::open(path)?))
+}
+
+// Runs until the stream ends or the capture is dropped
+fn read_stream(path: &str, filter: &str, events: SyncSender<StreamEvent>) {
+    let mut reader = match open_input(path).and_then(FrameReader::new) {
+        Ok(reader) => reader,
+        Err(e) => {
+            let _ = events.send(StreamEvent::Error(e.to_string()));
+            return;
+        }
+    };
+    let link_type = reader.link_type();
+    if events.send(StreamEvent::Header(link_type)).is_err() {
+        return;
+    }
+    let program = match filter.is_empty() {
+        true => None,
+        false => match bpf::compile(filter, Linktype(link_type)) {
+            Ok(program) => Some(program),
+            // Already reported by open
+            Err(_) => return,
+        },
+    };
+
+    loop {
+        let event = match reader.next_frame() {
+            Ok(Some(frame)) => {
+                if program.as_ref().map(|program| !program.filter(&frame.data)).unwrap_or(false) {
+                    continue;
+                }
+                StreamEvent::Frame(frame)
+            }
+            Ok(None) => return,
+            Err(e) => StreamEvent::Error(e.to_string()),
+        };
+        let failed = matches!(event, StreamEvent::Error(_));
+        // The capture has been stopped and dropped its end
+        if events.send(event).is_err() || failed {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // A little endian pcap stream with one record per (seconds, fraction) pair
+    fn stream(magic: u32, records: &[(u32, u32)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(magic.to_le_bytes());
+        bytes.extend(2u16.to_le_bytes());
+        bytes.extend(4u16.to_le_bytes());
+        bytes.extend([0u8; 8]);
+        bytes.extend(65535u32.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        for (ts_sec, ts_frac) in records {
+            bytes.extend(ts_sec.to_le_bytes());
+            bytes.extend(ts_frac.to_le_bytes());
+            bytes.extend(4u32.to_le_bytes());
+            bytes.extend(4u32.to_le_bytes());
+            bytes.extend([1, 2, 3, 4]);
+        }
+        bytes
+    }
+
+    fn read_all(bytes: Vec<u8>) -> Result<Vec<Frame>, Error> {
+        let mut reader = FrameReader::new(Cursor::new(bytes))?;
+        let mut frames = Vec::new();
+        while let Some(frame) = reader.next_frame()? {
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+
+    #[test]
+    fn reads_micro_and_nanosecond_records() {
+        let frames = read_all(stream(PCAP_MAGIC_MICROS, &[(10, 999_999)])).unwrap();
+        assert_eq!((frames[0].ts_sec, frames[0].ts_usec, frames[0].data.clone()), (10, 999_999, vec![1, 2, 3, 4]));
+
+        let frames = read_all(stream(PCAP_MAGIC_NANOS, &[(10, 999_999_999)])).unwrap();
+        assert_eq!((frames[0].ts_sec, frames[0].ts_usec), (10, 999_999));
+    }
+
+    #[test]
+    fn rejects_a_fraction_of_a_whole_second() {
+        for (magic, ts_frac) in [(PCAP_MAGIC_MICROS, 1_000_000), (PCAP_MAGIC_MICROS, u32::MAX), (PCAP_MAGIC_NANOS, 1_000_000_000)] {
+            let error = read_all(stream(magic, &[(10, 0), (11, ts_frac)])).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use std::io::{Error, ErrorKind, Read, Write};
+
+const SECTION_HEADER_BLOCK: u32 = 0x0a0d_0d0a;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const SIMPLE_PACKET_BLOCK: u32 = 0x0000_0003;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+const OPTION_IF_TSRESOL: u16 = 9;
+// Anything bigger is a corrupt stream, not a packet
+const MAX_BLOCK_LEN: usize = 16 * 1024 * 1024;
+const READ_CHUNK: usize = 64 * 1024;
+
+// A packet read from or written to a pcapng stream
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub ts_sec: i64,
+    pub ts_usec: u32,
+    // Length on the wire, `data` may be shorter if the snaplen cut it
+    pub len: u32,
+    pub data: Vec<u8>,
+}
+
+fn padded(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+// Writes one section with a single interface, enough for a live stream of frames
+pub struct PcapngWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    pub fn new(mut out: W, link_type: i32, snaplen: u32) -> Result<PcapngWriter<W>, Error> {
+        let mut header = Vec::with_capacity(28 + 20);
+        // Section header: byte order magic, version 1.0, unknown section length
+        header.extend(SECTION_HEADER_BLOCK.to_le_bytes());
+        header.extend(28u32.to_le_bytes());
+        header.extend(BYTE_ORDER_MAGIC.to_le_bytes());
+        header.extend(1u16.to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+        header.extend((-1i64).to_le_bytes());
+        header.extend(28u32.to_le_bytes());
+        // Interface description with the default microsecond timestamps
+        header.extend(INTERFACE_DESCRIPTION_BLOCK.to_le_bytes());
+        header.extend(20u32.to_le_bytes());
+        header.extend((link_type as u16).to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+        header.extend(snaplen.to_le_bytes());
+        header.extend(20u32.to_le_bytes());
+        out.write_all(&header)?;
+        Ok(PcapngWriter { out })
+    }
+
+    pub fn write(&mut self, ts_sec: i64, ts_usec: u32, len: u32, data: &[u8]) -> Result<(), Error> {
+        let block_len = (32 + padded(data.len())) as u32;
+        let ts = ts_sec.max(0) as u64 * 1_000_000 + ts_usec as u64;
+        let mut block = Vec::with_capacity(block_len as usize);
+        block.extend(ENHANCED_PACKET_BLOCK.to_le_bytes());
+        block.extend(block_len.to_le_bytes());
+        block.extend(0u32.to_le_bytes());
+        block.extend(((ts >> 32) as u32).to_le_bytes());
+        block.extend((ts as u32).to_le_bytes());
+        block.extend((data.len() as u32).to_le_bytes());
+        block.extend(len.to_le_bytes());
+        block.extend(data);
+        block.resize(block_len as usize - 4, 0);
+        block.extend(block_len.to_le_bytes());
+        // One write per block so a reader never sees half a packet for long
+        self.out.write_all(&block)
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.out.flush()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Interface {
+    link_type: i32,
+    // Timestamp units per second, from if_tsresol
+    units_per_sec: u64,
+}
+
+// Reads pcapng blocks as they arrive. Bytes are buffered until a whole block is in, so a
+// read that times out halfway through a block (sockets, FIFOs) loses nothing: the caller
+// gets the timeout error and simply calls next_frame again
+pub struct PcapngReader<R: Read> {
+    input: R,
+    buffer: Vec<u8>,
+    big_endian: bool,
+    interfaces: Vec<Interface>,
+}
+
+impl<R: Read> PcapngReader<R> {
+    pub fn new(input: R) -> PcapngReader<R> {
+        PcapngReader {
+            input,
+            buffer: Vec::new(),
+            big_endian: false,
+            interfaces: Vec::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.input
+    }
+
+    // Link type of the first interface, once its description block has been read
+    pub fn link_type(&self) -> Option<i32> {
+        self.interfaces.first().map(|interface| interface.link_type)
+    }
+
+    // Reads until the first interface is described, so the link type is known up front
+    pub fn read_header(&mut self) -> Result<i32, Error> {
+        while self.interfaces.is_empty() {
+            if self.next_block()?.is_none() {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "Stream ended before an interface was described"));
+            }
+        }
+        Ok(self.interfaces[0].link_type)
+    }
+
+    // The next packet, None at the end of the stream. Timeouts of the underlying reader are
+    // passed on as WouldBlock/TimedOut errors
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, Error> {
+        loop {
+            match self.next_block()? {
+                Some(Some(frame)) => return Ok(Some(frame)),
+                Some(None) => {}
+                None => return Ok(None),
+            }
+        }
+    }
+
+    // Some(Some(frame)) for a packet block, Some(None) for any other block
+    fn next_block(&mut self) -> Result<Option<Option<Frame>>, Error> {
+        if !self.fill(8)? {
+            return Ok(None);
+        }
+        let block_type = self.u32_at(0);
+        if block_type == SECTION_HEADER_BLOCK {
+            // The byte order magic decides how everything in the section is read
+            if !self.fill(12)? {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "Truncated section header"));
+            }
+            self.big_endian = match u32::from_le_bytes([self.buffer[8], self.buffer[9], self.buffer[10], self.buffer[11]]) {
+                BYTE_ORDER_MAGIC => false,
+                magic if magic.swap_bytes() == BYTE_ORDER_MAGIC => true,
+                _ => return Err(Error::new(ErrorKind::InvalidData, "Not a pcapng stream")),
+            };
+            self.interfaces.clear();
+        }
+
+        let block_len = self.u32_at(4) as usize;
+        if block_len < 12 || block_len & 3 != 0 || block_len > MAX_BLOCK_LEN {
+            return Err(Error::new(ErrorKind::InvalidData, format!("Invalid pcapng block length {}", block_len)));
+        }
+        if !self.fill(block_len)? {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "Truncated pcapng block"));
+        }
+        let block: Vec<u8> = self.buffer.drain(..block_len).collect();
+        let body = &block[8..block_len - 4];
+
+        let frame = match block_type {
+            INTERFACE_DESCRIPTION_BLOCK if body.len() >= 8 => {
+                let link_type = self.u16(&body[0..2]) as i32;
+                let units_per_sec = self.tsresol(&body[8..]).unwrap_or(1_000_000);
+                self.interfaces.push(Interface { link_type, units_per_sec });
+                None
+            }
+            ENHANCED_PACKET_BLOCK if body.len() >= 20 => {
+                let interface = self.u32(&body[0..4]) as usize;
+                let ts = (self.u32(&body[4..8]) as u64) << 32 | self.u32(&body[8..12]) as u64;
+                let captured = self.u32(&body[12..16]) as usize;
+                let len = self.u32(&body[16..20]);
+                let data = body[20..].get(..captured).ok_or_else(|| Error::new(ErrorKind::InvalidData, "Packet longer than its block"))?;
+                let units_per_sec = self.interfaces.get(interface).map(|interface| interface.units_per_sec).unwrap_or(1_000_000);
+                let (ts_sec, ts_usec) = split_timestamp(ts, units_per_sec);
+                Some(Frame { ts_sec, ts_usec, len, data: data.to_vec() })
+            }
+            // No timestamp, and only as much data as fits in the block
+            SIMPLE_PACKET_BLOCK if body.len() >= 4 => {
+                let len = self.u32(&body[0..4]);
+                let captured = (len as usize).min(body.len() - 4);
+                Some(Frame { ts_sec: 0, ts_usec: 0, len, data: body[4..4 + captured].to_vec() })
+            }
+            // Statistics, name resolution, custom blocks, ...
+            _ => None,
+        };
+        Ok(Some(frame))
+    }
+
+    // if_tsresol: 10^-n seconds, or 2^-n with the top bit set
+    fn tsresol(&self, mut options: &[u8]) -> Option<u64> {
+        while options.len() >= 4 {
+            let code = self.u16(&options[0..2]);
+            let len = self.u16(&options[2..4]) as usize;
+            let value = options.get(4..4 + len)?;
+            if code == OPTION_IF_TSRESOL && len == 1 {
+                let exponent = (value[0] & 0x7f) as u32;
+                return match value[0] & 0x80 {
+                    0 => 10u64.checked_pow(exponent),
+                    _ => 2u64.checked_pow(exponent),
+                };
+            }
+            if code == 0 {
+                break;
+            }
+            options = options.get(4 + padded(len)..)?;
+        }
+        None
+    }
+
+    // Reads until at least `len` bytes are buffered, false if the stream ended cleanly first
+    fn fill(&mut self, len: usize) -> Result<bool, Error> {
+        let mut chunk = [0u8; READ_CHUNK];
+        while self.buffer.len() < len {
+            match self.input.read(&mut chunk) {
+                Ok(0) if self.buffer.is_empty() => return Ok(false),
+                Ok(0) => return Err(Error::new(ErrorKind::UnexpectedEof, "Stream ended mid block")),
+                Ok(read) => self.buffer.extend_from_slice(&chunk[..read]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    fn u16(&self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) }
+    }
+
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+    }
+
+    fn u32_at(&self, offset: usize) -> u32 {
+        self.u32(&self.buffer[offset..offset + 4])
+    }
+}
+
+fn split_timestamp(ts: u64, units_per_sec: u64) -> (i64, u32) {
+    let units_per_sec = units_per_sec.max(1);
+    let sec = ts / units_per_sec;
+    let usec = (ts % units_per_sec) as u128 * 1_000_000 / units_per_sec as u128;
+    (sec as i64, usec as u32)
+}
@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// A frame copied out of pcap, `seq` is its position in capture order
+#[derive(Debug, Clone)]
+pub struct RawFrame {
+    pub seq: u64,
+    pub ts_sec: i64,
+    pub ts_usec: u32,
+    pub len: u32,
+    pub data: Vec<u8>,
+}
+
+// A frame with whatever the workers turned it into
+pub struct DissectedFrame<T> {
+    pub frame: RawFrame,
+    pub packet: Result<T, Error>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PipelineConfig {
+    pub workers: usize,
+    // Frames waiting for a worker, the capture thread drops frames beyond this
+    pub queue_len: usize,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        // Leave a core for the capture thread
+        let cores = thread::available_parallelism().map(|cores| cores.get()).unwrap_or(2);
+        PipelineConfig {
+            workers: cores.saturating_sub(1).max(1),
+            queue_len: 8192,
+        }
+    }
+}
+
+// Capture -> bounded queue -> dissector workers -> `Ordered`. The capture side only
+// copies frames in, so slow dissection shows up as pipeline drops instead of kernel drops
+pub struct Pipeline {
+    sender: Option<SyncSender<RawFrame>>,
+    workers: Vec<JoinHandle<()>>,
+    next_seq: u64,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Pipeline {
+    // `dissect` runs on the workers, so the expensive part of handling a packet belongs in it
+    pub fn start<T, F>(config: PipelineConfig, dissect: F) -> (Pipeline, Ordered<T>)
+    where
+        T: Send + 'static,
+        F: Fn(&RawFrame) -> Result<T, Error> + Send + Sync + 'static,
+    {
+        let (sender, frames) = mpsc::sync_channel::<RawFrame>(config.queue_len);
+        let frames = Arc::new(Mutex::new(frames));
+        let (result_sender, results) = mpsc::sync_channel(config.queue_len);
+        let dissect = Arc::new(dissect);
+
+        let workers = (0..config.workers.max(1))
+            .map(|_| {
+                let frames = frames.clone();
+                let results = result_sender.clone();
+                let dissect = dissect.clone();
+                thread::spawn(move || dissect_frames(frames, results, dissect))
+            })
+            .collect();
+
+        let pipeline = Pipeline {
+            sender: Some(sender),
+            workers,
+            next_seq: 0,
+            dropped: Arc::new(AtomicU64::new(0)),
+        };
+        let ordered = Ordered { results, pending: BTreeMap::new(), next_seq: 0 };
+        (pipeline, ordered)
+    }
+
+    // Never blocks; returns false (and counts the frame as dropped) when the workers are behind
+    pub fn submit(&mut self, ts_sec: i64, ts_usec: u32, len: u32, data: Vec<u8>) -> bool {
+        let Some(sender) = self.sender.as_ref() else {
+            return false;
+        };
+        let frame = RawFrame { seq: self.next_seq, ts_sec, ts_usec, len, data };
+        match sender.try_send(frame) {
+            Ok(()) => {
+                self.next_seq += 1;
+                true
+            }
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    // Waits for room in the queue, for offline input where nothing is lost by waiting
+    pub fn submit_blocking(&mut self, ts_sec: i64, ts_usec: u32, len: u32, data: Vec<u8>) -> bool {
+        let Some(sender) = self.sender.as_ref() else {
+            return false;
+        };
+        let frame = RawFrame { seq: self.next_seq, ts_sec, ts_usec, len, data };
+        if sender.send(frame).is_err() {
+            return false;
+        }
+        self.next_seq += 1;
+        true
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    // Frames already queued are still dissected; `Ordered` ends once they are all out
+    pub fn finish(mut self) {
+        self.sender = None;
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                eprintln!("Dissector worker panicked");
+            }
+        }
+    }
+}
+
+fn dissect_frames<T, F>(frames: Arc<Mutex<Receiver<RawFrame>>>, results: SyncSender<DissectedFrame<T>>, dissect: Arc<F>)
+where
+    F: Fn(&RawFrame) -> Result<T, Error>,
+{
+    loop {
+        let frame = match frames.lock() {
+            Ok(frames) => match frames.recv() {
+                Ok(frame) => frame,
+                Err(_) => return,
+            },
+            Err(_) => return,
+        };
+
+        // A panic in a dissector must not stall the frames queued behind it
+        let packet = panic::catch_unwind(AssertUnwindSafe(|| dissect(&frame)))
+            .unwrap_or_else(|_| Err(Error::new(ErrorKind::InvalidData, "Malformed packet")));
+
+        if results.send(DissectedFrame { frame, packet }).is_err() {
+            return;
+        }
+    }
+}
+
+// Dissected frames back in capture order, whichever worker finished them first
+pub struct Ordered<T> {
+    results: Receiver<DissectedFrame<T>>,
+    pending: BTreeMap<u64, DissectedFrame<T>>,
+    next_seq: u64,
+}
+
+impl<T> Ordered<T> {
+    // Like `next`, but gives up once `timeout` passes without the next frame in order, so
+    // the consumer can do periodic work on a quiet network
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<DissectedFrame<T>, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(dissected) = self.pending.remove(&self.next_seq) {
+                self.next_seq += 1;
+                return Ok(dissected);
+            }
+            let dissected = self.results.recv_timeout(deadline.saturating_duration_since(Instant::now()))?;
+            if dissected.frame.seq == self.next_seq {
+                self.next_seq += 1;
+                return Ok(dissected);
+            }
+            self.pending.insert(dissected.frame.seq, dissected);
+        }
+    }
+}
+
+impl<T> Iterator for Ordered<T> {
+    type Item = DissectedFrame<T>;
+
+    fn next(&mut self) -> Option<DissectedFrame<T>> {
+        loop {
+            if let Some(dissected) = self.pending.remove(&self.next_seq) {
+                self.next_seq += 1;
+                return Some(dissected);
+            }
+            match self.results.recv() {
+                Ok(dissected) if dissected.frame.seq == self.next_seq => {
+                    self.next_seq += 1;
+                    return Some(dissected);
+                }
+                Ok(dissected) => {
+                    self.pending.insert(dissected.frame.seq, dissected);
+                }
+                // Every worker is gone, nothing else can arrive
+                Err(_) => return None,
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use serde::Serialize;
+
+// One protocol under its parent, like a row of Wireshark's Protocol Hierarchy view.
+// Percentages are of every packet counted, not of the parent
+#[derive(Debug, Clone, Serialize)]
+pub struct ProtocolNode {
+    pub protocol: String,
+    pub packets: u64,
+    pub bytes: u64,
+    pub packet_percent: f64,
+    pub byte_percent: f64,
+    // Packets for which this was the innermost protocol decoded
+    pub end_packets: u64,
+    pub end_bytes: u64,
+    pub children: Vec<ProtocolNode>,
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    protocol: &'static str,
+    packets: u64,
+    bytes: u64,
+    end_packets: u64,
+    end_bytes: u64,
+    children: Vec<Counts>,
+}
+
+impl Counts {
+    fn child(&mut self, protocol: &'static str) -> &mut Counts {
+        let index = match self.children.iter().position(|child| child.protocol == protocol) {
+            Some(index) => index,
+            None => {
+                self.children.push(Counts { protocol, ..Counts::default() });
+                self.children.len() - 1
+            }
+        };
+        &mut self.children[index]
+    }
+
+    fn to_node(&self, total_packets: u64, total_bytes: u64) -> ProtocolNode {
+        let percent = |part: u64, total: u64| if total == 0 { 0.0 } else { part as f64 * 100.0 / total as f64 };
+        let mut children: Vec<ProtocolNode> = self
+            .children
+            .iter()
+            .map(|child| child.to_node(total_packets, total_bytes))
+            .collect();
+        children.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.protocol.cmp(&b.protocol)));
+        ProtocolNode {
+            protocol: self.protocol.to_string(),
+            packets: self.packets,
+            bytes: self.bytes,
+            packet_percent: percent(self.packets, total_packets),
+            byte_percent: percent(self.bytes, total_bytes),
+            end_packets: self.end_packets,
+            end_bytes: self.end_bytes,
+            children,
+        }
+    }
+}
+
+// Packets and bytes per protocol path (Ethernet → IPv4 → TCP → HTTPS, ...)
+#[derive(Debug, Default)]
+pub struct ProtocolHierarchy {
+    root: Counts,
+}
+
+impl ProtocolHierarchy {
+    pub fn new() -> ProtocolHierarchy {
+        ProtocolHierarchy::default()
+    }
+
+    // `path` runs from the link layer inward, `len` is the frame length on the wire
+    pub fn add(&mut self, path: &[&'static str], len: u32) {
+        self.root.packets += 1;
+        self.root.bytes += len as u64;
+        let mut node = &mut self.root;
+        for protocol in path {
+            node = node.child(protocol);
+            node.packets += 1;
+            node.bytes += len as u64;
+        }
+        node.end_packets += 1;
+        node.end_bytes += len as u64;
+    }
+
+    // The link layer protocols, each with everything decoded on top of it
+    pub fn tree(&self) -> Vec<ProtocolNode> {
+        self.root.to_node(self.root.packets, self.root.bytes).children
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_every_level_of_the_path() {
+        let mut hierarchy = ProtocolHierarchy::new();
+        hierarchy.add(&["Ethernet", "IPv4", "TCP", "HTTPS"], 500);
+        hierarchy.add(&["Ethernet", "IPv4", "TCP"], 60);
+        hierarchy.add(&["Ethernet", "IPv4", "UDP", "DNS"], 80);
+        hierarchy.add(&["Ethernet", "IPv6", "UDP", "DNS"], 360);
+
+        let tree = hierarchy.tree();
+        assert_eq!(tree.len(), 1);
+        let ethernet = &tree[0];
+        assert_eq!((ethernet.packets, ethernet.bytes), (4, 1000));
+        assert_eq!((ethernet.packet_percent, ethernet.byte_percent), (100.0, 100.0));
+        assert_eq!(ethernet.end_packets, 0);
+
+        // Children come largest first
+        let names: Vec<&str> = ethernet.children.iter().map(|node| node.protocol.as_str()).collect();
+        assert_eq!(names, ["IPv4", "IPv6"]);
+        let ipv4 = &ethernet.children[0];
+        assert_eq!((ipv4.packets, ipv4.bytes), (3, 640));
+        assert_eq!((ipv4.packet_percent, ipv4.byte_percent), (75.0, 64.0));
+
+        // Percentages are of all packets, not of the parent
+        let tcp = &ipv4.children[0];
+        assert_eq!(tcp.protocol, "TCP");
+        assert_eq!((tcp.packets, tcp.bytes, tcp.end_packets, tcp.end_bytes), (2, 560, 1, 60));
+        assert_eq!((tcp.packet_percent, tcp.byte_percent), (50.0, 56.0));
+        let https = &tcp.children[0];
+        assert_eq!((https.end_packets, https.end_bytes, https.packet_percent), (1, 500, 25.0));
+
+        assert!(ProtocolHierarchy::new().tree().is_empty());
+    }
+}
@@ -0,0 +1,364 @@
+use pcap::{Active, Capture};
+use serde::{Deserialize, Serialize};
+use std::io::{BufWriter, Error, ErrorKind, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use super::capture;
+use super::capture_error::CaptureError;
+use super::pcapng::{Frame, PcapngReader, PcapngWriter};
+
+pub const DEFAULT_PORT: u16 = 7447;
+const PROTOCOL_VERSION: u32 = 1;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// How long a client has to say hello before the agent hangs up
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_HANDSHAKE_LINE: usize = 64 * 1024;
+// The agent never sits on captured frames longer than this
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+const AGENT_READ_TIMEOUT_MS: i32 = 100;
+const SNAPLEN: u32 = 65535;
+
+// First line the client sends, as JSON
+#[derive(Debug, Serialize, Deserialize)]
+struct Hello {
+    version: u32,
+    token: String,
+    // BPF filter the agent applies on its side, empty for everything
+    #[serde(default)]
+    filter: String,
+}
+
+// The agent's answer; a pcapng stream follows when there is no error
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Welcome {
+    #[serde(default)]
+    error: Option<RemoteError>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RemoteError {
+    code: String,
+    message: String,
+}
+
+fn write_line<T: Serialize>(stream: &mut TcpStream, message: &T) -> Result<(), Error> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    stream.write_all(&line)
+}
+
+// Reads one handshake line without consuming anything after it
+fn read_line(stream: &mut TcpStream) -> Result<Vec<u8>, Error> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        match stream.read(&mut byte)? {
+            0 => return Err(Error::new(ErrorKind::UnexpectedEof, "Connection closed during handshake")),
+            _ if byte[0] == b'\n' => return Ok(line),
+            _ if line.len() >= MAX_HANDSHAKE_LINE => {
+                return Err(Error::new(ErrorKind::InvalidData, "Handshake line too long"))
+            }
+            _ => line.push(byte[0]),
+        }
+    }
+}
+
+// Compares every byte so the time taken does not leak how much of the token matched
+fn token_matches(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+// Agent side: accepts one client at a time and streams the interface to it as pcapng
+// until it disconnects. Only returns if the listener itself fails
+pub fn serve(listener: TcpListener, token: &str, interface: Option<&str>) -> Result<(), Error> {
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        let peer = stream.peer_addr().map(|peer| peer.to_string()).unwrap_or_default();
+        eprintln!("Client connected from {}", peer);
+        match serve_client(&mut stream, token, interface) {
+            Ok(frames) => eprintln!("Client {} disconnected after {} frames", peer, frames),
+            Err(e) => eprintln!("Client {}: {}", peer, e),
+        }
+    }
+    Ok(())
+}
+
+fn serve_client(stream: &mut TcpStream, token: &str, interface: Option<&str>) -> Result<u64, CaptureError> {
+    let io_error = |e: Error| CaptureError::Read(e.to_string());
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).map_err(io_error)?;
+    let hello: Hello = serde_json::from_slice(&read_line(stream).map_err(io_error)?)
+        .map_err(|e| CaptureError::Read(format!("Invalid handshake: {}", e)))?;
+
+    let opened = if hello.version != PROTOCOL_VERSION {
+        Err(CaptureError::RemoteRejected(format!("unsupported protocol version {}", hello.version)))
+    } else if !token_matches(token, &hello.token) {
+        Err(CaptureError::RemoteRejected("invalid token".to_string()))
+    } else {
+        // The client's filter is applied on its own first so a mistake in it comes back as
+        // the client's own invalid filter, not as one in the expression it gets spliced into
+        capture::open_device(interface, &hello.filter, AGENT_READ_TIMEOUT_MS).and_then(|mut capture| {
+            let filter = exclude_own_traffic(&hello.filter, stream);
+            capture
+                .filter(&filter, true)
+                .map_err(|e| CaptureError::Internal(format!("Failed to exclude the agent's own traffic: {}", e)))?;
+            Ok(capture)
+        })
+    };
+    let capture = match opened {
+        Ok(capture) => capture,
+        Err(error) => {
+            // The client wraps these two back into the same variants, so only their details go
+            let message = match &error {
+                CaptureError::RemoteRejected(message) | CaptureError::InvalidFilter { message, .. } => message.clone(),
+                _ => error.to_string(),
+            };
+            let welcome = Welcome { error: Some(RemoteError { code: error.code().to_string(), message }) };
+            let _ = write_line(stream, &welcome);
+            return Err(error);
+        }
+    };
+    write_line(stream, &Welcome::default()).map_err(io_error)?;
+    stream_frames(capture, stream).map_err(io_error)
+}
+
+// The agent must not capture the stream it is sending, or every frame would generate another
+fn exclude_own_traffic(filter: &str, stream: &TcpStream) -> String {
+    let (Ok(local), Ok(peer)) = (stream.local_addr(), stream.peer_addr()) else {
+        return filter.to_string();
+    };
+    let own = format!("not (host {} and tcp port {})", peer.ip(), local.port());
+    if filter.trim().is_empty() {
+        own
+    } else {
+        format!("({}) and {}", filter, own)
+    }
+}
+
+// Runs until the client goes away, which shows up as a failed write; returns the frames sent
+fn stream_frames(mut capture: Capture<Active>, stream: &TcpStream) -> Result<u64, Error> {
+    let link_type = capture.get_datalink().0;
+    let mut writer = PcapngWriter::new(BufWriter::new(stream), link_type, SNAPLEN)?;
+    let mut last_flush = Instant::now();
+    let mut frames = 0;
+    loop {
+        let written = match capture.next_packet() {
+            Ok(packet) => writer.write(
+                packet.header.ts.tv_sec as i64,
+                packet.header.ts.tv_usec as u32,
+                packet.header.len,
+                packet.data,
+            ).map(|_| 1),
+            Err(pcap::Error::TimeoutExpired) => Ok(0),
+            Err(e) => return Err(Error::other(e.to_string())),
+        };
+        let written = written.and_then(|count| {
+            if last_flush.elapsed() >= FLUSH_INTERVAL {
+                last_flush = Instant::now();
+                writer.flush()?;
+            }
+            Ok(count)
+        });
+        match written {
+            Ok(count) => frames += count,
+            Err(e) if matches!(e.kind(), ErrorKind::BrokenPipe | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted) => {
+                return Ok(frames)
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Client side: a capture running on an agent, read as if it were a local interface
+pub struct RemoteCapture {
+    address: String,
+    reader: PcapngReader<TcpStream>,
+    link_type: i32,
+}
+
+// An IP address (IPv6 bare or in brackets) or host name, with or without a port
+fn resolve(address: &str) -> Result<SocketAddr, String> {
+    if let Ok(socket_address) = address.parse::<SocketAddr>() {
+        return Ok(socket_address);
+    }
+    // Checked before looking for a port, the colons of a bare IPv6 address are not one
+    if let Ok(ip) = address.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, DEFAULT_PORT));
+    }
+    let target = if address.contains(':') { address.to_string() } else { format!("{}:{}", address, DEFAULT_PORT) };
+    target
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or_else(|| "address did not resolve".to_string())
+}
+
+impl RemoteCapture {
+    // `address` is host:port, the default port is used when there is none
+    pub fn connect(address: &str, token: &str, filter: &str, read_timeout_ms: i32) -> Result<RemoteCapture, CaptureError> {
+        let open_error = |message: String| CaptureError::Open { device: address.to_string(), message };
+        let socket_address = resolve(address).map_err(open_error)?;
+
+        let mut stream = TcpStream::connect_timeout(&socket_address, CONNECT_TIMEOUT).map_err(|e| open_error(e.to_string()))?;
+        stream.set_nodelay(true).map_err(|e| open_error(e.to_string()))?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).map_err(|e| open_error(e.to_string()))?;
+
+        let hello = Hello { version: PROTOCOL_VERSION, token: token.to_string(), filter: filter.to_string() };
+        write_line(&mut stream, &hello).map_err(|e| open_error(e.to_string()))?;
+        let welcome: Welcome = serde_json::from_slice(&read_line(&mut stream).map_err(|e| open_error(e.to_string()))?)
+            .map_err(|e| open_error(format!("not a capture agent ({})", e)))?;
+        if let Some(error) = welcome.error {
+            return Err(match error.code.as_str() {
+                "invalid_filter" => CaptureError::InvalidFilter { filter: filter.to_string(), message: error.message },
+                _ => CaptureError::RemoteRejected(error.message),
+            });
+        }
+
+        let mut reader = PcapngReader::new(stream);
+        let link_type = reader.read_header().map_err(|e| open_error(e.to_string()))?;
+        // From here on reads time out so the capture loop can notice a stop request
+        let timeout = Duration::from_millis(read_timeout_ms.max(1) as u64);
+        reader.get_ref().set_read_timeout(Some(timeout)).map_err(|e| open_error(e.to_string()))?;
+        Ok(RemoteCapture { address: address.to_string(), reader, link_type })
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    // libpcap link type of the agent's interface
+    pub fn link_type(&self) -> i32 {
+        self.link_type
+    }
+
+    // Ok(None) when no frame arrived within the read timeout
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, CaptureError> {
+        match self.reader.next_frame() {
+            Ok(Some(frame)) => Ok(Some(frame)),
+            Ok(None) => Err(CaptureError::Read(format!("agent {} closed the connection", self.address))),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(None),
+            Err(e) => Err(CaptureError::Read(e.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    const LINKTYPE_ETHERNET: i32 = 1;
+
+    fn bind() -> (TcpListener, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        (listener, address)
+    }
+
+    // Answers one client the way an agent does, without needing a device to capture on
+    fn fake_agent(listener: TcpListener, welcome: &'static [u8], frames: Vec<Frame>) -> thread::JoinHandle<Hello> {
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let hello: Hello = serde_json::from_slice(&read_line(&mut stream).unwrap()).unwrap();
+            stream.write_all(welcome).unwrap();
+            if !frames.is_empty() {
+                let mut writer = PcapngWriter::new(&stream, LINKTYPE_ETHERNET, SNAPLEN).unwrap();
+                for frame in &frames {
+                    writer.write(frame.ts_sec, frame.ts_usec, frame.len, &frame.data).unwrap();
+                }
+            }
+            // Keeps the connection open until the client has read everything
+            let _ = stream.read(&mut [0u8; 1]);
+            hello
+        })
+    }
+
+    #[test]
+    fn connect_streams_frames_from_the_agent() {
+        let (listener, address) = bind();
+        let frame = Frame { ts_sec: 1_700_000_000, ts_usec: 250_000, len: 60, data: vec![0xaa; 60] };
+        let agent = fake_agent(listener, b"{}\n", vec![frame.clone()]);
+
+        let mut remote = RemoteCapture::connect(&address, "secret", "udp port 53", 100).unwrap();
+        assert_eq!(remote.link_type(), LINKTYPE_ETHERNET);
+        let received = loop {
+            if let Some(received) = remote.next_frame().unwrap() {
+                break received;
+            }
+        };
+        assert_eq!((received.ts_sec, received.ts_usec, received.len), (frame.ts_sec, frame.ts_usec, frame.len));
+        assert_eq!(received.data, frame.data);
+        drop(remote);
+
+        let hello = agent.join().unwrap();
+        assert_eq!((hello.version, hello.token.as_str(), hello.filter.as_str()), (PROTOCOL_VERSION, "secret", "udp port 53"));
+    }
+
+    #[test]
+    fn serve_rejects_a_wrong_token() {
+        let (listener, address) = bind();
+        thread::spawn(move || serve(listener, "secret", None));
+
+        // The agent keeps serving after turning a client away
+        for token in ["wrong", "secre", ""] {
+            match RemoteCapture::connect(&address, token, "", 100) {
+                Err(CaptureError::RemoteRejected(message)) => assert_eq!(message, "invalid token"),
+                other => panic!("expected a rejection, got {:?}", other.map(|remote| remote.address().to_string())),
+            }
+        }
+    }
+
+    #[test]
+    fn agent_errors_reach_the_client() {
+        let (listener, address) = bind();
+        fake_agent(listener, b"{\"error\":{\"code\":\"invalid_filter\",\"message\":\"syntax error\"}}\n", Vec::new());
+        match RemoteCapture::connect(&address, "secret", "bogus", 100) {
+            Err(CaptureError::InvalidFilter { filter, message }) => assert_eq!((filter.as_str(), message.as_str()), ("bogus", "syntax error")),
+            other => panic!("expected an invalid filter, got {:?}", other.map(|remote| remote.address().to_string())),
+        }
+
+        let (listener, address) = bind();
+        fake_agent(listener, b"SSH-2.0-OpenSSH\n", Vec::new());
+        assert!(matches!(RemoteCapture::connect(&address, "secret", "", 100), Err(CaptureError::Open { .. })));
+    }
+
+    #[test]
+    fn own_traffic_is_excluded_around_the_client_filter() {
+        let (listener, address) = bind();
+        let client = TcpStream::connect(&address).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        let port = server.local_addr().unwrap().port();
+
+        assert_eq!(exclude_own_traffic("", &server), format!("not (host 127.0.0.1 and tcp port {})", port));
+        assert_eq!(
+            exclude_own_traffic("udp or icmp", &server),
+            format!("(udp or icmp) and not (host 127.0.0.1 and tcp port {})", port)
+        );
+        drop(client);
+    }
+
+    #[test]
+    fn addresses_resolve_with_or_without_a_port() {
+        let resolved = |address: &str| resolve(address).unwrap().to_string();
+        assert_eq!(resolved("192.0.2.1"), "192.0.2.1:7447");
+        assert_eq!(resolved("192.0.2.1:9000"), "192.0.2.1:9000");
+        // The colons of an IPv6 address are not a port
+        assert_eq!(resolved("2001:db8::1"), "[2001:db8::1]:7447");
+        assert_eq!(resolved("[2001:db8::1]"), "[2001:db8::1]:7447");
+        assert_eq!(resolved("[2001:db8::1]:9000"), "[2001:db8::1]:9000");
+        assert_eq!(resolve("localhost").unwrap().port(), DEFAULT_PORT);
+        assert_eq!(resolve("localhost:9000").unwrap().port(), 9000);
+        assert!(resolve("[2001:db8::1]:port").is_err());
+    }
+}
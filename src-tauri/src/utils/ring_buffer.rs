@@ -0,0 +1,407 @@
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use super::packet_store::StoredPacket;
+
+// Segments are plain pcap files, so a spilled capture can also be opened in Wireshark
+const PCAP_MAGIC: u32 = 0xa1b2_c3d4;
+const PCAP_GLOBAL_HEADER_LEN: u64 = 24;
+const PCAP_RECORD_HEADER_LEN: u64 = 16;
+const LINKTYPE_ETHERNET: u32 = 1;
+// Longer frames are truncated when pushed, as tcpdump does. Pipes and remote agents can
+// deliver more than the 65535 bytes a live capture asks for
+pub const SNAPLEN: u32 = 262_144;
+const SEGMENT_PREFIX: &str = "segment-";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RingBufferConfig {
+    // A new segment is started once the current one reaches this size
+    pub segment_bytes: u64,
+    // Caps on what is kept, the oldest segment is evicted when any is exceeded
+    pub max_packets: Option<u64>,
+    pub max_bytes: Option<u64>,
+    pub max_age_secs: Option<i64>,
+}
+
+impl Default for RingBufferConfig {
+    fn default() -> Self {
+        RingBufferConfig {
+            segment_bytes: 64 * 1024 * 1024,
+            max_packets: None,
+            max_bytes: Some(1024 * 1024 * 1024),
+            max_age_secs: None,
+        }
+    }
+}
+
+impl RingBufferConfig {
+    pub fn load(path: &Path) -> Result<RingBufferConfig, String> {
+        if !path.exists() {
+            return Ok(RingBufferConfig::default());
+        }
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read ring buffer config: {}", e))?;
+        serde_json::from_str(&contents)
+            .map_err(|e| format!("Invalid ring buffer config: {}", e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize ring buffer config: {}", e))?;
+        fs::write(path, contents).map_err(|e| format!("Failed to write ring buffer config: {}", e))
+    }
+}
+
+#[derive(Debug)]
+struct Segment {
+    path: PathBuf,
+    first_frame: u64,
+    // File offset of each record header, indexed by frame - first_frame
+    offsets: Vec<u64>,
+    bytes: u64,
+    last_ts: i64,
+    // Opened on the first lookup and kept, the UI pages through frames one at a time
+    reader: RefCell<Option<File>>,
+}
+
+impl Segment {
+    fn last_frame(&self) -> u64 {
+        self.first_frame + self.offsets.len() as u64 - 1
+    }
+}
+
+// Packets spilled to a rolling set of segment files, only the per frame
+// offsets are kept in memory
+#[derive(Debug)]
+pub struct RingBuffer {
+    dir: PathBuf,
+    config: RingBufferConfig,
+    segments: Vec<Segment>,
+    writer: Option<File>,
+    next_frame: u64,
+    next_segment: u64,
+}
+
+impl RingBuffer {
+    // Nothing touches the disk until the first packet is pushed
+    pub fn new(dir: PathBuf, config: RingBufferConfig) -> RingBuffer {
+        RingBuffer {
+            dir,
+            config,
+            segments: Vec::new(),
+            writer: None,
+            next_frame: 1,
+            next_segment: 0,
+        }
+    }
+
+    // Moves storage to another directory, dropping what was captured so far and any
+    // segments a previous run left behind there
+    pub fn open(&mut self, dir: PathBuf) -> Result<(), Error> {
+        self.clear()?;
+        self.dir = dir;
+        remove_segments(&self.dir)
+    }
+
+    pub fn config(&self) -> &RingBufferConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: RingBufferConfig) {
+        self.config = config;
+        self.evict();
+    }
+
+    pub fn first_frame(&self) -> Option<u64> {
+        self.segments.first().map(|segment| segment.first_frame)
+    }
+
+    pub fn last_frame(&self) -> Option<u64> {
+        self.segments.last().map(|segment| segment.last_frame())
+    }
+
+    pub fn len(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.offsets.len() as u64).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.bytes).sum()
+    }
+
+    // Appends a frame and returns its frame number, frames over SNAPLEN are truncated
+    pub fn push(&mut self, ts_sec: i64, ts_usec: u32, len: u32, data: &[u8]) -> Result<u64, Error> {
+        let needs_segment = match self.segments.last() {
+            Some(segment) => self.writer.is_none() || segment.bytes >= self.config.segment_bytes,
+            None => true,
+        };
+        if needs_segment {
+            self.start_segment()?;
+        }
+
+        let data = &data[..data.len().min(SNAPLEN as usize)];
+        let len = len.max(data.len() as u32);
+        let frame_number = self.next_frame;
+        let segment = self.segments.last_mut().unwrap();
+        let writer = self.writer.as_mut().unwrap();
+
+        // One write per record so readers never see half of it
+        let mut record = Vec::with_capacity(PCAP_RECORD_HEADER_LEN as usize + data.len());
+        record.extend_from_slice(&(ts_sec as u32).to_le_bytes());
+        record.extend_from_slice(&ts_usec.to_le_bytes());
+        record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(data);
+        writer.write_all(&record)?;
+
+        segment.offsets.push(segment.bytes);
+        segment.bytes += record.len() as u64;
+        segment.last_ts = ts_sec;
+        self.next_frame += 1;
+
+        self.evict();
+        Ok(frame_number)
+    }
+
+    pub fn get(&self, frame_number: u64) -> Result<Option<StoredPacket>, Error> {
+        let index = self.segments.partition_point(|segment| segment.first_frame <= frame_number);
+        let Some(segment) = index.checked_sub(1).and_then(|index| self.segments.get(index)) else {
+            return Ok(None);
+        };
+        let Some(offset) = segment.offsets.get((frame_number - segment.first_frame) as usize) else {
+            return Ok(None);
+        };
+
+        let mut reader = segment.reader.borrow_mut();
+        let file = match reader.as_mut() {
+            Some(file) => file,
+            None => reader.insert(File::open(&segment.path)?),
+        };
+        file.seek(SeekFrom::Start(*offset))?;
+        read_record(file, frame_number).map(Some)
+    }
+
+    // Every retained frame in order, read segment by segment
+    pub fn iter(&self) -> impl Iterator<Item = Result<StoredPacket, Error>> + '_ {
+        self.segments.iter().flat_map(|segment| {
+            let reader = File::open(&segment.path).and_then(|mut file| {
+                file.seek(SeekFrom::Start(PCAP_GLOBAL_HEADER_LEN))?;
+                Ok(BufReader::new(file))
+            });
+            let first_frame = segment.first_frame;
+            let count = segment.offsets.len() as u64;
+            SegmentIter { reader, next: 0, count, first_frame }
+        })
+    }
+
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.writer = None;
+        self.segments.clear();
+        self.next_frame = 1;
+        remove_segments(&self.dir)
+    }
+
+    fn start_segment(&mut self) -> Result<(), Error> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!("{}{:06}.pcap", SEGMENT_PREFIX, self.next_segment));
+        self.next_segment += 1;
+
+        let mut file = OpenOptions::new().create(true).write(true).truncate(true).open(&path)?;
+        let mut header = Vec::with_capacity(PCAP_GLOBAL_HEADER_LEN as usize);
+        header.extend_from_slice(&PCAP_MAGIC.to_le_bytes());
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&4u16.to_le_bytes());
+        header.extend_from_slice(&0i32.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&SNAPLEN.to_le_bytes());
+        header.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+        file.write_all(&header)?;
+
+        self.writer = Some(file);
+        self.segments.push(Segment {
+            path,
+            first_frame: self.next_frame,
+            offsets: Vec::new(),
+            bytes: PCAP_GLOBAL_HEADER_LEN,
+            last_ts: 0,
+            reader: RefCell::new(None),
+        });
+        Ok(())
+    }
+
+    fn over_limit(&self) -> bool {
+        if let Some(max_packets) = self.config.max_packets {
+            if self.len() > max_packets {
+                return true;
+            }
+        }
+        if let Some(max_bytes) = self.config.max_bytes {
+            if self.bytes() > max_bytes {
+                return true;
+            }
+        }
+        if let (Some(max_age), Some(oldest), Some(newest)) =
+            (self.config.max_age_secs, self.segments.first(), self.segments.last())
+        {
+            // A segment is only too old once its newest packet is
+            if newest.last_ts - oldest.last_ts > max_age {
+                return true;
+            }
+        }
+        false
+    }
+
+    // Drops whole segments from the front, the segment being written is always kept
+    fn evict(&mut self) {
+        while self.segments.len() > 1 && self.over_limit() {
+            // Closes the cached reader before the file goes
+            let Segment { path, .. } = self.segments.remove(0);
+            if let Err(e) = fs::remove_file(&path) {
+                eprintln!("Failed to remove segment {:?}: {:?}", path, e);
+            }
+        }
+    }
+}
+
+impl Default for RingBuffer {
+    fn default() -> Self {
+        RingBuffer::new(std::env::temp_dir().join("metal-sniffer"), RingBufferConfig::default())
+    }
+}
+
+impl Drop for RingBuffer {
+    fn drop(&mut self) {
+        let _ = remove_segments(&self.dir);
+    }
+}
+
+struct SegmentIter {
+    reader: Result<BufReader<File>, Error>,
+    next: u64,
+    count: u64,
+    first_frame: u64,
+}
+
+impl Iterator for SegmentIter {
+    type Item = Result<StoredPacket, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.count {
+            return None;
+        }
+        let frame_number = self.first_frame + self.next;
+        self.next += 1;
+        match self.reader.as_mut() {
+            Ok(reader) => Some(read_record(reader, frame_number)),
+            Err(e) => {
+                // Report a missing segment once instead of for every frame in it
+                self.next = self.count;
+                Some(Err(Error::new(e.kind(), e.to_string())))
+            }
+        }
+    }
+}
+
+fn read_record(reader: &mut impl Read, frame_number: u64) -> Result<StoredPacket, Error> {
+    let mut header = [0u8; PCAP_RECORD_HEADER_LEN as usize];
+    reader.read_exact(&mut header)?;
+    let field = |i: usize| u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]);
+    let caplen = field(8);
+    if caplen > SNAPLEN {
+        return Err(Error::new(ErrorKind::InvalidData, "Corrupt segment record"));
+    }
+
+    let mut data = vec![0u8; caplen as usize];
+    reader.read_exact(&mut data)?;
+    Ok(StoredPacket {
+        frame_number,
+        ts_sec: field(0) as i64,
+        ts_usec: field(4),
+        len: field(12),
+        data,
+    })
+}
+
+fn remove_segments(dir: &Path) -> Result<(), Error> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_segment = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| name.starts_with(SEGMENT_PREFIX) && name.ends_with(".pcap"))
+            .unwrap_or(false);
+        if is_segment {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(name: &str) -> RingBuffer {
+        let dir = std::env::temp_dir().join(format!("metal-sniffer-ring-{}-{}", name, std::process::id()));
+        let mut ring = RingBuffer::new(dir.clone(), RingBufferConfig::default());
+        ring.open(dir).unwrap();
+        ring
+    }
+
+    #[test]
+    fn oversized_frames_are_truncated_and_read_back() {
+        let mut ring = ring("oversized");
+        let big = vec![0xab; SNAPLEN as usize + 1000];
+        ring.push(1, 0, big.len() as u32, &big).unwrap();
+        ring.push(2, 0, 3, &[1, 2, 3]).unwrap();
+
+        let first = ring.get(1).unwrap().unwrap();
+        assert_eq!(first.data.len(), SNAPLEN as usize);
+        assert_eq!(first.len as usize, big.len());
+        assert_eq!(ring.get(2).unwrap().unwrap().data, vec![1, 2, 3]);
+
+        let all: Vec<StoredPacket> = ring.iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1].frame_number, 2);
+    }
+
+    #[test]
+    fn cached_reader_sees_later_frames() {
+        let mut ring = ring("cached");
+        ring.push(1, 10, 2, &[1, 2]).unwrap();
+        assert_eq!(ring.get(1).unwrap().unwrap().data, vec![1, 2]);
+        ring.push(2, 20, 2, &[3, 4]).unwrap();
+        let second = ring.get(2).unwrap().unwrap();
+        assert_eq!((second.ts_sec, second.ts_usec, second.data), (2, 20, vec![3, 4]));
+        assert!(ring.get(3).unwrap().is_none());
+    }
+
+    #[test]
+    fn evicted_segments_are_removed() {
+        let mut ring = ring("evict");
+        ring.set_config(RingBufferConfig { segment_bytes: 1, max_packets: Some(2), max_bytes: None, max_age_secs: None });
+        for i in 0..5u8 {
+            ring.push(i as i64, 0, 1, &[i]).unwrap();
+            ring.get(ring.last_frame().unwrap()).unwrap();
+        }
+        assert_eq!(ring.first_frame(), Some(4));
+        assert!(ring.get(1).unwrap().is_none());
+        assert_eq!(ring.get(5).unwrap().unwrap().data, vec![4]);
+        let files = fs::read_dir(&ring.dir).unwrap().count();
+        assert_eq!(files, 2);
+    }
+}
@@ -1,4 +1,4 @@
-import React, { useState, useCallback, useMemo } from "react";
+import React, { useState, useCallback, useMemo, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import ResizableFooter from "./components/resizable-footer";
 import { FilterInput } from "./components/filter-input";
@@ -8,6 +8,27 @@ import { PacketCard } from "./components/packetCard";
 import { EasyPacketCard } from "./components/easyPacketCard";
 import { Header } from "./components/header";
 import { GroupView } from "./components/groupView";
+import { DecodeAsEditor } from "./components/decode-as-editor";
+
+// How close to the bottom of the page the next page of filter results is fetched
+const SCROLL_MARGIN = 400;
+
+// Adds packets to the source/destination groups without touching the sets already in `groups`
+const groupPackets = (groups, packets) => {
+  const grouped = { ...groups };
+  const copied = new Set();
+  packets.forEach((packet) => {
+    if (packet && packet["Layer 1"]) {
+      const tup = [packet["Layer 1"]["Source IP"], packet["Layer 1"]["Destination IP"]].toString();
+      if (!copied.has(tup)) {
+        grouped[tup] = new Set(grouped[tup] ?? []);
+        copied.add(tup);
+      }
+      grouped[tup].add(packet);
+    }
+  });
+  return grouped;
+};
 
 function App() {
   const [isSwitch, setIsSwitch] = useState(false);
@@ -20,7 +41,9 @@ function App() {
     filterActiveData,
     toggleCapture,
     setPacketData,
-    setFilterActiveData
+    setFilterActiveData,
+    showFilterResults,
+    loadOlderFilteredPackets
   } = usePacketCapture(currentFilter);
 
   const {
@@ -37,34 +60,44 @@ function App() {
     if (newFilter.trim().length <= 0) {
       setFilterActiveData([]);
       setFilterActiveFilteredData({});
+      await invoke("clear_packet_filter");
     }
 
     if (isActive) {
       await invoke("stop_packet_capture");
       setPacketData([]);
+      await invoke("clear_packets");
       await invoke("start_packet_capture");
     }
 
     if (newFilter.trim()) {
-      const filteredPackets = await invoke("filter_packets", {
-        packets: packetData,
-        filter: newFilter
-      });
-      setFilterActiveData(filteredPackets);
-      
-      const newFilteredFilteredData = {};
-      filteredPackets.forEach((packet) => {
-        if (packet && packet["Layer 1"]) {
-          const tup = [packet["Layer 1"]["Source IP"], packet["Layer 1"]["Destination IP"]].toString();
-          if (!newFilteredFilteredData[tup]) {
-            newFilteredFilteredData[tup] = new Set();
-          }
-          newFilteredFilteredData[tup].add(packet);
-        }
-      });
-      setFilterActiveFilteredData(newFilteredFilteredData);
+      // Packets live in the Rust side store, only the newest page of matches comes back
+      // and older ones are paged in as the list is scrolled
+      const counts = await invoke("filter_packets", { filter: newFilter });
+      const filteredPackets = await showFilterResults(counts.visible);
+      setFilterActiveFilteredData(groupPackets({}, filteredPackets));
     }
-  }, [isActive, packetData]);
+  }, [isActive, showFilterResults]);
+
+  const loadOlderPackets = useCallback(async () => {
+    const olderPackets = await loadOlderFilteredPackets();
+    if (olderPackets.length > 0) {
+      setFilterActiveFilteredData(prev => groupPackets(prev, olderPackets));
+    }
+  }, [loadOlderFilteredPackets]);
+
+  useEffect(() => {
+    if (!currentFilter) {
+      return;
+    }
+    const handleScroll = () => {
+      if (window.innerHeight + window.scrollY >= document.documentElement.scrollHeight - SCROLL_MARGIN) {
+        loadOlderPackets();
+      }
+    };
+    window.addEventListener("scroll", handleScroll);
+    return () => window.removeEventListener("scroll", handleScroll);
+  }, [currentFilter, loadOlderPackets]);
 
   const handleModeSwitch = useCallback(() => {
     setIsSwitch(prev => !prev);
@@ -83,6 +116,17 @@ function App() {
     return currentFilter.length > 0 ? filterActiveData : packetData;
   }, [packetData, filterActiveData, isSwitch, currentGroupView, currentFilter]);
 
+  // Rows are replaced when they are fetched again (after a decode as change), keep the
+  // packet open in the footer pointing at its fresh dissection
+  useEffect(() => {
+    setCurrentPacket(prev => {
+      if (prev?.frame_number === undefined) {
+        return prev;
+      }
+      return visiblePackets.find(packet => packet?.frame_number === prev.frame_number) ?? prev;
+    });
+  }, [visiblePackets]);
+
   return (
     <div className="flex flex-col min-h-screen bg-gray-100">
       <div className="flex-grow p-8">
@@ -99,6 +143,7 @@ function App() {
             onFilterChange={handleFilterChange}
             disabled={isActive}
           />
+          <DecodeAsEditor />
           {currentFilter && (
             <div className="mt-2 text-sm text-gray-600">
               Current filter: {currentFilter}
@@ -0,0 +1,168 @@
+import React, { useEffect, useState, useCallback } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+
+const DISSECTORS = ["DNS", "MDNS", "HTTP", "HTTPS", "QUIC", "DHCP", "DHCPV6", "Raw"];
+
+const EMPTY_FORM = {
+  transport: "TCP",
+  kind: "port",
+  port: "",
+  aIp: "",
+  aPort: "",
+  bIp: "",
+  bPort: "",
+  dissector: "HTTP"
+};
+
+const parsePort = (text) => {
+  const port = Number(text);
+  return Number.isInteger(port) && port >= 0 && port <= 65535 && text.trim() !== "" ? port : null;
+};
+
+const describeTarget = (target) => {
+  if (target.port !== undefined) {
+    return `port ${target.port}`;
+  }
+  const { a_ip, a_port, b_ip, b_port } = target.flow;
+  return `${a_ip}:${a_port} <-> ${b_ip}:${b_port}`;
+};
+
+// Forces a port or a single conversation to a dissector; flow rules win over port rules,
+// which win over the built-in port tables
+export const DecodeAsEditor = () => {
+  const [open, setOpen] = useState(false);
+  const [rules, setRules] = useState([]);
+  const [form, setForm] = useState(EMPTY_FORM);
+  const [error, setError] = useState('');
+
+  const loadRules = useCallback(async () => {
+    try {
+      setRules(await invoke("get_decode_as"));
+    } catch (err) {
+      console.error('Decode as error:', err);
+    }
+  }, []);
+
+  useEffect(() => {
+    loadRules();
+    let unlisten;
+    listen("decode-as-changed", () => loadRules()).then(fn => { unlisten = fn; });
+    return () => unlisten && unlisten();
+  }, [loadRules]);
+
+  const update = (key) => (e) => setForm(prev => ({ ...prev, [key]: e.target.value }));
+
+  const buildTarget = () => {
+    if (form.kind === "port") {
+      const port = parsePort(form.port);
+      return port === null ? null : { port };
+    }
+    const aPort = parsePort(form.aPort);
+    const bPort = parsePort(form.bPort);
+    if (aPort === null || bPort === null || !form.aIp.trim() || !form.bIp.trim()) {
+      return null;
+    }
+    return { flow: { a_ip: form.aIp.trim(), a_port: aPort, b_ip: form.bIp.trim(), b_port: bPort } };
+  };
+
+  const handleSubmit = async (e) => {
+    e.preventDefault();
+    const target = buildTarget();
+    if (!target) {
+      setError(form.kind === "port" ? 'Enter a port between 0 and 65535' : 'Enter both addresses and ports');
+      return;
+    }
+    try {
+      await invoke("set_decode_as", { rule: { transport: form.transport, target, dissector: form.dissector } });
+      setError('');
+      setForm(prev => ({ ...EMPTY_FORM, transport: prev.transport, kind: prev.kind, dissector: prev.dissector }));
+    } catch (err) {
+      setError(err.toString());
+    }
+  };
+
+  const removeRule = async (rule) => {
+    try {
+      await invoke("remove_decode_as", { transport: rule.transport, target: rule.target });
+      setError('');
+    } catch (err) {
+      setError(err.toString());
+    }
+  };
+
+  const inputClass = "px-2 py-1 border border-gray-300 rounded-md focus:outline-none focus:ring-2 focus:ring-blue-500";
+
+  return (
+    <div className="ml-4">
+      <button
+        onClick={() => setOpen(prev => !prev)}
+        className="px-4 py-2 bg-gray-200 rounded hover:bg-gray-300"
+      >
+        Decode As {rules.length > 0 && `(${rules.length})`}
+      </button>
+
+      {open && (
+        <div className="absolute mt-2 w-[36rem] p-4 bg-white border border-gray-300 rounded-md shadow-lg z-20">
+          <form onSubmit={handleSubmit} className="flex flex-wrap items-center gap-2">
+            <select value={form.transport} onChange={update("transport")} className={inputClass}>
+              <option value="TCP">TCP</option>
+              <option value="UDP">UDP</option>
+            </select>
+            <select value={form.kind} onChange={update("kind")} className={inputClass}>
+              <option value="port">Port</option>
+              <option value="flow">Flow</option>
+            </select>
+            {form.kind === "port" ? (
+              <input value={form.port} onChange={update("port")} placeholder="8080" className={`${inputClass} w-24`} />
+            ) : (
+              <>
+                <input value={form.aIp} onChange={update("aIp")} placeholder="10.0.0.1" className={`${inputClass} w-32`} />
+                <input value={form.aPort} onChange={update("aPort")} placeholder="port" className={`${inputClass} w-20`} />
+                <input value={form.bIp} onChange={update("bIp")} placeholder="10.0.0.2" className={`${inputClass} w-32`} />
+                <input value={form.bPort} onChange={update("bPort")} placeholder="port" className={`${inputClass} w-20`} />
+              </>
+            )}
+            <span className="text-gray-600">as</span>
+            <select value={form.dissector} onChange={update("dissector")} className={inputClass}>
+              {DISSECTORS.map(dissector => (
+                <option key={dissector} value={dissector}>{dissector}</option>
+              ))}
+            </select>
+            <button
+              type="submit"
+              className="px-4 py-1 bg-blue-500 text-white rounded-md hover:bg-blue-600"
+            >
+              Add
+            </button>
+          </form>
+          {error && (
+            <div className="mt-2 text-red-500 text-sm">{error}</div>
+          )}
+
+          {rules.length === 0 ? (
+            <div className="mt-4 text-sm text-gray-500">No rules, packets are decoded by their well known ports</div>
+          ) : (
+            <ul className="mt-4 divide-y divide-gray-200">
+              {rules.map(rule => (
+                <li key={`${rule.transport}-${describeTarget(rule.target)}`} className="flex justify-between items-center py-2">
+                  <span className="text-sm">
+                    {rule.transport} {describeTarget(rule.target)} as <span className="font-medium">{rule.dissector}</span>
+                  </span>
+                  <button
+                    onClick={() => removeRule(rule)}
+                    className="px-2 py-1 text-sm text-red-600 hover:bg-red-50 rounded"
+                  >
+                    Remove
+                  </button>
+                </li>
+              ))}
+            </ul>
+          )}
+        </div>
+      )}
+    </div>
+  );
+};
+
+export default DecodeAsEditor;
@@ -1,40 +1,162 @@
-import { useState, useCallback, useEffect } from 'react';
+import { useState, useCallback, useEffect, useRef } from 'react';
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 
+// Older packets stay in the Rust side ring buffer and can be paged in with get_packets
+const MAX_LIVE_PACKETS = 5000;
+// Filter results are fetched a page at a time, newest first, as the list is scrolled
+const FILTER_PAGE_SIZE = 500;
+
+// The newest `count` packets passing the store's current filter, newest first like the live view
+export const fetchNewestPackets = async (count) => {
+  const { visible } = await invoke("get_packet_count");
+  const offset = Math.max(0, visible - count);
+  return (await invoke("get_packets", { offset, limit: visible - offset })).reverse();
+};
+
 export const usePacketCapture = (currentFilter) => {
   const [packetData, setPacketData] = useState([]);
   const [isActive, setIsActive] = useState(false);
   const [filterActiveData, setFilterActiveData] = useState([]);
+  // View offset of the oldest filter result fetched so far
+  const filterOffset = useRef(0);
+  const loadingOlder = useRef(false);
+
+  // Read by the decode-as-changed listener, which is only subscribed once
+  const shown = useRef({ filtered: false, count: 0 });
+  shown.current = currentFilter.length > 0
+    ? { filtered: true, count: filterActiveData.length }
+    : { filtered: false, count: packetData.length };
 
-  const handleNewPacket = useCallback((newPacket) => {
-    if (!newPacket || !newPacket["Layer 1"]) {
-      console.error("Invalid packet data received");
+  const handleNewBatch = useCallback((batch) => {
+    // Acknowledge straight away, the backend holds off on new batches until we do
+    invoke("ack_packets", { batchId: batch.batch_id });
+    if (batch.skipped > 0) {
+      console.warn(`${batch.skipped} packets were not sent live, fetch them with get_packets`);
+    }
+
+    const newPackets = batch.packets.filter(packet => packet && packet["Layer 1"]).reverse();
+    if (newPackets.length === 0) {
       return;
     }
 
     if (currentFilter.length > 0) {
-      setFilterActiveData(prevData => [newPacket, ...prevData]);
+      setFilterActiveData(prevData => [...newPackets, ...prevData].slice(0, MAX_LIVE_PACKETS));
     } else {
-      setPacketData(prevData => [newPacket, ...prevData]);
+      setPacketData(prevData => [...newPackets, ...prevData].slice(0, MAX_LIVE_PACKETS));
     }
   }, [currentFilter]);
 
+  // The newest page of a freshly applied filter's `visible` results
+  const showFilterResults = useCallback(async (visible) => {
+    const offset = Math.max(0, visible - FILTER_PAGE_SIZE);
+    const packets = (await invoke("get_packets", { offset, limit: visible - offset })).reverse();
+    filterOffset.current = offset;
+    setFilterActiveData(packets);
+    return packets;
+  }, []);
+
+  // The page of filter results before the oldest one shown, empty once they are all loaded
+  const loadOlderFilteredPackets = useCallback(async () => {
+    const end = filterOffset.current;
+    if (end === 0 || loadingOlder.current) {
+      return [];
+    }
+    loadingOlder.current = true;
+    try {
+      const offset = Math.max(0, end - FILTER_PAGE_SIZE);
+      const packets = (await invoke("get_packets", { offset, limit: end - offset })).reverse();
+      filterOffset.current = offset;
+      setFilterActiveData(prevData => [...prevData, ...packets]);
+      return packets;
+    } finally {
+      loadingOlder.current = false;
+    }
+  }, []);
+
   const toggleCapture = useCallback(async () => {
-    if (isActive) {
-      await invoke("stop_packet_capture");
-    } else {
-      await invoke("start_packet_capture");
+    try {
+      if (isActive) {
+        await invoke("stop_packet_capture");
+      } else {
+        await invoke("start_packet_capture");
+      }
+    } catch (error) {
+      // Commands reject with { code, message, hint }
+      console.error(`Capture error (${error.code}): ${error.message}`, error.hint ?? "");
     }
-    setIsActive(prev => !prev);
   }, [isActive]);
 
+  // Streams from a `metal-sniffer-cli --serve` agent instead of a local interface
+  const startRemoteCapture = useCallback(async (address, token) => {
+    try {
+      await invoke("start_packet_capture", { source: { type: "remote", address, token } });
+    } catch (error) {
+      console.error(`Capture error (${error.code}): ${error.message}`, error.hint ?? "");
+    }
+  }, []);
+
+  // A named pipe (or "-" for the app's stdin) carrying pcap/pcapng, e.g. from `tcpdump -w`
+  const startPipeCapture = useCallback(async (path) => {
+    try {
+      await invoke("start_packet_capture", { source: { type: "pipe", path } });
+    } catch (error) {
+      console.error(`Capture error (${error.code}): ${error.message}`, error.hint ?? "");
+    }
+  }, []);
+
+  // The backend reports every session transition, including captures that fail on their own
+  useEffect(() => {
+    let unlisten;
+    listen("capture-state", (event) => {
+      const { status } = event.payload;
+      setIsActive(status === "starting" || status === "running");
+    }).then(fn => { unlisten = fn; });
+
+    return () => unlisten && unlisten();
+  }, []);
+
+  // Errors that end a running capture (e.g. the interface going away) only arrive as events
+  useEffect(() => {
+    let unlisten;
+    listen("capture-error", (event) => {
+      const { code, message, hint } = event.payload;
+      console.error(`Capture error (${code}): ${message}`, hint ?? "");
+    }).then(fn => { unlisten = fn; });
+
+    return () => unlisten && unlisten();
+  }, []);
+
+  // Stored packets are dissected when they are read, so the rows on screen are fetched
+  // again to show them with the new mapping
+  useEffect(() => {
+    let unlisten;
+    listen("decode-as-changed", async () => {
+      const { filtered, count } = shown.current;
+      if (count === 0) {
+        return;
+      }
+      try {
+        const packets = await fetchNewestPackets(count);
+        if (filtered) {
+          setFilterActiveData(packets);
+        } else {
+          setPacketData(packets);
+        }
+      } catch (error) {
+        console.error(`Capture error (${error.code}): ${error.message}`, error.hint ?? "");
+      }
+    }).then(fn => { unlisten = fn; });
+
+    return () => unlisten && unlisten();
+  }, []);
+
   useEffect(() => {
     let isSubscribed = true;
     const setupListener = async () => {
-      const unlisten = await listen("packet-captured", (event) => {
+      const unlisten = await listen("packets-captured", (event) => {
         if (isSubscribed) {
-          handleNewPacket(event.payload);
+          handleNewBatch(event.payload);
         }
       });
       
@@ -45,14 +167,18 @@ export const usePacketCapture = (currentFilter) => {
     };
 
     setupListener();
-  }, [handleNewPacket]);
+  }, [handleNewBatch]);
 
   return {
     packetData,
     isActive,
     filterActiveData,
     toggleCapture,
+    startRemoteCapture,
+    startPipeCapture,
     setPacketData,
-    setFilterActiveData
+    setFilterActiveData,
+    showFilterResults,
+    loadOlderFilteredPackets
   };
 };
\ No newline at end of file